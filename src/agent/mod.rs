@@ -0,0 +1,97 @@
+pub mod observer;
+pub mod runner;
+pub mod stopping;
+
+use std::collections::BTreeMap;
+
+use rand::Rng;
+
+use crate::{
+    algorithms::TabularAlgorithm,
+    mdp::{GenericAction, GenericMdp, GenericState, Reward},
+};
+
+// everything an agent gets to see about a single step
+#[derive(Clone, Debug)]
+pub struct Observation<S: GenericState, A: GenericAction> {
+    pub state: S,
+    pub action: A,
+    pub reward: Reward,
+    pub next_state: S,
+    pub next_possible_actions: Vec<A>,
+    pub terminal: bool,
+    pub discount_factor: f64,
+}
+
+// online interface shared by all tabular learners, the Runner drives it on any GenericMdp
+pub trait Agent<S: GenericState, A: GenericAction> {
+    fn begin_episode<R: Rng>(&mut self, _rng: &mut R) {}
+
+    fn act<R: Rng>(&mut self, state: S, possible_actions: &[A], rng: &mut R) -> Option<A>;
+
+    // returns the td error of the observation if the agent has one
+    fn observe<R: Rng>(&mut self, observation: &Observation<S, A>, rng: &mut R) -> Option<f64>;
+
+    fn end_episode<R: Rng>(&mut self, _rng: &mut R) {}
+
+    fn q_map(&self) -> &BTreeMap<(S, A), f64>;
+}
+
+// pairs an algorithm with the q_map it learns and its memory of the current episode
+pub struct TabularAgent<G: TabularAlgorithm<S, A>, S: GenericState, A: GenericAction> {
+    pub algorithm: G,
+    pub q_map: BTreeMap<(S, A), f64>,
+    memory: G::Memory,
+}
+
+impl<G: TabularAlgorithm<S, A>, S: GenericState, A: GenericAction> TabularAgent<G, S, A> {
+    pub fn new(algorithm: G, q_map: BTreeMap<(S, A), f64>) -> Self {
+        Self {
+            algorithm,
+            q_map,
+            memory: G::Memory::default(),
+        }
+    }
+
+    // every state action pair of the mdp starts at 0
+    pub fn from_mdp<M: GenericMdp<S, A>>(algorithm: G, mdp: &M) -> Self {
+        let q_map = mdp
+            .get_all_state_actions()
+            .iter()
+            .map(|state_action| (*state_action, 0.0))
+            .collect();
+        Self::new(algorithm, q_map)
+    }
+
+    pub fn into_q_map(self) -> BTreeMap<(S, A), f64> {
+        self.q_map
+    }
+}
+
+impl<G: TabularAlgorithm<S, A>, S: GenericState, A: GenericAction> Agent<S, A>
+    for TabularAgent<G, S, A>
+{
+    fn begin_episode<R: Rng>(&mut self, rng: &mut R) {
+        self.algorithm
+            .begin_episode(&mut self.memory, &mut self.q_map, rng);
+    }
+
+    fn act<R: Rng>(&mut self, state: S, possible_actions: &[A], rng: &mut R) -> Option<A> {
+        self.algorithm
+            .act(&mut self.memory, &self.q_map, state, possible_actions, rng)
+    }
+
+    fn observe<R: Rng>(&mut self, observation: &Observation<S, A>, rng: &mut R) -> Option<f64> {
+        self.algorithm
+            .observe(&mut self.memory, &mut self.q_map, observation, rng)
+    }
+
+    fn end_episode<R: Rng>(&mut self, rng: &mut R) {
+        self.algorithm
+            .end_episode(&mut self.memory, &mut self.q_map, rng);
+    }
+
+    fn q_map(&self) -> &BTreeMap<(S, A), f64> {
+        &self.q_map
+    }
+}
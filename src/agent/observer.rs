@@ -0,0 +1,216 @@
+use std::{collections::BTreeMap, path::Path};
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+
+use crate::{
+    eval::evaluate_greedy_policy,
+    mdp::{GenericAction, GenericMdp, GenericState},
+};
+
+use super::{runner::EpisodeStats, Observation};
+
+// hooks called by the Runner during training, all of them default to doing nothing
+pub trait Observer<S: GenericState, A: GenericAction> {
+    // td_error is what the agent returned for the observation, see Agent::observe
+    fn on_step(&mut self, _observation: &Observation<S, A>, _td_error: Option<f64>) {}
+
+    fn on_episode_end(&mut self, _stats: &EpisodeStats, _q_map: &BTreeMap<(S, A), f64>) {}
+
+    fn on_training_end(&mut self, _q_map: &BTreeMap<(S, A), f64>) {}
+}
+
+impl<S: GenericState, A: GenericAction> Observer<S, A> for () {}
+
+impl<S: GenericState, A: GenericAction, O: Observer<S, A>> Observer<S, A> for &mut O {
+    fn on_step(&mut self, observation: &Observation<S, A>, td_error: Option<f64>) {
+        (**self).on_step(observation, td_error);
+    }
+
+    fn on_episode_end(&mut self, stats: &EpisodeStats, q_map: &BTreeMap<(S, A), f64>) {
+        (**self).on_episode_end(stats, q_map);
+    }
+
+    fn on_training_end(&mut self, q_map: &BTreeMap<(S, A), f64>) {
+        (**self).on_training_end(q_map);
+    }
+}
+
+// combines observers, nest tuples for more than two
+impl<S: GenericState, A: GenericAction, O1: Observer<S, A>, O2: Observer<S, A>> Observer<S, A>
+    for (O1, O2)
+{
+    fn on_step(&mut self, observation: &Observation<S, A>, td_error: Option<f64>) {
+        self.0.on_step(observation, td_error);
+        self.1.on_step(observation, td_error);
+    }
+
+    fn on_episode_end(&mut self, stats: &EpisodeStats, q_map: &BTreeMap<(S, A), f64>) {
+        self.0.on_episode_end(stats, q_map);
+        self.1.on_episode_end(stats, q_map);
+    }
+
+    fn on_training_end(&mut self, q_map: &BTreeMap<(S, A), f64>) {
+        self.0.on_training_end(q_map);
+        self.1.on_training_end(q_map);
+    }
+}
+
+// records the stats of every episode
+#[derive(Clone, Debug, Default)]
+pub struct LearningCurve {
+    pub episodes: Vec<EpisodeStats>,
+}
+
+impl LearningCurve {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn total_rewards(&self) -> Vec<f64> {
+        self.episodes
+            .iter()
+            .map(|stats| stats.total_reward)
+            .collect()
+    }
+
+    // mean total reward over the last window episodes, shorter at the start
+    pub fn moving_average(&self, window: usize) -> Vec<f64> {
+        let rewards = self.total_rewards();
+        (0..rewards.len())
+            .map(|i| {
+                let start = (i + 1).saturating_sub(window.max(1));
+                rewards[start..=i].iter().sum::<f64>() / (i + 1 - start) as f64
+            })
+            .collect()
+    }
+
+    pub fn to_csv(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let mut csv_writer = csv::Writer::from_path(path)?;
+        csv_writer.write_record([
+            "episode",
+            "steps",
+            "total_reward",
+            "discounted_return",
+            "terminated",
+        ])?;
+        for stats in self.episodes.iter() {
+            csv_writer.serialize((
+                stats.episode,
+                stats.steps,
+                stats.total_reward,
+                stats.discounted_return,
+                stats.terminated,
+            ))?;
+        }
+        csv_writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<S: GenericState, A: GenericAction> Observer<S, A> for LearningCurve {
+    fn on_episode_end(&mut self, stats: &EpisodeStats, _q_map: &BTreeMap<(S, A), f64>) {
+        self.episodes.push(*stats);
+    }
+}
+
+// tracks the absolute td errors of the real transitions per episode, planning updates are not
+// included. Steps without a td error, e.g. of Monte Carlo, are skipped and an episode without any
+// shows up as 0.
+#[derive(Clone, Debug, Default)]
+pub struct TdErrorTracker {
+    pub mean_abs: Vec<f64>,
+    pub max_abs: Vec<f64>,
+    sum: f64,
+    max: f64,
+    count: usize,
+}
+
+impl TdErrorTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<S: GenericState, A: GenericAction> Observer<S, A> for TdErrorTracker {
+    fn on_step(&mut self, _observation: &Observation<S, A>, td_error: Option<f64>) {
+        let Some(td_error) = td_error else {
+            return;
+        };
+        let error = td_error.abs();
+        self.sum += error;
+        self.max = self.max.max(error);
+        self.count += 1;
+    }
+
+    fn on_episode_end(&mut self, _stats: &EpisodeStats, _q_map: &BTreeMap<(S, A), f64>) {
+        let mean = if self.count > 0 {
+            self.sum / self.count as f64
+        } else {
+            0.0
+        };
+        self.mean_abs.push(mean);
+        self.max_abs.push(self.max);
+        self.sum = 0.0;
+        self.max = 0.0;
+        self.count = 0;
+    }
+}
+
+// evaluates the greedy policy every few episodes and after training. Uses its own rng so the
+// evaluation does not change the course of training.
+pub struct GreedyEvaluation<'a, M> {
+    mdp: &'a M,
+    every: usize,
+    episodes: usize,
+    max_steps: usize,
+    rng: ChaCha20Rng,
+    // (episodes trained, average reward)
+    pub results: Vec<(usize, f64)>,
+    trained: usize,
+}
+
+impl<'a, M> GreedyEvaluation<'a, M> {
+    pub fn new(mdp: &'a M, every: usize, episodes: usize, max_steps: usize, seed: u64) -> Self {
+        Self {
+            mdp,
+            every: every.max(1),
+            episodes,
+            max_steps,
+            rng: ChaCha20Rng::seed_from_u64(seed),
+            results: vec![],
+            trained: 0,
+        }
+    }
+
+    fn evaluate<S: GenericState, A: GenericAction>(&mut self, q_map: &BTreeMap<(S, A), f64>)
+    where
+        M: GenericMdp<S, A>,
+    {
+        let avg_reward = evaluate_greedy_policy(
+            self.mdp,
+            q_map,
+            self.episodes,
+            self.max_steps,
+            &mut self.rng,
+        );
+        self.results.push((self.trained, avg_reward));
+    }
+}
+
+impl<S: GenericState, A: GenericAction, M: GenericMdp<S, A>> Observer<S, A>
+    for GreedyEvaluation<'_, M>
+{
+    fn on_episode_end(&mut self, _stats: &EpisodeStats, q_map: &BTreeMap<(S, A), f64>) {
+        self.trained += 1;
+        if self.trained.is_multiple_of(self.every) {
+            self.evaluate(q_map);
+        }
+    }
+
+    fn on_training_end(&mut self, q_map: &BTreeMap<(S, A), f64>) {
+        if self.results.last().map(|(trained, _)| *trained) != Some(self.trained) {
+            self.evaluate(q_map);
+        }
+    }
+}
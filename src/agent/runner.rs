@@ -0,0 +1,310 @@
+use std::{collections::BTreeMap, mem};
+
+use rand::Rng;
+
+use crate::{
+    algorithms::TabularAlgorithm,
+    mdp::{GenericAction, GenericMdp, GenericState},
+};
+
+use super::{
+    observer::Observer,
+    stopping::{StopReason, StoppingCriterion},
+    Agent, Observation, TabularAgent,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EpisodeStats {
+    pub episode: usize,
+    pub steps: usize,
+    pub total_reward: f64,
+    pub discounted_return: f64,
+    // the episode ended in a terminal state instead of running into max_steps
+    pub terminated: bool,
+}
+
+// the single episode loop, drives any agent on any mdp
+#[derive(Clone, Copy, Debug)]
+pub struct Runner {
+    max_steps: usize,
+}
+
+impl Runner {
+    pub fn new(max_steps: usize) -> Self {
+        Self { max_steps }
+    }
+
+    pub fn get_max_steps(&self) -> usize {
+        self.max_steps
+    }
+
+    pub fn run<M, S, A, G, R>(
+        &self,
+        mdp: &M,
+        agent: &mut G,
+        episodes: usize,
+        rng: &mut R,
+    ) -> Vec<EpisodeStats>
+    where
+        M: GenericMdp<S, A>,
+        S: GenericState,
+        A: GenericAction,
+        G: Agent<S, A>,
+        R: Rng,
+    {
+        self.run_observed(mdp, agent, episodes, rng, &mut ())
+    }
+
+    // same as run, but reports every step and episode to the observer
+    pub fn run_observed<M, S, A, G, R, O>(
+        &self,
+        mdp: &M,
+        agent: &mut G,
+        episodes: usize,
+        rng: &mut R,
+        observer: &mut O,
+    ) -> Vec<EpisodeStats>
+    where
+        M: GenericMdp<S, A>,
+        S: GenericState,
+        A: GenericAction,
+        G: Agent<S, A>,
+        R: Rng,
+        O: Observer<S, A>,
+    {
+        self.run_until(mdp, agent, episodes, rng, &mut (), observer)
+            .0
+    }
+
+    // runs until the criterion stops it or max_episodes are done and reports why it stopped
+    pub fn run_until<M, S, A, G, R, C, O>(
+        &self,
+        mdp: &M,
+        agent: &mut G,
+        max_episodes: usize,
+        rng: &mut R,
+        criterion: &mut C,
+        observer: &mut O,
+    ) -> (Vec<EpisodeStats>, StopReason)
+    where
+        M: GenericMdp<S, A>,
+        S: GenericState,
+        A: GenericAction,
+        G: Agent<S, A>,
+        R: Rng,
+        C: StoppingCriterion<S, A>,
+        O: Observer<S, A>,
+    {
+        let mut stats = vec![];
+        let mut reason = StopReason::MaxEpisodes;
+        criterion.start();
+        for episode in 0..max_episodes {
+            let episode_stats = self.observed_episode(mdp, agent, episode, rng, observer);
+            observer.on_episode_end(&episode_stats, agent.q_map());
+            stats.push(episode_stats);
+            if let Some(stop) = criterion.check(&episode_stats, agent.q_map()) {
+                reason = stop;
+                break;
+            }
+        }
+        observer.on_training_end(agent.q_map());
+        (stats, reason)
+    }
+
+    // trains a tabular algorithm on the q_map with observers, TabularAlgorithm::run_with_q_map
+    // is this without observers. The algorithm is handed back afterwards since some of them
+    // carry state between runs.
+    pub fn run_with_q_map<M, S, A, G, R, O>(
+        &self,
+        algorithm: G,
+        mdp: &M,
+        episodes: usize,
+        rng: &mut R,
+        q_map: &mut BTreeMap<(S, A), f64>,
+        observer: &mut O,
+    ) -> G
+    where
+        M: GenericMdp<S, A>,
+        S: GenericState,
+        A: GenericAction,
+        G: TabularAlgorithm<S, A>,
+        R: Rng,
+        O: Observer<S, A>,
+    {
+        self.run_with_q_map_until(algorithm, mdp, episodes, rng, q_map, &mut (), observer)
+            .0
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn run_with_q_map_until<M, S, A, G, R, C, O>(
+        &self,
+        algorithm: G,
+        mdp: &M,
+        max_episodes: usize,
+        rng: &mut R,
+        q_map: &mut BTreeMap<(S, A), f64>,
+        criterion: &mut C,
+        observer: &mut O,
+    ) -> (G, StopReason)
+    where
+        M: GenericMdp<S, A>,
+        S: GenericState,
+        A: GenericAction,
+        G: TabularAlgorithm<S, A>,
+        R: Rng,
+        C: StoppingCriterion<S, A>,
+        O: Observer<S, A>,
+    {
+        let mut agent = TabularAgent::new(algorithm, mem::take(q_map));
+        let (_, reason) = self.run_until(mdp, &mut agent, max_episodes, rng, criterion, observer);
+        *q_map = agent.q_map;
+        (agent.algorithm, reason)
+    }
+
+    // one continuous run of the given number of steps for continuing tasks. Unlike run it ignores
+    // max_steps and only starts over from an initial state once a terminal state is reached, the
+    // stats cover the whole run.
+    pub fn run_continuing<M, S, A, G, R, O>(
+        &self,
+        mdp: &M,
+        agent: &mut G,
+        steps: usize,
+        rng: &mut R,
+        observer: &mut O,
+    ) -> EpisodeStats
+    where
+        M: GenericMdp<S, A>,
+        S: GenericState,
+        A: GenericAction,
+        G: Agent<S, A>,
+        R: Rng,
+        O: Observer<S, A>,
+    {
+        let mut stats = EpisodeStats::default();
+        let mut discount = 1.0;
+
+        agent.begin_episode(rng);
+
+        let mut current_state = mdp.get_initial_state(rng);
+        while stats.steps < steps && !mdp.is_terminal(current_state) {
+            let Some((next_state, reward)) =
+                Self::observed_step(mdp, agent, current_state, rng, observer)
+            else {
+                break;
+            };
+
+            stats.steps += 1;
+            stats.total_reward += reward;
+            stats.discounted_return += discount * reward;
+            discount *= mdp.get_discount_factor();
+
+            current_state = next_state;
+            if mdp.is_terminal(current_state) && stats.steps < steps {
+                agent.end_episode(rng);
+                agent.begin_episode(rng);
+                current_state = mdp.get_initial_state(rng);
+                discount = 1.0;
+            }
+        }
+        stats.terminated = mdp.is_terminal(current_state);
+
+        agent.end_episode(rng);
+        observer.on_episode_end(&stats, agent.q_map());
+        observer.on_training_end(agent.q_map());
+
+        stats
+    }
+
+    pub fn run_episode<M, S, A, G, R>(&self, mdp: &M, agent: &mut G, rng: &mut R) -> EpisodeStats
+    where
+        M: GenericMdp<S, A>,
+        S: GenericState,
+        A: GenericAction,
+        G: Agent<S, A>,
+        R: Rng,
+    {
+        self.observed_episode(mdp, agent, 0, rng, &mut ())
+    }
+
+    fn observed_episode<M, S, A, G, R, O>(
+        &self,
+        mdp: &M,
+        agent: &mut G,
+        episode: usize,
+        rng: &mut R,
+        observer: &mut O,
+    ) -> EpisodeStats
+    where
+        M: GenericMdp<S, A>,
+        S: GenericState,
+        A: GenericAction,
+        G: Agent<S, A>,
+        R: Rng,
+        O: Observer<S, A>,
+    {
+        let mut stats = EpisodeStats {
+            episode,
+            ..Default::default()
+        };
+        let mut discount = 1.0;
+
+        agent.begin_episode(rng);
+
+        let mut current_state = mdp.get_initial_state(rng);
+        while !mdp.is_terminal(current_state) && stats.steps < self.max_steps {
+            let Some((next_state, reward)) =
+                Self::observed_step(mdp, agent, current_state, rng, observer)
+            else {
+                break;
+            };
+
+            stats.steps += 1;
+            stats.total_reward += reward;
+            stats.discounted_return += discount * reward;
+            discount *= mdp.get_discount_factor();
+
+            current_state = next_state;
+        }
+        stats.terminated = mdp.is_terminal(current_state);
+
+        agent.end_episode(rng);
+
+        stats
+    }
+
+    // acts in the state and lets the agent and the observer see the transition, None if no action
+    // is possible
+    fn observed_step<M, S, A, G, R, O>(
+        mdp: &M,
+        agent: &mut G,
+        current_state: S,
+        rng: &mut R,
+        observer: &mut O,
+    ) -> Option<(S, f64)>
+    where
+        M: GenericMdp<S, A>,
+        S: GenericState,
+        A: GenericAction,
+        G: Agent<S, A>,
+        R: Rng,
+        O: Observer<S, A>,
+    {
+        let possible_actions = mdp.get_possible_actions(current_state);
+        let selected_action = agent.act(current_state, &possible_actions, rng)?;
+        let (next_state, reward) = mdp.perform_action((current_state, selected_action), rng);
+
+        let observation = Observation {
+            state: current_state,
+            action: selected_action,
+            reward,
+            next_state,
+            next_possible_actions: mdp.get_possible_actions(next_state),
+            terminal: mdp.is_terminal(next_state),
+            discount_factor: mdp.get_discount_factor(),
+        };
+        let td_error = agent.observe(&observation, rng);
+        observer.on_step(&observation, td_error);
+
+        Some((next_state, reward))
+    }
+}
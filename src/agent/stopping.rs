@@ -0,0 +1,270 @@
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+
+use crate::{
+    eval::evaluate_greedy_policy,
+    mdp::{GenericAction, GenericMdp, GenericState},
+    policies::TabularPolicy,
+};
+
+use super::runner::EpisodeStats;
+
+// why a run stopped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    MaxEpisodes,
+    QConverged,
+    PolicyStable,
+    TargetReached,
+    TimeBudget,
+}
+
+// checked by Runner::run_until after every episode
+pub trait StoppingCriterion<S: GenericState, A: GenericAction> {
+    // called once before the first episode
+    fn start(&mut self) {}
+
+    fn check(&mut self, stats: &EpisodeStats, q_map: &BTreeMap<(S, A), f64>) -> Option<StopReason>;
+}
+
+// never stops, the run ends after the given number of episodes
+impl<S: GenericState, A: GenericAction> StoppingCriterion<S, A> for () {
+    fn check(
+        &mut self,
+        _stats: &EpisodeStats,
+        _q_map: &BTreeMap<(S, A), f64>,
+    ) -> Option<StopReason> {
+        None
+    }
+}
+
+impl<S: GenericState, A: GenericAction, C: StoppingCriterion<S, A>> StoppingCriterion<S, A>
+    for &mut C
+{
+    fn start(&mut self) {
+        (**self).start();
+    }
+
+    fn check(&mut self, stats: &EpisodeStats, q_map: &BTreeMap<(S, A), f64>) -> Option<StopReason> {
+        (**self).check(stats, q_map)
+    }
+}
+
+// stops as soon as one of the criteria does, both are checked every episode
+impl<S, A, C1, C2> StoppingCriterion<S, A> for (C1, C2)
+where
+    S: GenericState,
+    A: GenericAction,
+    C1: StoppingCriterion<S, A>,
+    C2: StoppingCriterion<S, A>,
+{
+    fn start(&mut self) {
+        self.0.start();
+        self.1.start();
+    }
+
+    fn check(&mut self, stats: &EpisodeStats, q_map: &BTreeMap<(S, A), f64>) -> Option<StopReason> {
+        let first = self.0.check(stats, q_map);
+        let second = self.1.check(stats, q_map);
+        first.or(second)
+    }
+}
+
+// the largest change of any q value per episode stayed below tolerance for window episodes
+pub struct QChange<S: GenericState, A: GenericAction> {
+    tolerance: f64,
+    window: usize,
+    prev_q_map: BTreeMap<(S, A), f64>,
+    below: usize,
+}
+
+impl<S: GenericState, A: GenericAction> QChange<S, A> {
+    pub fn new(tolerance: f64, window: usize) -> Self {
+        Self {
+            tolerance,
+            window: window.max(1),
+            prev_q_map: BTreeMap::new(),
+            below: 0,
+        }
+    }
+}
+
+impl<S: GenericState, A: GenericAction> StoppingCriterion<S, A> for QChange<S, A> {
+    fn start(&mut self) {
+        self.prev_q_map.clear();
+        self.below = 0;
+    }
+
+    fn check(
+        &mut self,
+        _stats: &EpisodeStats,
+        q_map: &BTreeMap<(S, A), f64>,
+    ) -> Option<StopReason> {
+        // entries that appear or vanish count as changes from 0
+        let max_change = q_map
+            .iter()
+            .map(|(state_action, q)| (q - self.prev_q_map.get(state_action).unwrap_or(&0.0)).abs())
+            .chain(
+                self.prev_q_map
+                    .iter()
+                    .filter(|(state_action, _)| !q_map.contains_key(state_action))
+                    .map(|(_, q)| q.abs()),
+            )
+            .fold(0.0, f64::max);
+        self.prev_q_map.clone_from(q_map);
+
+        if max_change < self.tolerance {
+            self.below += 1;
+        } else {
+            self.below = 0;
+        }
+        (self.below >= self.window).then_some(StopReason::QConverged)
+    }
+}
+
+// the greedy policy did not change for checks consecutive checks, one check every few episodes
+pub struct PolicyStability<S: GenericState, A: GenericAction> {
+    checks: usize,
+    every: usize,
+    prev_policy: Option<BTreeMap<S, A>>,
+    stable: usize,
+    episodes: usize,
+}
+
+impl<S: GenericState, A: GenericAction> PolicyStability<S, A> {
+    pub fn new(checks: usize, every: usize) -> Self {
+        Self {
+            checks: checks.max(1),
+            every: every.max(1),
+            prev_policy: None,
+            stable: 0,
+            episodes: 0,
+        }
+    }
+}
+
+impl<S: GenericState, A: GenericAction> StoppingCriterion<S, A> for PolicyStability<S, A> {
+    fn start(&mut self) {
+        self.prev_policy = None;
+        self.stable = 0;
+        self.episodes = 0;
+    }
+
+    fn check(
+        &mut self,
+        _stats: &EpisodeStats,
+        q_map: &BTreeMap<(S, A), f64>,
+    ) -> Option<StopReason> {
+        self.episodes += 1;
+        if !self.episodes.is_multiple_of(self.every) {
+            return None;
+        }
+
+        let policy = TabularPolicy::from_q_map(q_map).actions;
+        if self.prev_policy.as_ref() == Some(&policy) {
+            self.stable += 1;
+        } else {
+            self.stable = 0;
+        }
+        self.prev_policy = Some(policy);
+        (self.stable >= self.checks).then_some(StopReason::PolicyStable)
+    }
+}
+
+// the average reward of the greedy policy is within epsilon of the target. Evaluates with its own
+// rng so the checks do not change the course of training.
+pub struct TargetReturn<'a, M> {
+    mdp: &'a M,
+    target: f64,
+    epsilon: f64,
+    every: usize,
+    episodes: usize,
+    max_steps: usize,
+    rng: ChaCha20Rng,
+    trained: usize,
+}
+
+impl<'a, M> TargetReturn<'a, M> {
+    pub fn new(
+        mdp: &'a M,
+        target: f64,
+        epsilon: f64,
+        every: usize,
+        episodes: usize,
+        max_steps: usize,
+        seed: u64,
+    ) -> Self {
+        Self {
+            mdp,
+            target,
+            epsilon,
+            every: every.max(1),
+            episodes,
+            max_steps,
+            rng: ChaCha20Rng::seed_from_u64(seed),
+            trained: 0,
+        }
+    }
+}
+
+impl<S: GenericState, A: GenericAction, M: GenericMdp<S, A>> StoppingCriterion<S, A>
+    for TargetReturn<'_, M>
+{
+    fn start(&mut self) {
+        self.trained = 0;
+    }
+
+    fn check(
+        &mut self,
+        _stats: &EpisodeStats,
+        q_map: &BTreeMap<(S, A), f64>,
+    ) -> Option<StopReason> {
+        self.trained += 1;
+        if !self.trained.is_multiple_of(self.every) {
+            return None;
+        }
+
+        let avg_reward = evaluate_greedy_policy(
+            self.mdp,
+            q_map,
+            self.episodes,
+            self.max_steps,
+            &mut self.rng,
+        );
+        ((avg_reward - self.target).abs() <= self.epsilon).then_some(StopReason::TargetReached)
+    }
+}
+
+// wall clock budget, measured from the start of the run
+pub struct TimeBudget {
+    budget: Duration,
+    started: Instant,
+}
+
+impl TimeBudget {
+    pub fn new(budget: Duration) -> Self {
+        Self {
+            budget,
+            started: Instant::now(),
+        }
+    }
+}
+
+impl<S: GenericState, A: GenericAction> StoppingCriterion<S, A> for TimeBudget {
+    fn start(&mut self) {
+        self.started = Instant::now();
+    }
+
+    fn check(
+        &mut self,
+        _stats: &EpisodeStats,
+        _q_map: &BTreeMap<(S, A), f64>,
+    ) -> Option<StopReason> {
+        (self.started.elapsed() >= self.budget).then_some(StopReason::TimeBudget)
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::BTreeMap;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    agent::Observation,
+    mdp::{GenericAction, GenericState},
+    policies::epsilon_greedy_policy_ma,
+};
+
+use super::{NextAction, TabularAlgorithm};
+
+// tabular differential SARSA, the average reward counterpart of SARSA for continuing tasks
+#[derive(Serialize, Deserialize)]
+pub struct DifferentialSarsa {
+    alpha: f64,
+    beta: f64,
+    epsilon: f64,
+    max_steps: usize,
+    rho: f64,
+}
+
+impl DifferentialSarsa {
+    pub fn new(alpha: f64, beta: f64, epsilon: f64, max_steps: usize) -> Self {
+        DifferentialSarsa {
+            alpha,
+            beta,
+            epsilon,
+            max_steps,
+            rho: 0.0,
+        }
+    }
+
+    pub fn get_rho(&self) -> f64 {
+        self.rho
+    }
+
+    pub fn reset_rho(&mut self) {
+        self.rho = 0.0;
+    }
+}
+
+// trained with run_continuing on continuing tasks, run restarts from an initial state every
+// max_steps steps like for any other algorithm
+impl<S: GenericState, A: GenericAction> TabularAlgorithm<S, A> for DifferentialSarsa {
+    type Memory = NextAction<S, A>;
+
+    fn max_steps(&self) -> usize {
+        self.max_steps
+    }
+
+    fn act<R: Rng>(
+        &mut self,
+        memory: &mut NextAction<S, A>,
+        q_map: &BTreeMap<(S, A), f64>,
+        state: S,
+        possible_actions: &[A],
+        rng: &mut R,
+    ) -> Option<A> {
+        memory
+            .take(state)
+            .or_else(|| epsilon_greedy_policy_ma(possible_actions, q_map, state, self.epsilon, rng))
+    }
+
+    // a terminal state has no value
+    fn observe<R: Rng>(
+        &mut self,
+        memory: &mut NextAction<S, A>,
+        q_map: &mut BTreeMap<(S, A), f64>,
+        observation: &Observation<S, A>,
+        rng: &mut R,
+    ) -> Option<f64> {
+        let next_action = if observation.terminal {
+            None
+        } else {
+            epsilon_greedy_policy_ma(
+                &observation.next_possible_actions,
+                q_map,
+                observation.next_state,
+                self.epsilon,
+                rng,
+            )
+        };
+        let next_q = next_action
+            .and_then(|action| q_map.get(&(observation.next_state, action)))
+            .copied()
+            .unwrap_or(0.0);
+
+        let current_q = q_map
+            .entry((observation.state, observation.action))
+            .or_insert(0.0);
+        let delta = observation.reward - self.rho + next_q - *current_q;
+
+        self.rho += self.beta * delta;
+        *current_q += self.alpha * delta;
+
+        memory.set(observation.next_state, next_action);
+        Some(delta)
+    }
+
+    fn end_episode<R: Rng>(
+        &mut self,
+        memory: &mut NextAction<S, A>,
+        _q_map: &mut BTreeMap<(S, A), f64>,
+        _rng: &mut R,
+    ) {
+        *memory = NextAction::default();
+    }
+}
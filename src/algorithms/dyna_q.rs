@@ -1,54 +1,98 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 use rand::{seq::IteratorRandom, Rng};
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    mdp::{GenericAction, GenericMdp, GenericState},
-    policies::{epsilon_greedy_policy, greedy_policy},
+    agent::Observation,
+    mdp::{GenericAction, GenericMdp, GenericState, MapMdp},
+    persistence::{entries, nested_entries},
+    policies::epsilon_greedy_policy_ma,
 };
 
-pub trait Dyna<S: GenericState, A: GenericAction> {
-    fn run<M: GenericMdp<S, A>, R: Rng>(
-        &mut self,
-        mdp: &M,
-        episodes: usize,
-        rng: &mut R,
-    ) -> BTreeMap<(S, A), f64> {
-        let mut q_map = BTreeMap::new();
-
-        mdp.get_all_state_actions().iter().for_each(|state_action| {
-            q_map.insert(*state_action, 0.0);
-        });
-
-        self.run_with_q_map(mdp, episodes, rng, &mut q_map);
-
-        q_map
-    }
+use super::{
+    greedy_next_q,
+    replay::{Experience, ReplayBuffer},
+    TabularAlgorithm,
+};
 
-    fn run_with_q_map<M: GenericMdp<S, A>, R: Rng>(
-        &mut self,
-        mdp: &M,
-        episodes: usize,
-        rng: &mut R,
-        q_map: &mut BTreeMap<(S, A), f64>,
-    );
-}
+// observed next states of a pair with their count and reward sum
+type Outcomes<S> = BTreeMap<S, (usize, f64)>;
 
+#[derive(Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "S: Serialize, A: Serialize",
+    deserialize = "S: Deserialize<'de>, A: Deserialize<'de>"
+))]
 pub struct DynaQ<S: GenericState, A: GenericAction> {
     alpha: f64,
     epsilon: f64,
     k: usize,
     max_steps: usize,
+    #[serde(with = "entries")]
     model: BTreeMap<(S, A), (f64, S)>,
-    t_table: BTreeMap<(S, A), BTreeMap<S, usize>>,
+    // observed outcomes of every pair with their count and reward sum, only kept by stochastic
+    // models
+    #[serde(with = "nested_entries")]
+    t_table: BTreeMap<(S, A), Outcomes<S>>,
+    // next states that ended an episode
+    terminal_states: BTreeSet<S>,
+    // possible actions of every next state in the model, planning updates maximise over them
+    #[serde(with = "entries")]
+    actions: BTreeMap<S, Vec<A>>,
     deterministic: bool,
     direct_learning: bool,
+    replay: Option<ReplayBuffer<S, A>>,
 }
 
 impl<S: GenericState, A: GenericAction> DynaQ<S, A> {
     pub fn clear_model(&mut self) {
         self.model.clear();
         self.t_table.clear();
+        self.terminal_states.clear();
+        self.actions.clear();
+    }
+
+    // exports what the agent learned about the environment. A deterministic model keeps the last
+    // observed outcome of every pair, a stochastic model the empirical probabilities and mean
+    // rewards of the t_table.
+    pub fn export_model(&self, discount_factor: f64, initial_state: S) -> MapMdp<S, A> {
+        let mut mdp = MapMdp::new(discount_factor, initial_state);
+        for (state_action, (reward, next_state)) in self.model.iter() {
+            let transitions = if self.deterministic {
+                vec![(1.0, *next_state, *reward)]
+            } else {
+                let outcomes = &self.t_table[state_action];
+                let total: usize = outcomes.values().map(|(count, _)| count).sum();
+                outcomes
+                    .iter()
+                    .map(|(next_state, (count, reward_sum))| {
+                        (
+                            *count as f64 / total as f64,
+                            *next_state,
+                            reward_sum / *count as f64,
+                        )
+                    })
+                    .collect()
+            };
+            mdp.add_transition_vector(*state_action, transitions)
+                .expect("model contains every state action pair once");
+        }
+        self.terminal_states
+            .iter()
+            .for_each(|state| mdp.add_terminal_state(*state));
+
+        mdp
+    }
+
+    // real transitions are additionally stored in the buffer and replayed after planning
+    pub fn with_replay(mut self, buffer: ReplayBuffer<S, A>) -> Self {
+        self.replay = Some(buffer);
+        self
+    }
+
+    pub fn get_replay_buffer_mut(&mut self) -> Option<&mut ReplayBuffer<S, A>> {
+        self.replay.as_mut()
     }
 }
 
@@ -56,6 +100,7 @@ impl<S: GenericState, A: GenericAction> BetaDynaQ<S, A> {
     pub fn clear_model(&mut self) {
         self.model.clear();
         self.t_table.clear();
+        self.actions.clear();
         self.beta_denom = 0.0;
     }
 }
@@ -79,123 +124,165 @@ impl<S: GenericState, A: GenericAction> DynaQ<S, A> {
             t_table: BTreeMap::new(),
             deterministic,
             direct_learning,
+            terminal_states: BTreeSet::new(),
+            actions: BTreeMap::new(),
+            replay: None,
         }
     }
 }
 
-impl<S: GenericState, A: GenericAction> Dyna<S, A> for DynaQ<S, A> {
-    fn run_with_q_map<M: GenericMdp<S, A>, R: Rng>(
+impl<S: GenericState, A: GenericAction> DynaQ<S, A> {
+    // model update, direct learning, planning and replay for a single real transition, returns
+    // the td error of the direct learning step
+    fn learn<R: Rng>(
         &mut self,
-        mdp: &M,
-        episodes: usize,
-        rng: &mut R,
         q_map: &mut BTreeMap<(S, A), f64>,
-    ) {
-        for _ in 1..=episodes {
-            let mut current_state = mdp.get_initial_state(rng);
-            let mut steps = 0;
-
-            while !mdp.is_terminal(current_state) && steps < self.max_steps {
-                let Some(selected_action) =
-                    epsilon_greedy_policy(mdp, q_map, current_state, self.epsilon, rng)
-                else {
-                    break;
-                };
-                let (next_state, reward) =
-                    mdp.perform_action((current_state, selected_action), rng);
-
-                // direct learning step
-                if self.direct_learning {
-                    let Some(best_action) = greedy_policy(mdp, q_map, next_state, rng) else {
-                        break;
-                    };
-                    let best_q = *q_map
-                        .get(&(next_state, best_action))
-                        .expect("No qmap entry found");
-
-                    let current_q = q_map.entry((current_state, selected_action)).or_insert(0.0);
-                    *current_q = *current_q
-                        + self.alpha * (reward + mdp.get_discount_factor() * best_q - *current_q);
-                }
-
-                // determine reward value used for updating model
-                let model_reward = if self.deterministic {
-                    reward
-                } else {
-                    let mut state_count = 0;
-
-                    // update t_table
-                    self.t_table
-                        .entry((current_state, selected_action))
-                        .and_modify(|map| {
-                            map.entry(next_state)
-                                .and_modify(|count| {
-                                    *count += 1;
-                                    state_count = *count;
-                                })
-                                .or_insert(1);
-                        })
-                        .or_insert(BTreeMap::from([(next_state, 1)]));
-
-                    state_count = *self
-                        .t_table
-                        .get(&(current_state, selected_action))
-                        .unwrap()
-                        .get(&next_state)
-                        .unwrap();
-
-                    let sum: usize = self
-                        .t_table
-                        .get(&(current_state, selected_action))
-                        .unwrap()
-                        .values()
-                        .copied()
-                        .sum();
-
-                    reward * (state_count as f64 / sum as f64)
-                };
-
-                // update model
-                self.model
-                    .insert((current_state, selected_action), (model_reward, next_state));
-
-                // run q on model
-                for _ in 0..self.k {
-                    let (key, (reward, next_state)) = self
-                        .model
-                        .iter()
-                        .choose(rng)
-                        .expect("Model should not be empty");
-
-                    let Some(selected_action) = greedy_policy(mdp, q_map, *next_state, rng) else {
-                        // no action possible
-                        continue;
-                    };
-
-                    let best_q = *q_map
-                        .get(&(*next_state, selected_action))
-                        .expect("No qmap entry found");
-
-                    let current_q = q_map.entry(*key).or_insert(0.0);
-
-                    *current_q = *current_q
-                        + self.alpha * (reward + mdp.get_discount_factor() * best_q - *current_q);
-                }
-                current_state = next_state;
-
-                steps += 1;
-            }
+        observation: &Observation<S, A>,
+        rng: &mut R,
+    ) -> Option<f64> {
+        let current_state = observation.state;
+        let selected_action = observation.action;
+        let reward = observation.reward;
+        let next_state = observation.next_state;
+        let discount_factor = observation.discount_factor;
+
+        // the model sees every transition, also the last one of an episode
+        if observation.terminal {
+            self.terminal_states.insert(next_state);
         }
+
+        // determine reward value used for updating model
+        let model_reward = model_reward(
+            &mut self.t_table,
+            self.deterministic,
+            (current_state, selected_action),
+            reward,
+            next_state,
+        );
+
+        // update model
+        self.model
+            .insert((current_state, selected_action), (model_reward, next_state));
+        self.actions
+            .insert(next_state, observation.next_possible_actions.clone());
+
+        // direct learning step
+        let mut td_error = None;
+        if self.direct_learning {
+            let best_q = greedy_next_q(
+                q_map,
+                next_state,
+                &observation.next_possible_actions,
+                observation.terminal,
+                rng,
+            );
+
+            let current_q = q_map.entry((current_state, selected_action)).or_insert(0.0);
+            let delta = reward + discount_factor * best_q - *current_q;
+            *current_q += self.alpha * delta;
+            td_error = Some(delta);
+        }
+
+        // run q on model
+        for _ in 0..self.k {
+            let (key, (reward, next_state)) = self
+                .model
+                .iter()
+                .choose(rng)
+                .expect("Model should not be empty");
+
+            let best_q = greedy_next_q(
+                q_map,
+                *next_state,
+                &self.actions[next_state],
+                self.terminal_states.contains(next_state),
+                rng,
+            );
+
+            let current_q = q_map.entry(*key).or_insert(0.0);
+
+            *current_q = *current_q + self.alpha * (reward + discount_factor * best_q - *current_q);
+        }
+
+        // replay stored real transitions
+        if let Some(buffer) = self.replay.as_mut() {
+            buffer.push(Experience {
+                state: current_state,
+                action: selected_action,
+                reward,
+                next_state,
+                next_possible_actions: observation.next_possible_actions.clone(),
+                terminal: observation.terminal,
+            });
+
+            let alpha = self.alpha;
+            buffer.replay_with(rng, |experience, rng| {
+                let best_q = greedy_next_q(
+                    q_map,
+                    experience.next_state,
+                    &experience.next_possible_actions,
+                    experience.terminal,
+                    rng,
+                );
+
+                let current_q = q_map
+                    .entry((experience.state, experience.action))
+                    .or_insert(0.0);
+                let delta = alpha * (experience.reward + discount_factor * best_q - *current_q);
+                *current_q += delta;
+                delta
+            });
+        }
+
+        td_error
+    }
+}
+
+impl<S: GenericState, A: GenericAction> TabularAlgorithm<S, A> for DynaQ<S, A> {
+    type Memory = ();
+
+    fn max_steps(&self) -> usize {
+        self.max_steps
+    }
+
+    fn act<R: Rng>(
+        &mut self,
+        _memory: &mut (),
+        q_map: &BTreeMap<(S, A), f64>,
+        state: S,
+        possible_actions: &[A],
+        rng: &mut R,
+    ) -> Option<A> {
+        epsilon_greedy_policy_ma(possible_actions, q_map, state, self.epsilon, rng)
+    }
+
+    fn observe<R: Rng>(
+        &mut self,
+        _memory: &mut (),
+        q_map: &mut BTreeMap<(S, A), f64>,
+        observation: &Observation<S, A>,
+        rng: &mut R,
+    ) -> Option<f64> {
+        self.learn(q_map, observation, rng)
     }
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "S: Serialize, A: Serialize",
+    deserialize = "S: Deserialize<'de>, A: Deserialize<'de>"
+))]
 pub struct BetaDynaQ<S: GenericState, A: GenericAction> {
     alpha: f64,
     epsilon: f64,
     k: usize,
     max_steps: usize,
+    #[serde(with = "entries")]
     model: BTreeMap<(S, A), (f64, S)>,
-    t_table: BTreeMap<(S, A), BTreeMap<S, usize>>,
+    #[serde(with = "nested_entries")]
+    t_table: BTreeMap<(S, A), Outcomes<S>>,
+    #[serde(with = "entries")]
+    actions: BTreeMap<S, Vec<A>>,
     deterministic: bool,
     beta_rate: usize,
     beta_denom: f64,
@@ -221,6 +308,7 @@ impl<S: GenericState, A: GenericAction> BetaDynaQ<S, A> {
             max_steps,
             model: BTreeMap::new(),
             t_table: BTreeMap::new(),
+            actions: BTreeMap::new(),
             deterministic,
             beta_rate,
             beta_denom: 0.0_f64,
@@ -247,6 +335,7 @@ impl<S: GenericState, A: GenericAction> BetaDynaQ<S, A> {
             max_steps,
             model: BTreeMap::new(),
             t_table: BTreeMap::new(),
+            actions: BTreeMap::new(),
             deterministic,
             beta_rate,
             beta_denom: 0.0_f64,
@@ -257,124 +346,159 @@ impl<S: GenericState, A: GenericAction> BetaDynaQ<S, A> {
     }
 }
 
-impl<S: GenericState, A: GenericAction> Dyna<S, A> for BetaDynaQ<S, A> {
-    fn run_with_q_map<M: GenericMdp<S, A>, R: Rng>(
+impl<S: GenericState, A: GenericAction> BetaDynaQ<S, A> {
+    // step sizes of the episode that is about to start
+    fn next_rates(&mut self) -> (f64, f64) {
+        let alpha = if self.converging_alpha {
+            1.0 / (self.beta_denom + 1.0).sqrt()
+        } else {
+            self.alpha
+        };
+
+        let beta = 1.0 / (self.beta_denom + 1.0);
+
+        if self.total_episodes % self.beta_rate == 0 {
+            self.beta_denom += 1.0;
+        }
+
+        (alpha, beta)
+    }
+
+    // same as DynaQ::learn with every update shrunk by (1 - beta)
+    fn learn<R: Rng>(
         &mut self,
-        mdp: &M,
-        episodes: usize,
+        q_map: &mut BTreeMap<(S, A), f64>,
+        observation: &Observation<S, A>,
+        (alpha, beta): (f64, f64),
         rng: &mut R,
+    ) -> Option<f64> {
+        let current_state = observation.state;
+        let selected_action = observation.action;
+        let reward = observation.reward;
+        let next_state = observation.next_state;
+        let discount_factor = observation.discount_factor;
+
+        // determine reward value used for updating model
+        let model_reward = model_reward(
+            &mut self.t_table,
+            self.deterministic,
+            (current_state, selected_action),
+            reward,
+            next_state,
+        );
+
+        // update model
+        self.model
+            .insert((current_state, selected_action), (model_reward, next_state));
+        self.actions
+            .insert(next_state, observation.next_possible_actions.clone());
+
+        // direct RL step
+        let mut td_error = None;
+        if self.direct_learning {
+            let best_q = greedy_next_q(
+                q_map,
+                next_state,
+                &observation.next_possible_actions,
+                observation.terminal,
+                rng,
+            );
+
+            let current_q = q_map.entry((current_state, selected_action)).or_insert(0.0);
+            let delta = reward + discount_factor * best_q - *current_q;
+            *current_q = (*current_q + alpha * delta) * (1.0 - beta);
+            td_error = Some(delta);
+        }
+
+        // run q on model
+        for _ in 0..self.k {
+            let (key, (reward, next_state)) = self
+                .model
+                .iter()
+                .choose(rng)
+                .expect("Model should not be empty");
+
+            let best_q = greedy_next_q(q_map, *next_state, &self.actions[next_state], false, rng);
+
+            let current_q = q_map.entry(*key).or_insert(0.0);
+
+            *current_q = (*current_q + alpha * (reward + discount_factor * best_q - *current_q))
+                * (1.0 - beta);
+        }
+
+        td_error
+    }
+}
+
+// the memory holds the step sizes of the current episode
+impl<S: GenericState, A: GenericAction> TabularAlgorithm<S, A> for BetaDynaQ<S, A> {
+    type Memory = (f64, f64);
+
+    fn max_steps(&self) -> usize {
+        self.max_steps
+    }
+
+    fn begin_episode<R: Rng>(
+        &mut self,
+        rates: &mut (f64, f64),
+        _q_map: &mut BTreeMap<(S, A), f64>,
+        _rng: &mut R,
+    ) {
+        *rates = self.next_rates();
+    }
+
+    fn act<R: Rng>(
+        &mut self,
+        _rates: &mut (f64, f64),
+        q_map: &BTreeMap<(S, A), f64>,
+        state: S,
+        possible_actions: &[A],
+        rng: &mut R,
+    ) -> Option<A> {
+        epsilon_greedy_policy_ma(possible_actions, q_map, state, self.epsilon, rng)
+    }
+
+    fn observe<R: Rng>(
+        &mut self,
+        rates: &mut (f64, f64),
         q_map: &mut BTreeMap<(S, A), f64>,
+        observation: &Observation<S, A>,
+        rng: &mut R,
+    ) -> Option<f64> {
+        self.learn(q_map, observation, *rates, rng)
+    }
+
+    fn end_episode<R: Rng>(
+        &mut self,
+        _rates: &mut (f64, f64),
+        _q_map: &mut BTreeMap<(S, A), f64>,
+        _rng: &mut R,
     ) {
-        for _ in 1..=episodes {
-            // println!("beta_denom: {}", self.beta_denom);
-            let alpha = if self.converging_alpha {
-                1.0 / (self.beta_denom + 1.0).sqrt()
-            } else {
-                self.alpha
-            };
+        self.total_episodes += 1;
+    }
+}
 
-            let beta = 1.0 / (self.beta_denom + 1.0);
-
-            if self.total_episodes % self.beta_rate == 0 {
-                self.beta_denom += 1.0;
-            }
-
-            let mut current_state = mdp.get_initial_state(rng);
-            let mut steps = 0;
-
-            while !mdp.is_terminal(current_state) && steps < self.max_steps {
-                let Some(selected_action) =
-                    epsilon_greedy_policy(mdp, q_map, current_state, self.epsilon, rng)
-                else {
-                    break;
-                };
-                let (next_state, reward) =
-                    mdp.perform_action((current_state, selected_action), rng);
-
-                // direct RL step
-                if self.direct_learning {
-                    let Some(best_action) = greedy_policy(mdp, q_map, next_state, rng) else {
-                        break;
-                    };
-                    let best_q = *q_map
-                        .get(&(next_state, best_action))
-                        .expect("No qmap entry found");
-
-                    let current_q = q_map.entry((current_state, selected_action)).or_insert(0.0);
-                    *current_q = (*current_q
-                        + alpha * (reward + mdp.get_discount_factor() * best_q - *current_q))
-                        * (1.0 - beta);
-                }
-
-                // determine reward value used for updating model
-                let model_reward = if self.deterministic {
-                    reward
-                } else {
-                    let mut state_count = 0;
-
-                    // update t_table
-                    self.t_table
-                        .entry((current_state, selected_action))
-                        .and_modify(|map| {
-                            map.entry(next_state)
-                                .and_modify(|count| {
-                                    *count += 1;
-                                    state_count = *count;
-                                })
-                                .or_insert(1);
-                        })
-                        .or_insert(BTreeMap::from([(next_state, 1)]));
-
-                    state_count = *self
-                        .t_table
-                        .get(&(current_state, selected_action))
-                        .unwrap()
-                        .get(&next_state)
-                        .unwrap();
-
-                    let sum: usize = self
-                        .t_table
-                        .get(&(current_state, selected_action))
-                        .unwrap()
-                        .values()
-                        .copied()
-                        .sum();
-
-                    reward * (state_count as f64 / sum as f64)
-                };
-
-                // update model
-                self.model
-                    .insert((current_state, selected_action), (model_reward, next_state));
-
-                // run q on model
-                for _ in 0..self.k {
-                    let (key, (reward, next_state)) = self
-                        .model
-                        .iter()
-                        .choose(rng)
-                        .expect("Model should not be empty");
-
-                    let Some(selected_action) = greedy_policy(mdp, q_map, *next_state, rng) else {
-                        // no action possible
-                        continue;
-                    };
-
-                    let best_q = *q_map
-                        .get(&(*next_state, selected_action))
-                        .expect("No qmap entry found");
-
-                    let current_q = q_map.entry(*key).or_insert(0.0);
-
-                    *current_q = (*current_q
-                        + alpha * (reward + mdp.get_discount_factor() * best_q - *current_q))
-                        * (1.0 - beta);
-                }
-                current_state = next_state;
-
-                steps += 1;
-            }
-            self.total_episodes += 1;
-        }
+// reward stored in the model, stochastic models weight the reward with the observed frequency of
+// the outcome
+fn model_reward<S: GenericState, A: GenericAction>(
+    t_table: &mut BTreeMap<(S, A), Outcomes<S>>,
+    deterministic: bool,
+    (current_state, selected_action): (S, A),
+    reward: f64,
+    next_state: S,
+) -> f64 {
+    if deterministic {
+        return reward;
     }
+
+    // update t_table
+    let outcomes = t_table.entry((current_state, selected_action)).or_default();
+    let outcome = outcomes.entry(next_state).or_insert((0, 0.0));
+    outcome.0 += 1;
+    outcome.1 += reward;
+
+    let state_count = outcomes[&next_state].0;
+    let sum: usize = outcomes.values().map(|(count, _)| count).sum();
+
+    reward * (state_count as f64 / sum as f64)
 }
@@ -0,0 +1,106 @@
+use std::collections::BTreeMap;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    agent::Observation,
+    mdp::{GenericAction, GenericState},
+    policies::epsilon_greedy_policy_ma,
+};
+
+use super::TabularAlgorithm;
+
+#[derive(Serialize, Deserialize)]
+pub struct ExpectedSarsa {
+    alpha: f64,
+    epsilon: f64,
+    max_steps: usize,
+}
+
+impl ExpectedSarsa {
+    pub fn new(alpha: f64, epsilon: f64, max_steps: usize) -> Self {
+        ExpectedSarsa {
+            alpha,
+            epsilon,
+            max_steps,
+        }
+    }
+
+    // expected q-value of the next state under the epsilon-greedy policy, ties of the greedy action
+    // share its probability
+    fn expected_q<S: GenericState, A: GenericAction>(
+        &self,
+        q_map: &BTreeMap<(S, A), f64>,
+        possible_actions: &[A],
+        state: S,
+    ) -> Option<f64> {
+        let q_values: Vec<f64> = possible_actions
+            .iter()
+            .map(|action| *q_map.get(&(state, *action)).expect("No qmap entry found"))
+            .collect();
+        let max_q = q_values.iter().copied().reduce(f64::max)?;
+        let greedy_count = q_values.iter().filter(|q| **q == max_q).count() as f64;
+        let n = q_values.len() as f64;
+
+        Some(
+            q_values
+                .iter()
+                .map(|q| {
+                    let greedy_prob = if *q == max_q {
+                        (1.0 - self.epsilon) / greedy_count
+                    } else {
+                        0.0
+                    };
+                    (greedy_prob + self.epsilon / n) * q
+                })
+                .sum(),
+        )
+    }
+}
+
+impl<S: GenericState, A: GenericAction> TabularAlgorithm<S, A> for ExpectedSarsa {
+    type Memory = ();
+
+    fn max_steps(&self) -> usize {
+        self.max_steps
+    }
+
+    fn act<R: Rng>(
+        &mut self,
+        _memory: &mut (),
+        q_map: &BTreeMap<(S, A), f64>,
+        state: S,
+        possible_actions: &[A],
+        rng: &mut R,
+    ) -> Option<A> {
+        epsilon_greedy_policy_ma(possible_actions, q_map, state, self.epsilon, rng)
+    }
+
+    fn observe<R: Rng>(
+        &mut self,
+        _memory: &mut (),
+        q_map: &mut BTreeMap<(S, A), f64>,
+        observation: &Observation<S, A>,
+        _rng: &mut R,
+    ) -> Option<f64> {
+        // a terminal state or a state without actions has no value
+        let expected_q = if observation.terminal {
+            0.0
+        } else {
+            self.expected_q(
+                q_map,
+                &observation.next_possible_actions,
+                observation.next_state,
+            )
+            .unwrap_or(0.0)
+        };
+
+        let current_q = q_map
+            .entry((observation.state, observation.action))
+            .or_insert(0.0);
+        let td_error = observation.reward + observation.discount_factor * expected_q - *current_q;
+        *current_q += self.alpha * td_error;
+        Some(td_error)
+    }
+}
@@ -1,92 +1,252 @@
+pub mod differential_sarsa;
 pub mod dyna_q;
+pub mod expected_sarsa;
 pub mod monte_carlo;
+pub mod policy_iteration;
+pub mod psrl;
 pub mod q_learning;
 pub mod q_learning_beta;
 pub mod q_learning_dynamic;
 pub mod q_learning_lambda;
+pub mod r_learning;
+pub mod relative_value_iteration;
+pub mod replay;
 pub mod sarsa;
 pub mod sarsa_lambda;
 pub mod value_iteration;
 
-use std::{collections::BTreeMap, fmt::Display};
+use std::{collections::BTreeMap, fmt::Display, mem};
 
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
-use crate::mdp::{GenericAction, GenericMdp, GenericState};
+use crate::{
+    agent::{runner::Runner, Observation, TabularAgent},
+    mdp::{GenericAction, GenericMdp, GenericState},
+    policies::greedy_policy_ma,
+};
 
-pub trait GenericStateActionAlgorithm {
-    // default implementation
-    fn run<M: GenericMdp<S, A>, S: GenericState, A: GenericAction, R: Rng>(
-        &self,
-        mdp: &M,
-        episodes: usize,
+// the update rule of a tabular learner. agent::TabularAgent pairs it with a q_map and the
+// algorithm's memory of the current episode, which makes it an agent::Agent for the Runner.
+// Everything an algorithm learns across episodes (models, visit counts, step size schedules) is
+// kept in the algorithm itself so that chunked training matches a single run.
+pub trait TabularAlgorithm<S: GenericState, A: GenericAction> {
+    // state the algorithm needs within an episode, e.g. eligibility traces or the next action
+    type Memory: Default;
+
+    fn max_steps(&self) -> usize;
+
+    fn begin_episode<R: Rng>(
+        &mut self,
+        _memory: &mut Self::Memory,
+        _q_map: &mut BTreeMap<(S, A), f64>,
+        _rng: &mut R,
+    ) {
+    }
+
+    fn act<R: Rng>(
+        &mut self,
+        memory: &mut Self::Memory,
+        q_map: &BTreeMap<(S, A), f64>,
+        state: S,
+        possible_actions: &[A],
         rng: &mut R,
-    ) -> BTreeMap<(S, A), f64> {
-        let mut q_map: BTreeMap<(S, A), f64> = BTreeMap::new();
+    ) -> Option<A>;
 
-        mdp.get_all_state_actions().iter().for_each(|state_action| {
-            q_map.insert(*state_action, 0.0);
-        });
+    // learns from a real transition and returns its td error before the update, None for
+    // algorithms that do not learn from single steps or did not update the pair
+    fn observe<R: Rng>(
+        &mut self,
+        memory: &mut Self::Memory,
+        q_map: &mut BTreeMap<(S, A), f64>,
+        observation: &Observation<S, A>,
+        rng: &mut R,
+    ) -> Option<f64>;
 
-        self.run_with_q_map(mdp, episodes, rng, &mut q_map);
+    fn end_episode<R: Rng>(
+        &mut self,
+        _memory: &mut Self::Memory,
+        _q_map: &mut BTreeMap<(S, A), f64>,
+        _rng: &mut R,
+    ) {
+    }
 
+    // every state action pair of the mdp starts at 0
+    fn run<M: GenericMdp<S, A>, R: Rng>(
+        &mut self,
+        mdp: &M,
+        episodes: usize,
+        rng: &mut R,
+    ) -> BTreeMap<(S, A), f64>
+    where
+        Self: Sized,
+    {
+        let mut q_map = BTreeMap::new();
+        self.run_with_q_map(mdp, episodes, rng, &mut q_map);
         q_map
     }
 
-    fn run_with_q_map<M: GenericMdp<S, A>, S: GenericState, A: GenericAction, R: Rng>(
-        &self,
+    // continues training on the given q_map, pairs of the mdp that are missing start at 0
+    fn run_with_q_map<M: GenericMdp<S, A>, R: Rng>(
+        &mut self,
         mdp: &M,
         episodes: usize,
         rng: &mut R,
         q_map: &mut BTreeMap<(S, A), f64>,
-    );
-
-    fn get_epsilon(&self) -> f64;
+    ) where
+        Self: Sized,
+    {
+        mdp.get_all_state_actions().iter().for_each(|state_action| {
+            q_map.entry(*state_action).or_insert(0.0);
+        });
+        Runner::new(self.max_steps()).run_with_q_map(self, mdp, episodes, rng, q_map, &mut ());
+    }
 
-    #[allow(unused_variables)]
-    fn step<S: GenericState, A: GenericAction, R: Rng>(
-        &self,
-        q_map: &mut BTreeMap<(S, A), f64>,
-        possible_actions: &[A],
-        current_state: S,
-        selected_action: A,
-        next_state: S,
-        reward: f64,
-        discount_factor: f64,
+    // one continuous run of the given number of steps that only restarts in a terminal state, for
+    // continuing tasks that episodes of max_steps would cut into pieces
+    fn run_continuing<M: GenericMdp<S, A>, R: Rng>(
+        &mut self,
+        mdp: &M,
+        steps: usize,
         rng: &mut R,
-    ) -> bool {
-        unimplemented!()
+    ) -> BTreeMap<(S, A), f64>
+    where
+        Self: Sized,
+    {
+        let mut q_map = BTreeMap::new();
+        self.run_continuing_with_q_map(mdp, steps, rng, &mut q_map);
+        q_map
     }
-}
 
-pub trait GenericStateActionAlgorithmStateful {
-    // default implementation
-    fn run<M: GenericMdp<S, A>, S: GenericState, A: GenericAction, R: Rng>(
+    fn run_continuing_with_q_map<M: GenericMdp<S, A>, R: Rng>(
         &mut self,
         mdp: &M,
-        episodes: usize,
+        steps: usize,
         rng: &mut R,
-    ) -> BTreeMap<(S, A), f64> {
-        let mut q_map: BTreeMap<(S, A), f64> = BTreeMap::new();
-
+        q_map: &mut BTreeMap<(S, A), f64>,
+    ) where
+        Self: Sized,
+    {
         mdp.get_all_state_actions().iter().for_each(|state_action| {
-            q_map.insert(*state_action, 0.0);
+            q_map.entry(*state_action).or_insert(0.0);
         });
+        let runner = Runner::new(self.max_steps());
+        let mut agent = TabularAgent::new(self, mem::take(q_map));
+        runner.run_continuing(mdp, &mut agent, steps, rng, &mut ());
+        *q_map = agent.q_map;
+    }
+}
 
-        self.run_with_q_map(mdp, episodes, rng, &mut q_map);
+// lets the Runner borrow an algorithm instead of taking it
+impl<S: GenericState, A: GenericAction, G: TabularAlgorithm<S, A>> TabularAlgorithm<S, A>
+    for &mut G
+{
+    type Memory = G::Memory;
 
-        q_map
+    fn max_steps(&self) -> usize {
+        (**self).max_steps()
     }
 
-    fn run_with_q_map<M: GenericMdp<S, A>, S: GenericState, A: GenericAction, R: Rng>(
+    fn begin_episode<R: Rng>(
         &mut self,
-        mdp: &M,
-        episodes: usize,
+        memory: &mut Self::Memory,
+        q_map: &mut BTreeMap<(S, A), f64>,
+        rng: &mut R,
+    ) {
+        (**self).begin_episode(memory, q_map, rng)
+    }
+
+    fn act<R: Rng>(
+        &mut self,
+        memory: &mut Self::Memory,
+        q_map: &BTreeMap<(S, A), f64>,
+        state: S,
+        possible_actions: &[A],
+        rng: &mut R,
+    ) -> Option<A> {
+        (**self).act(memory, q_map, state, possible_actions, rng)
+    }
+
+    fn observe<R: Rng>(
+        &mut self,
+        memory: &mut Self::Memory,
+        q_map: &mut BTreeMap<(S, A), f64>,
+        observation: &Observation<S, A>,
         rng: &mut R,
+    ) -> Option<f64> {
+        (**self).observe(memory, q_map, observation, rng)
+    }
+
+    fn end_episode<R: Rng>(
+        &mut self,
+        memory: &mut Self::Memory,
         q_map: &mut BTreeMap<(S, A), f64>,
-    );
+        rng: &mut R,
+    ) {
+        (**self).end_episode(memory, q_map, rng)
+    }
 }
-#[derive(Copy, Clone, Debug)]
+
+// episode memory of the on-policy learners that pick the next action during the update
+pub struct NextAction<S, A>(Option<(S, A)>);
+
+impl<S, A> Default for NextAction<S, A> {
+    fn default() -> Self {
+        Self(None)
+    }
+}
+
+impl<S: GenericState, A: GenericAction> NextAction<S, A> {
+    pub fn set(&mut self, state: S, action: Option<A>) {
+        self.0 = action.map(|action| (state, action));
+    }
+
+    // takes the preselected action if it belongs to the given state
+    pub fn take(&mut self, state: S) -> Option<A> {
+        match self.0.take() {
+            Some((s, action)) if s == state => Some(action),
+            _ => None,
+        }
+    }
+}
+
+// episode memory of the eligibility trace learners
+pub struct Traces<S, A> {
+    pub next_action: NextAction<S, A>,
+    pub traces: BTreeMap<(S, A), f64>,
+}
+
+impl<S, A> Default for Traces<S, A> {
+    fn default() -> Self {
+        Self {
+            next_action: NextAction::default(),
+            traces: BTreeMap::new(),
+        }
+    }
+}
+
+// value of a greedy action in the next state, which the q-learning updates bootstrap from. A
+// terminal state or a state without actions is worth 0.
+pub fn greedy_next_q<S: GenericState, A: GenericAction, R: Rng>(
+    q_map: &BTreeMap<(S, A), f64>,
+    next_state: S,
+    next_possible_actions: &[A],
+    terminal: bool,
+    rng: &mut R,
+) -> f64 {
+    if terminal {
+        return 0.0;
+    }
+    greedy_policy_ma(next_possible_actions, q_map, next_state, rng)
+        .map(|action| {
+            *q_map
+                .get(&(next_state, action))
+                .expect("No qmap entry found")
+        })
+        .unwrap_or(0.0)
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub enum Trace {
     Accumulating,
     Replacing,
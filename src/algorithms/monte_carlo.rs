@@ -1,95 +1,129 @@
-use std::collections::{BTreeMap, HashSet};
+use std::collections::BTreeMap;
 
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    mdp::{GenericAction, GenericMdp, GenericState, Reward},
-    policies::epsilon_greedy_policy,
+    agent::Observation,
+    mdp::{GenericAction, GenericState, Reward},
+    persistence::entries,
+    policies::epsilon_greedy_policy_ma,
 };
 
-use super::GenericStateActionAlgorithm;
+use super::TabularAlgorithm;
 
-pub struct MonteCarlo {
+// first-visit Monte Carlo, every q value is the running average of the discounted returns that
+// followed the first visit of its state action pair in an episode. The visit counts are kept across
+// runs, so training in chunks averages over all episodes.
+#[derive(Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "S: Serialize, A: Serialize",
+    deserialize = "S: Deserialize<'de>, A: Deserialize<'de>"
+))]
+pub struct MonteCarlo<S: GenericState, A: GenericAction> {
     epsilon: f64,
     max_steps: usize,
+    #[serde(with = "entries")]
+    visits: BTreeMap<(S, A), usize>,
 }
 
-impl MonteCarlo {
-    pub fn new(epsilon: f64, max_steps: usize) -> MonteCarlo {
-        MonteCarlo { max_steps, epsilon }
+impl<S: GenericState, A: GenericAction> MonteCarlo<S, A> {
+    pub fn new(epsilon: f64, max_steps: usize) -> Self {
+        MonteCarlo {
+            max_steps,
+            epsilon,
+            visits: BTreeMap::new(),
+        }
     }
 
-    fn generate_episode<M: GenericMdp<S, A>, S: GenericState, A: GenericAction, R: Rng>(
-        &self,
-        mdp: &M,
-        q_map: &BTreeMap<(S, A), Reward>,
-        rng: &mut R,
-    ) -> Vec<(S, A, Reward)> {
-        let mut episode = vec![];
-
-        let mut current_state = mdp.get_initial_state(rng);
-        let mut steps = 0;
-        while !mdp.is_terminal(current_state) && steps < self.max_steps {
-            let selected_action =
-                epsilon_greedy_policy(mdp, q_map, current_state, self.epsilon, rng);
+    pub fn clear_visits(&mut self) {
+        self.visits.clear();
+    }
 
-            let Some(selected_action) = selected_action else {
-                break;
-            };
-            let (next_state, reward) = mdp.perform_action((current_state, selected_action), rng);
+    fn update_from_episode(
+        &mut self,
+        episode: &[(S, A, Reward)],
+        discount_factor: f64,
+        q_map: &mut BTreeMap<(S, A), f64>,
+    ) {
+        let mut first_visits: BTreeMap<(S, A), usize> = BTreeMap::new();
+        episode
+            .iter()
+            .enumerate()
+            .for_each(|(t, (state, action, _))| {
+                first_visits.entry((*state, *action)).or_insert(t);
+            });
 
-            episode.push((current_state, selected_action, reward));
-            current_state = next_state;
-            steps += 1;
+        // returns are accumulated backwards
+        let mut ret = 0.0;
+        for (t, (state, action, reward)) in episode.iter().enumerate().rev() {
+            ret = reward + discount_factor * ret;
+            if first_visits[&(*state, *action)] != t {
+                continue;
+            }
+            if let Some(q) = q_map.get_mut(&(*state, *action)) {
+                let count = self.visits.entry((*state, *action)).or_insert(0);
+                *count += 1;
+                *q += (ret - *q) / *count as f64;
+            }
         }
-        episode
     }
 }
 
-impl GenericStateActionAlgorithm for MonteCarlo {
-    fn run_with_q_map<M: GenericMdp<S, A>, S: GenericState, A: GenericAction, R: Rng>(
-        &self,
-        mdp: &M,
-        episodes: usize,
-        rng: &mut R,
-        q_map: &mut BTreeMap<(S, A), f64>,
-    ) {
-        let mut returns: BTreeMap<(S, A), Vec<f64>> = BTreeMap::new();
-        for _ in 0..episodes {
-            // generate episode
-            let episode = self.generate_episode(mdp, q_map, rng);
-            let mut g: BTreeMap<(S, A), f64> = BTreeMap::new();
-            let mut visited_states = HashSet::new();
+// the steps of the current episode and their discount factor
+pub struct Episode<S, A> {
+    steps: Vec<(S, A, Reward)>,
+    discount_factor: f64,
+}
 
-            // get first-visit returns
-            episode.iter().for_each(|(state, action, reward)| {
-                g.entry((*state, *action))
-                    .and_modify(|e| *e += *reward)
-                    .or_insert(*reward);
-                // track all visited states for later while we're at it
-                visited_states.insert(state);
-            });
+impl<S, A> Default for Episode<S, A> {
+    fn default() -> Self {
+        Self {
+            steps: vec![],
+            discount_factor: 1.0,
+        }
+    }
+}
 
-            // append to returns(s, a)
-            g.iter().for_each(|((state, action), ret)| {
-                returns
-                    .entry((*state, *action))
-                    .and_modify(|v| v.push(*ret))
-                    .or_insert(vec![*ret]);
-            });
+impl<S: GenericState, A: GenericAction> TabularAlgorithm<S, A> for MonteCarlo<S, A> {
+    type Memory = Episode<S, A>;
 
-            // average into q_map
-            returns.iter().for_each(|(key, rets)| {
-                q_map
-                    .entry(*key)
-                    .and_modify(|entry| *entry = rets.iter().sum::<f64>() / rets.len() as f64);
-            });
+    fn max_steps(&self) -> usize {
+        self.max_steps
+    }
 
-            returns.clear();
-        }
+    fn act<R: Rng>(
+        &mut self,
+        _episode: &mut Episode<S, A>,
+        q_map: &BTreeMap<(S, A), f64>,
+        state: S,
+        possible_actions: &[A],
+        rng: &mut R,
+    ) -> Option<A> {
+        epsilon_greedy_policy_ma(possible_actions, q_map, state, self.epsilon, rng)
+    }
+
+    fn observe<R: Rng>(
+        &mut self,
+        episode: &mut Episode<S, A>,
+        _q_map: &mut BTreeMap<(S, A), f64>,
+        observation: &Observation<S, A>,
+        _rng: &mut R,
+    ) -> Option<f64> {
+        episode.discount_factor = observation.discount_factor;
+        episode
+            .steps
+            .push((observation.state, observation.action, observation.reward));
+        None
     }
 
-    fn get_epsilon(&self) -> f64 {
-        self.epsilon
+    fn end_episode<R: Rng>(
+        &mut self,
+        episode: &mut Episode<S, A>,
+        q_map: &mut BTreeMap<(S, A), f64>,
+        _rng: &mut R,
+    ) {
+        let episode = std::mem::take(episode);
+        self.update_from_episode(&episode.steps, episode.discount_factor, q_map);
     }
 }
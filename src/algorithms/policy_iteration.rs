@@ -0,0 +1,84 @@
+use std::collections::BTreeMap;
+
+use crate::mdp::{GenericAction, GenericState, MapMdp};
+
+// howard's policy iteration, alternates iterative policy evaluation with greedy improvement until
+// the policy is stable. Returns the policy and its values, terminal states keep a value of 0. An
+// action only replaces the current one if it is better by more than the tolerance, so ties do not
+// make the policy oscillate.
+// Each evaluation stops after max_iterations sweeps. Without discounting the values of a policy
+// that never reaches a terminal state diverge, the cap leaves them very low instead of looping
+// forever and the improvement step moves away from that policy.
+pub fn policy_iteration<S: GenericState, A: GenericAction>(
+    mdp: &MapMdp<S, A>,
+    tolerance: f64,
+    max_iterations: usize,
+) -> (BTreeMap<S, A>, BTreeMap<S, f64>) {
+    // keys are sorted by state, so the actions of a state are consecutive
+    let mut actions: BTreeMap<S, Vec<A>> = BTreeMap::new();
+    mdp.transitions
+        .keys()
+        .filter(|(state, _)| !mdp.terminal_states.contains(state))
+        .for_each(|(state, action)| actions.entry(*state).or_default().push(*action));
+
+    let mut policy: BTreeMap<S, A> = actions
+        .iter()
+        .map(|(state, actions)| (*state, actions[0]))
+        .collect();
+    // same states as value_iteration, terminal states with transitions are in the map as well
+    let mut value_map: BTreeMap<S, f64> = mdp
+        .transitions
+        .keys()
+        .filter(|(state, _)| mdp.terminal_states.contains(state))
+        .map(|(state, _)| (*state, 0.0))
+        .collect();
+
+    loop {
+        // policy evaluation
+        for _ in 0..max_iterations {
+            let mut delta: f64 = 0.0;
+            for (state, action) in policy.iter() {
+                let new_value = expected_value(mdp, *state, *action, &value_map);
+                let old_value = value_map.insert(*state, new_value).unwrap_or(0.0);
+                delta = delta.max((new_value - old_value).abs());
+            }
+            if delta < tolerance {
+                break;
+            }
+        }
+
+        // policy improvement
+        let mut stable = true;
+        for (state, actions) in actions.iter() {
+            let current = policy[state];
+            let mut best = (current, expected_value(mdp, *state, current, &value_map));
+            for action in actions {
+                let value = expected_value(mdp, *state, *action, &value_map);
+                if value > best.1 + tolerance {
+                    best = (*action, value);
+                }
+            }
+            if best.0 != current {
+                policy.insert(*state, best.0);
+                stable = false;
+            }
+        }
+        if stable {
+            return (policy, value_map);
+        }
+    }
+}
+
+fn expected_value<S: GenericState, A: GenericAction>(
+    mdp: &MapMdp<S, A>,
+    state: S,
+    action: A,
+    value_map: &BTreeMap<S, f64>,
+) -> f64 {
+    mdp.transitions[&(state, action)]
+        .iter()
+        .map(|(prob, next_state, reward)| {
+            prob * (reward + mdp.discount_factor * value_map.get(next_state).unwrap_or(&0.0))
+        })
+        .sum()
+}
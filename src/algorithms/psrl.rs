@@ -0,0 +1,262 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use rand::{distributions::Distribution, Rng};
+use rand_distr::{Gamma, Normal};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    agent::Observation,
+    mdp::{GenericAction, GenericMdp, GenericState, MapMdp},
+    persistence::entries,
+    policies::greedy_policy_ma,
+};
+
+use super::{
+    value_iteration::{q_map_from_values, value_iteration},
+    TabularAlgorithm,
+};
+
+// keeps undiscounted sampled mdps without reachable terminal states solvable
+const MAX_PLANNING_DISCOUNT: f64 = 0.999;
+
+// normal-gamma prior over the mean and precision of the rewards of a state action pair
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct NormalGammaPrior {
+    pub mean: f64,
+    pub kappa: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Default for NormalGammaPrior {
+    fn default() -> Self {
+        Self {
+            mean: 0.0,
+            kappa: 1.0,
+            alpha: 1.0,
+            beta: 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound(serialize = "S: Serialize", deserialize = "S: Deserialize<'de>"))]
+struct Posterior<S: GenericState> {
+    #[serde(with = "entries")]
+    next_state_counts: BTreeMap<S, f64>,
+    reward_count: f64,
+    reward_sum: f64,
+    reward_sq_sum: f64,
+}
+
+impl<S: GenericState> Default for Posterior<S> {
+    fn default() -> Self {
+        Self {
+            next_state_counts: BTreeMap::new(),
+            reward_count: 0.0,
+            reward_sum: 0.0,
+            reward_sq_sum: 0.0,
+        }
+    }
+}
+
+// posterior sampling for reinforcement learning (Osband et al. 2013). Every episode an mdp is
+// drawn from Dirichlet posteriors over the next states and normal-gamma posteriors over the
+// rewards, solved with value iteration and followed greedily.
+#[derive(Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "S: Serialize, A: Serialize",
+    deserialize = "S: Deserialize<'de>, A: Deserialize<'de>"
+))]
+pub struct Psrl<S: GenericState, A: GenericAction> {
+    max_steps: usize,
+    tolerance: f64,
+    // pseudo count of every known state in the dirichlet prior
+    dirichlet_prior: f64,
+    reward_prior: NormalGammaPrior,
+    #[serde(with = "entries")]
+    posteriors: BTreeMap<(S, A), Posterior<S>>,
+    // the agent never sees the mdp, only the terminal states it reached so far
+    terminal_states: BTreeSet<S>,
+    discount_factor: f64,
+}
+
+impl<S: GenericState, A: GenericAction> Psrl<S, A> {
+    pub fn new<M: GenericMdp<S, A>>(
+        max_steps: usize,
+        tolerance: f64,
+        dirichlet_prior: f64,
+        reward_prior: NormalGammaPrior,
+        mdp: &M,
+    ) -> Self {
+        Self {
+            max_steps,
+            tolerance,
+            dirichlet_prior,
+            reward_prior,
+            posteriors: BTreeMap::new(),
+            terminal_states: BTreeSet::new(),
+            discount_factor: mdp.get_discount_factor(),
+        }
+    }
+
+    pub fn clear_posterior(&mut self) {
+        self.posteriors.clear();
+        self.terminal_states.clear();
+    }
+
+    fn update_posterior(&mut self, state_action: (S, A), reward: f64, next_state: S) {
+        let posterior = self.posteriors.entry(state_action).or_default();
+        *posterior.next_state_counts.entry(next_state).or_insert(0.0) += 1.0;
+        posterior.reward_count += 1.0;
+        posterior.reward_sum += reward;
+        posterior.reward_sq_sum += reward * reward;
+    }
+
+    fn sample_mdp<F: Fn(S) -> bool, R: Rng>(
+        &self,
+        state_actions: &[(S, A)],
+        is_terminal: F,
+        discount_factor: f64,
+        initial_state: S,
+        rng: &mut R,
+    ) -> MapMdp<S, A> {
+        // the prior covers every state the agent knows of
+        let mut states: BTreeSet<S> = state_actions.iter().map(|(state, _)| *state).collect();
+        self.posteriors.values().for_each(|posterior| {
+            states.extend(posterior.next_state_counts.keys().copied());
+        });
+
+        let mut sampled = MapMdp::new(discount_factor.min(MAX_PLANNING_DISCOUNT), initial_state);
+        states
+            .iter()
+            .filter(|state| is_terminal(**state))
+            .for_each(|state| sampled.add_terminal_state(*state));
+
+        let empty = Posterior::default();
+        for state_action in state_actions {
+            if is_terminal(state_action.0) {
+                continue;
+            }
+            let posterior = self.posteriors.get(state_action).unwrap_or(&empty);
+
+            let weights: Vec<f64> = states
+                .iter()
+                .map(|state| {
+                    let count = posterior.next_state_counts.get(state).unwrap_or(&0.0);
+                    sample_gamma(count + self.dirichlet_prior, rng)
+                })
+                .collect();
+            let total: f64 = weights.iter().sum();
+            let reward = self.sample_mean_reward(posterior, rng);
+
+            let transitions = states
+                .iter()
+                .zip(weights)
+                .filter(|(_, weight)| *weight > 0.0)
+                .map(|(state, weight)| (weight / total, *state, reward))
+                .collect();
+            sampled
+                .add_transition_vector(*state_action, transitions)
+                .expect("state actions of the mdp are unique");
+        }
+
+        sampled
+    }
+
+    fn sample_mean_reward<R: Rng>(&self, posterior: &Posterior<S>, rng: &mut R) -> f64 {
+        let prior = self.reward_prior;
+        let n = posterior.reward_count;
+        let sample_mean = if n > 0.0 {
+            posterior.reward_sum / n
+        } else {
+            0.0
+        };
+        let squared_deviations = (posterior.reward_sq_sum - n * sample_mean * sample_mean).max(0.0);
+
+        let kappa = prior.kappa + n;
+        let mean = (prior.kappa * prior.mean + n * sample_mean) / kappa;
+        let alpha = prior.alpha + n / 2.0;
+        let beta = prior.beta
+            + 0.5 * squared_deviations
+            + prior.kappa * n * (sample_mean - prior.mean).powi(2) / (2.0 * kappa);
+
+        let precision = Gamma::new(alpha, 1.0 / beta)
+            .expect("valid gamma parameters")
+            .sample(rng);
+        Normal::new(mean, (1.0 / (kappa * precision)).sqrt())
+            .expect("valid normal parameters")
+            .sample(rng)
+    }
+}
+
+fn sample_gamma<R: Rng>(shape: f64, rng: &mut R) -> f64 {
+    if shape <= 0.0 {
+        return 0.0;
+    }
+    Gamma::new(shape, 1.0)
+        .expect("valid gamma parameters")
+        .sample(rng)
+}
+
+// the sampled mdps cover the state action pairs of the q_map, which holds the action values of the
+// mdp sampled for the current episode
+impl<S: GenericState, A: GenericAction> TabularAlgorithm<S, A> for Psrl<S, A> {
+    type Memory = ();
+
+    fn max_steps(&self) -> usize {
+        self.max_steps
+    }
+
+    fn begin_episode<R: Rng>(
+        &mut self,
+        _memory: &mut (),
+        q_map: &mut BTreeMap<(S, A), f64>,
+        rng: &mut R,
+    ) {
+        let state_actions: Vec<(S, A)> = q_map.keys().copied().collect();
+        let Some((initial_state, _)) = state_actions.first().copied() else {
+            return;
+        };
+
+        let sampled = self.sample_mdp(
+            &state_actions,
+            |state| self.terminal_states.contains(&state),
+            self.discount_factor,
+            initial_state,
+            rng,
+        );
+        let values = value_iteration(&sampled, self.tolerance);
+        q_map.extend(q_map_from_values(&sampled, &values));
+    }
+
+    fn act<R: Rng>(
+        &mut self,
+        _memory: &mut (),
+        q_map: &BTreeMap<(S, A), f64>,
+        state: S,
+        possible_actions: &[A],
+        rng: &mut R,
+    ) -> Option<A> {
+        greedy_policy_ma(possible_actions, q_map, state, rng)
+    }
+
+    fn observe<R: Rng>(
+        &mut self,
+        _memory: &mut (),
+        _q_map: &mut BTreeMap<(S, A), f64>,
+        observation: &Observation<S, A>,
+        _rng: &mut R,
+    ) -> Option<f64> {
+        if observation.terminal {
+            self.terminal_states.insert(observation.next_state);
+        }
+        self.discount_factor = observation.discount_factor;
+        self.update_posterior(
+            (observation.state, observation.action),
+            observation.reward,
+            observation.next_state,
+        );
+        None
+    }
+}
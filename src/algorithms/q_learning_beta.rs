@@ -1,12 +1,17 @@
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
-use crate::mdp::{GenericAction, GenericMdp, GenericState};
+use crate::{
+    agent::Observation,
+    mdp::{GenericAction, GenericState},
+};
 use std::collections::BTreeMap;
 
-use crate::policies::{epsilon_greedy_policy, greedy_policy};
+use crate::policies::epsilon_greedy_policy_ma;
 
-use super::GenericStateActionAlgorithmStateful;
+use super::{greedy_next_q, TabularAlgorithm};
 
+#[derive(Serialize, Deserialize)]
 pub struct QLearningBeta {
     alpha: f64,
     epsilon: f64,
@@ -27,55 +32,74 @@ impl QLearningBeta {
             total_episodes: 0,
         }
     }
-}
 
-impl GenericStateActionAlgorithmStateful for QLearningBeta {
-    fn run_with_q_map<M: GenericMdp<S, A>, S: GenericState, A: GenericAction, R: Rng>(
-        &mut self,
-        mdp: &M,
-        episodes: usize,
-        rng: &mut R,
-        q_map: &mut BTreeMap<(S, A), f64>,
-    ) {
-        for _ in 1..=episodes {
-            let mut current_state = mdp.get_initial_state(rng);
-            let mut steps = 0;
+    // beta of the episode that is about to start
+    fn next_beta(&mut self) -> f64 {
+        if self.total_episodes % self.rate == 0 {
+            self.beta_denom += 1.0;
+        }
 
-            if self.total_episodes % self.rate == 0 {
-                self.beta_denom += 1.0;
-                // let beta = 1.0 / (self.beta_denom + 1.0);
-                // println!("beta={beta}");
-            }
+        1.0 / (self.beta_denom + 1.0)
+    }
+}
 
-            let beta = 1.0 / (self.beta_denom + 1.0);
+// the memory is the beta of the current episode
+impl<S: GenericState, A: GenericAction> TabularAlgorithm<S, A> for QLearningBeta {
+    type Memory = f64;
 
-            while !mdp.is_terminal(current_state) && steps < self.max_steps {
-                let Some(selected_action) =
-                    epsilon_greedy_policy(mdp, q_map, current_state, self.epsilon, rng)
-                else {
-                    break;
-                };
-                let (next_state, reward) =
-                    mdp.perform_action((current_state, selected_action), rng);
+    fn max_steps(&self) -> usize {
+        self.max_steps
+    }
 
-                // update q_map
-                let Some(best_action) = greedy_policy(mdp, q_map, next_state, rng) else {
-                    break;
-                };
-                let best_q = *q_map
-                    .get(&(next_state, best_action))
-                    .expect("No qmap entry found");
+    fn begin_episode<R: Rng>(
+        &mut self,
+        beta: &mut f64,
+        _q_map: &mut BTreeMap<(S, A), f64>,
+        _rng: &mut R,
+    ) {
+        *beta = self.next_beta();
+    }
 
-                let current_q = q_map.entry((current_state, selected_action)).or_insert(0.0);
-                *current_q = (*current_q
-                    + (self.alpha * (reward + mdp.get_discount_factor() * best_q - *current_q)))
-                    * (1.0 - beta);
+    fn act<R: Rng>(
+        &mut self,
+        _beta: &mut f64,
+        q_map: &BTreeMap<(S, A), f64>,
+        state: S,
+        possible_actions: &[A],
+        rng: &mut R,
+    ) -> Option<A> {
+        epsilon_greedy_policy_ma(possible_actions, q_map, state, self.epsilon, rng)
+    }
 
-                current_state = next_state;
+    fn observe<R: Rng>(
+        &mut self,
+        beta: &mut f64,
+        q_map: &mut BTreeMap<(S, A), f64>,
+        observation: &Observation<S, A>,
+        rng: &mut R,
+    ) -> Option<f64> {
+        let best_q = greedy_next_q(
+            q_map,
+            observation.next_state,
+            &observation.next_possible_actions,
+            observation.terminal,
+            rng,
+        );
 
-                steps += 1;
-            }
-            self.total_episodes += 1;
-        }
+        let current_q = q_map
+            .entry((observation.state, observation.action))
+            .or_insert(0.0);
+        let td_error = observation.reward + observation.discount_factor * best_q - *current_q;
+        *current_q = (*current_q + self.alpha * td_error) * (1.0 - *beta);
+        Some(td_error)
+    }
+
+    fn end_episode<R: Rng>(
+        &mut self,
+        _beta: &mut f64,
+        _q_map: &mut BTreeMap<(S, A), f64>,
+        _rng: &mut R,
+    ) {
+        self.total_episodes += 1;
     }
 }
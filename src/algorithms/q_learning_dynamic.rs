@@ -1,14 +1,17 @@
 use std::{collections::BTreeMap, iter::zip};
 
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    mdp::{GenericAction, GenericMdp, GenericState},
-    policies::{epsilon_greedy_policy, greedy_policy},
+    agent::Observation,
+    mdp::{GenericAction, GenericState},
+    policies::epsilon_greedy_policy_ma,
 };
 
-use super::GenericStateActionAlgorithm;
+use super::{greedy_next_q, TabularAlgorithm};
 
+#[derive(Serialize, Deserialize)]
 pub struct QLearningDynamic {
     alpha: f64,
     epsilon: f64,
@@ -23,87 +26,90 @@ impl QLearningDynamic {
             max_steps,
         }
     }
-}
 
-impl GenericStateActionAlgorithm for QLearningDynamic {
-    fn run_with_q_map<M: GenericMdp<S, A>, S: GenericState, A: GenericAction, R: Rng>(
+    // the step size grows with the mean squared change of the q-values in the last episode
+    fn episode_alpha<S: GenericState, A: GenericAction>(
         &self,
-        mdp: &M,
-        episodes: usize,
-        rng: &mut R,
-        q_map: &mut BTreeMap<(S, A), f64>,
-    ) {
-        let mut prev_q_map: BTreeMap<(S, A), f64> = BTreeMap::new();
-
-        mdp.get_all_state_actions().iter().for_each(|state_action| {
-            prev_q_map.insert(*state_action, 0.0);
-        });
-
-        for episode in 1..=episodes {
-            let mut alpha = self.alpha;
-            if episode > 1 {
-                // calculate the mean squared error
-                let mut acc = 0.0;
-                let mut max = f64::MIN;
-                // let non_zero = q_map
-                //     .values()
-                //     .fold(0, |acc, elem| if *elem != 0.0 { acc + 1 } else { acc });
-
-                for (entry1, entry2) in zip(prev_q_map.iter(), q_map.iter()) {
-                    acc += (*entry1.1 - *entry2.1).powi(2);
-                    if *entry2.1 > max {
-                        max = *entry2.1;
-                    }
-                }
-                acc /= q_map.len() as f64;
-                // acc = acc / (max + 0.0001);
-
-                // println!("max = {max}");
-                // println!("acc = {acc}");
-                // println!("Non-zero = {non_zero}");
-                alpha += acc;
-                alpha = alpha.clamp(self.alpha / 2.0, self.alpha * 2.0);
-            }
-            // println!("alpha = {alpha}");
+        prev_q_map: &BTreeMap<(S, A), f64>,
+        q_map: &BTreeMap<(S, A), f64>,
+    ) -> f64 {
+        let mut acc = 0.0;
+        for (entry1, entry2) in zip(prev_q_map.iter(), q_map.iter()) {
+            acc += (*entry1.1 - *entry2.1).powi(2);
+        }
+        acc /= q_map.len() as f64;
 
-            // prev_q_map.extend(q_map.iter());
-            prev_q_map = q_map.clone();
-            // println!("q_map:");
-            // print_q_map(q_map);
-            // println!("prev_q_map:");
-            // print_q_map(&prev_q_map);
+        (self.alpha + acc).clamp(self.alpha / 2.0, self.alpha * 2.0)
+    }
+}
 
-            let mut current_state = mdp.get_initial_state(rng);
-            let mut steps = 0;
+// step size of the current episode and the q_map it started with, the first episode of a run uses
+// the base step size
+pub struct EpisodeAlpha<S, A> {
+    alpha: f64,
+    prev_q_map: Option<BTreeMap<(S, A), f64>>,
+}
 
-            while !mdp.is_terminal(current_state) && steps < self.max_steps {
-                let Some(selected_action) =
-                    epsilon_greedy_policy(mdp, q_map, current_state, self.epsilon, rng)
-                else {
-                    break;
-                };
-                let (next_state, reward) =
-                    mdp.perform_action((current_state, selected_action), rng);
+impl<S, A> Default for EpisodeAlpha<S, A> {
+    fn default() -> Self {
+        Self {
+            alpha: 0.0,
+            prev_q_map: None,
+        }
+    }
+}
 
-                // update q_map
-                let Some(best_action) = greedy_policy(mdp, q_map, next_state, rng) else {
-                    break;
-                };
-                let best_q = *q_map
-                    .get(&(next_state, best_action))
-                    .expect("No qmap entry found");
+impl<S: GenericState, A: GenericAction> TabularAlgorithm<S, A> for QLearningDynamic {
+    type Memory = EpisodeAlpha<S, A>;
 
-                let current_q = q_map.entry((current_state, selected_action)).or_insert(0.0);
-                *current_q =
-                    *current_q + alpha * (reward + mdp.get_discount_factor() * best_q - *current_q);
+    fn max_steps(&self) -> usize {
+        self.max_steps
+    }
 
-                current_state = next_state;
+    fn begin_episode<R: Rng>(
+        &mut self,
+        memory: &mut EpisodeAlpha<S, A>,
+        q_map: &mut BTreeMap<(S, A), f64>,
+        _rng: &mut R,
+    ) {
+        memory.alpha = match &memory.prev_q_map {
+            Some(prev_q_map) => self.episode_alpha(prev_q_map, q_map),
+            None => self.alpha,
+        };
+        memory.prev_q_map = Some(q_map.clone());
+    }
 
-                steps += 1;
-            }
-        }
+    fn act<R: Rng>(
+        &mut self,
+        _memory: &mut EpisodeAlpha<S, A>,
+        q_map: &BTreeMap<(S, A), f64>,
+        state: S,
+        possible_actions: &[A],
+        rng: &mut R,
+    ) -> Option<A> {
+        epsilon_greedy_policy_ma(possible_actions, q_map, state, self.epsilon, rng)
     }
-    fn get_epsilon(&self) -> f64 {
-        self.epsilon
+
+    fn observe<R: Rng>(
+        &mut self,
+        memory: &mut EpisodeAlpha<S, A>,
+        q_map: &mut BTreeMap<(S, A), f64>,
+        observation: &Observation<S, A>,
+        rng: &mut R,
+    ) -> Option<f64> {
+        let best_q = greedy_next_q(
+            q_map,
+            observation.next_state,
+            &observation.next_possible_actions,
+            observation.terminal,
+            rng,
+        );
+
+        let current_q = q_map
+            .entry((observation.state, observation.action))
+            .or_insert(0.0);
+        let td_error = observation.reward + observation.discount_factor * best_q - *current_q;
+        *current_q += memory.alpha * td_error;
+        Some(td_error)
     }
 }
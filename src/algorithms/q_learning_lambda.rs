@@ -1,14 +1,17 @@
 use std::collections::BTreeMap;
 
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    mdp::{GenericAction, GenericMdp, GenericState},
-    policies::{epsilon_greedy_policy, greedy_policy},
+    agent::Observation,
+    mdp::{GenericAction, GenericState},
+    policies::{epsilon_greedy_policy_ma, greedy_policy_ma},
 };
 
-use super::{GenericStateActionAlgorithm, Trace};
+use super::{TabularAlgorithm, Trace, Traces};
 
+#[derive(Serialize, Deserialize)]
 pub struct QLearningLambda {
     alpha: f64,
     epsilon: f64,
@@ -29,85 +32,90 @@ impl QLearningLambda {
     }
 }
 
-impl GenericStateActionAlgorithm for QLearningLambda {
-    fn run_with_q_map<M: GenericMdp<S, A>, S: GenericState, A: GenericAction, R: Rng>(
-        &self,
-        mdp: &M,
-        episodes: usize,
-        rng: &mut R,
-        q_map: &mut BTreeMap<(S, A), f64>,
-    ) {
-        for _ in 0..episodes {
-            let mut e_map: BTreeMap<(S, A), f64> = BTreeMap::new();
-            mdp.get_all_state_actions().iter().for_each(|state_action| {
-                e_map.insert(*state_action, 0.0);
-            });
-
-            let (mut current_state, mut current_action) = (
-                mdp.get_initial_state(rng),
-                epsilon_greedy_policy(mdp, q_map, mdp.get_initial_state(rng), self.epsilon, rng)
-                    .unwrap(),
-            );
-            let mut steps = 0;
-
-            while !mdp.is_terminal(current_state) && steps < self.max_steps {
-                let (next_state, reward) = mdp.perform_action((current_state, current_action), rng);
-
-                let Some(next_action) =
-                    epsilon_greedy_policy(mdp, q_map, next_state, self.epsilon, rng)
-                else {
-                    break;
-                };
-                let Some(best_action) = greedy_policy(mdp, q_map, next_state, rng) else {
-                    break;
-                };
-
-                // update q_map
-                let next_q = *q_map.get(&(next_state, best_action)).unwrap();
-                let current_q = *q_map.get(&(current_state, current_action)).unwrap();
-
-                let delta = reward + mdp.get_discount_factor() * next_q - current_q;
-
-                e_map
-                    .entry((current_state, current_action))
-                    .and_modify(|entry| {
-                        // if *entry > 0.0 {
-                        //     println!("hooray");
-                        //     println!("old e: {:?}", *entry);
-                        // }
-                        // println!("old e: {:?}", *entry);
-                        *entry = self.trace.calculate(*entry, self.alpha);
-                        // println!("new e: {:?}", *entry);
-                    });
-
-                let _test_e = e_map.entry((current_state, current_action));
-                // println!("{:?}", test_e);
+impl<S: GenericState, A: GenericAction> TabularAlgorithm<S, A> for QLearningLambda {
+    type Memory = Traces<S, A>;
 
-                // update q and e for all (state, action) pairs
-                mdp.get_all_state_actions().iter().for_each(|key| {
-                    // println!("iterating");
-                    let e_entry = e_map.get_mut(key).expect("no e-entry");
+    fn max_steps(&self) -> usize {
+        self.max_steps
+    }
 
-                    q_map.entry(*key).and_modify(|q_entry| {
-                        *q_entry += self.alpha * delta * *e_entry;
-                    });
-                    if next_action == best_action {
-                        // println!("next_action == best_action");
-                        *e_entry *= mdp.get_discount_factor() * self.lambda;
-                    } else {
-                        // println!("doesn't");
-                        *e_entry = 0.0;
-                    }
-                });
+    fn act<R: Rng>(
+        &mut self,
+        memory: &mut Traces<S, A>,
+        q_map: &BTreeMap<(S, A), f64>,
+        state: S,
+        possible_actions: &[A],
+        rng: &mut R,
+    ) -> Option<A> {
+        memory
+            .next_action
+            .take(state)
+            .or_else(|| epsilon_greedy_policy_ma(possible_actions, q_map, state, self.epsilon, rng))
+    }
 
-                current_state = next_state;
-                current_action = next_action;
+    fn observe<R: Rng>(
+        &mut self,
+        memory: &mut Traces<S, A>,
+        q_map: &mut BTreeMap<(S, A), f64>,
+        observation: &Observation<S, A>,
+        rng: &mut R,
+    ) -> Option<f64> {
+        // a terminal state or a state without actions has no value
+        let (next_action, best_action) = if observation.terminal {
+            (None, None)
+        } else {
+            (
+                epsilon_greedy_policy_ma(
+                    &observation.next_possible_actions,
+                    q_map,
+                    observation.next_state,
+                    self.epsilon,
+                    rng,
+                ),
+                greedy_policy_ma(
+                    &observation.next_possible_actions,
+                    q_map,
+                    observation.next_state,
+                    rng,
+                ),
+            )
+        };
+        let next_q = best_action
+            .and_then(|action| q_map.get(&(observation.next_state, action)))
+            .copied()
+            .unwrap_or(0.0);
+        let current_q = *q_map
+            .get(&(observation.state, observation.action))
+            .unwrap_or(&0.0);
+
+        let delta = observation.reward + observation.discount_factor * next_q - current_q;
+
+        let e_entry = memory
+            .traces
+            .entry((observation.state, observation.action))
+            .or_insert(0.0);
+        *e_entry = self.trace.calculate(*e_entry, self.alpha);
+
+        for (key, e_entry) in memory.traces.iter_mut() {
+            *q_map.entry(*key).or_insert(0.0) += self.alpha * delta * *e_entry;
+            *e_entry *= observation.discount_factor * self.lambda;
+        }
 
-                steps += 1;
-            }
+        // traces are cut after exploratory actions
+        if next_action != best_action {
+            memory.traces.clear();
         }
+
+        memory.next_action.set(observation.next_state, next_action);
+        Some(delta)
     }
-    fn get_epsilon(&self) -> f64 {
-        self.epsilon
+
+    fn end_episode<R: Rng>(
+        &mut self,
+        memory: &mut Traces<S, A>,
+        _q_map: &mut BTreeMap<(S, A), f64>,
+        _rng: &mut R,
+    ) {
+        *memory = Traces::default();
     }
 }
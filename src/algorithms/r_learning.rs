@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    agent::Observation,
+    mdp::{GenericAction, GenericState},
+    policies::{epsilon_greedy_policy_ma, greedy_policy_ma},
+};
+
+use super::TabularAlgorithm;
+
+// R-learning (Schwartz 1993) for continuing tasks, learns the gain rho together with the relative
+// action values instead of discounting
+#[derive(Serialize, Deserialize)]
+pub struct RLearning {
+    alpha: f64,
+    beta: f64,
+    epsilon: f64,
+    max_steps: usize,
+    rho: f64,
+}
+
+impl RLearning {
+    pub fn new(alpha: f64, beta: f64, epsilon: f64, max_steps: usize) -> Self {
+        RLearning {
+            alpha,
+            beta,
+            epsilon,
+            max_steps,
+            rho: 0.0,
+        }
+    }
+
+    pub fn get_rho(&self) -> f64 {
+        self.rho
+    }
+
+    pub fn reset_rho(&mut self) {
+        self.rho = 0.0;
+    }
+}
+
+// trained with run_continuing on continuing tasks, run restarts from an initial state every
+// max_steps steps like for any other algorithm. The memory holds the possible actions of the state
+// the agent acted in, the update compares against its best action.
+impl<S: GenericState, A: GenericAction> TabularAlgorithm<S, A> for RLearning {
+    type Memory = Vec<A>;
+
+    fn max_steps(&self) -> usize {
+        self.max_steps
+    }
+
+    fn act<R: Rng>(
+        &mut self,
+        current_actions: &mut Vec<A>,
+        q_map: &BTreeMap<(S, A), f64>,
+        state: S,
+        possible_actions: &[A],
+        rng: &mut R,
+    ) -> Option<A> {
+        *current_actions = possible_actions.to_vec();
+        epsilon_greedy_policy_ma(possible_actions, q_map, state, self.epsilon, rng)
+    }
+
+    fn observe<R: Rng>(
+        &mut self,
+        current_actions: &mut Vec<A>,
+        q_map: &mut BTreeMap<(S, A), f64>,
+        observation: &Observation<S, A>,
+        rng: &mut R,
+    ) -> Option<f64> {
+        let max_current = max_q(current_actions, q_map, observation.state, rng)?;
+        let max_next = if observation.terminal {
+            0.0
+        } else {
+            max_q(
+                &observation.next_possible_actions,
+                q_map,
+                observation.next_state,
+                rng,
+            )
+            .unwrap_or(0.0)
+        };
+
+        let current_q = q_map
+            .entry((observation.state, observation.action))
+            .or_insert(0.0);
+
+        // rho is only updated on greedy steps, exploratory actions would bias the gain estimate
+        let greedy = *current_q >= max_current;
+
+        let td_error = observation.reward - self.rho + max_next - *current_q;
+        *current_q += self.alpha * td_error;
+
+        if greedy {
+            self.rho += self.beta * (observation.reward - self.rho + max_next - max_current);
+        }
+        Some(td_error)
+    }
+}
+
+fn max_q<S: GenericState, A: GenericAction, R: Rng>(
+    possible_actions: &[A],
+    q_map: &BTreeMap<(S, A), f64>,
+    state: S,
+    rng: &mut R,
+) -> Option<f64> {
+    let best_action = greedy_policy_ma(possible_actions, q_map, state, rng)?;
+    Some(
+        *q_map
+            .get(&(state, best_action))
+            .expect("No qmap entry found"),
+    )
+}
@@ -0,0 +1,85 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::mdp::{GenericAction, GenericState, MapMdp};
+
+// mixes each backup with the previous values, this makes the iteration converge on periodic mdps
+const APERIODICITY: f64 = 0.5;
+
+// relative value iteration for the average reward criterion, returns the optimal gain and the
+// relative values (bias) normalised to 0 at the initial state. The discount factor is ignored,
+// terminal states and states without actions are treated as absorbing with zero reward. The gain
+// is only well defined if every policy reaches the same recurrent class, on a multichain mdp (a
+// terminal state next to a rewarding cycle) the iteration never settles and errors after
+// max_iterations.
+pub fn relative_value_iteration<S: GenericState, A: GenericAction>(
+    mdp: &MapMdp<S, A>,
+    tolerance: f64,
+    max_iterations: usize,
+) -> anyhow::Result<(f64, BTreeMap<S, f64>)> {
+    let mut states: BTreeSet<S> = BTreeSet::new();
+    for ((state, _), transitions) in mdp.transitions.iter() {
+        states.insert(*state);
+        transitions.iter().for_each(|(_, next_state, _)| {
+            states.insert(*next_state);
+        });
+    }
+    let reference_state = mdp.initial_state;
+
+    let mut value_map: BTreeMap<S, f64> = states.iter().map(|state| (*state, 0.0)).collect();
+
+    for _ in 0..max_iterations {
+        let best_values = best_action_values(mdp, &value_map);
+        let backup: BTreeMap<S, f64> = states
+            .iter()
+            .map(|state| {
+                let old_value = value_map[state];
+                let new_value = *best_values.get(state).unwrap_or(&old_value);
+                (
+                    *state,
+                    APERIODICITY * new_value + (1.0 - APERIODICITY) * old_value,
+                )
+            })
+            .collect();
+
+        // span of the change bounds the error of the gain estimate
+        let (min_diff, max_diff) = states.iter().fold((f64::MAX, f64::MIN), |(min, max), s| {
+            let diff = backup[s] - value_map[s];
+            (min.min(diff), max.max(diff))
+        });
+
+        let offset = *backup.get(&reference_state).unwrap_or(&0.0);
+        value_map = backup
+            .into_iter()
+            .map(|(state, value)| (state, value - offset))
+            .collect();
+
+        if max_diff - min_diff < tolerance {
+            // the transformation scales the gain but leaves the relative values untouched
+            return Ok((offset / APERIODICITY, value_map));
+        }
+    }
+
+    anyhow::bail!("relative value iteration did not converge in {max_iterations} iterations")
+}
+
+// best one step value of every non terminal state with actions, in one pass over the transitions
+fn best_action_values<S: GenericState, A: GenericAction>(
+    mdp: &MapMdp<S, A>,
+    value_map: &BTreeMap<S, f64>,
+) -> BTreeMap<S, f64> {
+    let mut best_values: BTreeMap<S, f64> = BTreeMap::new();
+    for ((state, _), transitions) in mdp.transitions.iter() {
+        if mdp.terminal_states.contains(state) {
+            continue;
+        }
+        let value = transitions
+            .iter()
+            .map(|(prob, next_state, reward)| prob * (reward + value_map[next_state]))
+            .sum::<f64>();
+        best_values
+            .entry(*state)
+            .and_modify(|best| *best = best.max(value))
+            .or_insert(value);
+    }
+    best_values
+}
@@ -0,0 +1,276 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    mem,
+};
+
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    Rng,
+};
+
+use crate::{
+    agent::{runner::Runner, Agent, Observation},
+    mdp::{GenericAction, GenericMdp, GenericState, Reward},
+};
+
+use super::TabularAlgorithm;
+
+// keeps prioritized sampling from starving transitions whose last update was zero
+const PRIORITY_EPSILON: f64 = 1e-6;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Experience<S: GenericState, A: GenericAction> {
+    pub state: S,
+    pub action: A,
+    pub reward: Reward,
+    pub next_state: S,
+    // stored so a transition can be replayed without the mdp
+    pub next_possible_actions: Vec<A>,
+    pub terminal: bool,
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum Sampling {
+    Uniform,
+    // proportional prioritization, a transition is drawn with probability ~ priority^alpha where
+    // the priority is the size of its last update
+    Prioritized { alpha: f64 },
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ReplayBuffer<S: GenericState, A: GenericAction> {
+    capacity: usize,
+    replay_ratio: f64,
+    sampling: Sampling,
+    experiences: VecDeque<Experience<S, A>>,
+    priorities: VecDeque<f64>,
+    max_priority: f64,
+    pending_replays: f64,
+}
+
+impl<S: GenericState, A: GenericAction> ReplayBuffer<S, A> {
+    // replay_ratio is the number of replayed updates per stored transition, fractional ratios are
+    // carried over to later calls
+    pub fn new(capacity: usize, replay_ratio: f64, sampling: Sampling) -> Self {
+        assert!(capacity > 0, "replay buffer capacity must be positive");
+        assert!(replay_ratio >= 0.0, "replay ratio must not be negative");
+
+        Self {
+            capacity,
+            replay_ratio,
+            sampling,
+            experiences: VecDeque::with_capacity(capacity),
+            priorities: VecDeque::with_capacity(capacity),
+            max_priority: 1.0,
+            pending_replays: 0.0,
+        }
+    }
+
+    pub fn uniform(capacity: usize, replay_ratio: f64) -> Self {
+        Self::new(capacity, replay_ratio, Sampling::Uniform)
+    }
+
+    pub fn prioritized(capacity: usize, replay_ratio: f64, alpha: f64) -> Self {
+        Self::new(capacity, replay_ratio, Sampling::Prioritized { alpha })
+    }
+
+    pub fn len(&self) -> usize {
+        self.experiences.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.experiences.is_empty()
+    }
+
+    pub fn get_capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn get_replay_ratio(&self) -> f64 {
+        self.replay_ratio
+    }
+
+    pub fn clear(&mut self) {
+        self.experiences.clear();
+        self.priorities.clear();
+        self.max_priority = 1.0;
+        self.pending_replays = 0.0;
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Experience<S, A>> {
+        self.experiences.get(index)
+    }
+
+    pub fn get_priority(&self, index: usize) -> Option<f64> {
+        self.priorities.get(index).copied()
+    }
+
+    // new transitions get the highest priority seen so far, so they are replayed at least once
+    // with high probability; the oldest transition is dropped once the buffer is full
+    pub fn push(&mut self, experience: Experience<S, A>) {
+        if self.experiences.len() == self.capacity {
+            self.experiences.pop_front();
+            self.priorities.pop_front();
+        }
+        self.experiences.push_back(experience);
+        self.priorities.push_back(self.max_priority);
+        self.pending_replays += self.replay_ratio;
+    }
+
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> Option<usize> {
+        if self.experiences.is_empty() {
+            return None;
+        }
+
+        match self.sampling {
+            Sampling::Uniform => Some(rng.gen_range(0..self.experiences.len())),
+            Sampling::Prioritized { alpha } => {
+                let weights = self
+                    .priorities
+                    .iter()
+                    .map(|priority| (priority + PRIORITY_EPSILON).powf(alpha));
+                let distribution = WeightedIndex::new(weights).ok()?;
+                Some(distribution.sample(rng))
+            }
+        }
+    }
+
+    pub fn update_priority(&mut self, index: usize, priority: f64) {
+        let priority = priority.abs();
+        if let Some(p) = self.priorities.get_mut(index) {
+            *p = priority;
+            self.max_priority = self.max_priority.max(priority);
+        }
+    }
+
+    // performs the replays that are due according to the replay ratio, update is called for every
+    // sampled transition and returns its new priority. Returns the number of replayed transitions.
+    pub fn replay_with<R: Rng, F: FnMut(&Experience<S, A>, &mut R) -> f64>(
+        &mut self,
+        rng: &mut R,
+        mut update: F,
+    ) -> usize {
+        if self.experiences.is_empty() {
+            return 0;
+        }
+
+        let due = self.pending_replays.floor() as usize;
+        self.pending_replays -= due as f64;
+
+        for _ in 0..due {
+            let Some(index) = self.sample(rng) else {
+                return 0;
+            };
+            let priority = update(&self.experiences[index], rng);
+            self.update_priority(index, priority);
+        }
+
+        due
+    }
+
+    // replays through the update of an algorithm without episode memory, the priority is the
+    // change of the q-value
+    pub fn replay<G: TabularAlgorithm<S, A, Memory = ()>, R: Rng>(
+        &mut self,
+        algo: &mut G,
+        q_map: &mut BTreeMap<(S, A), f64>,
+        discount_factor: f64,
+        rng: &mut R,
+    ) -> usize {
+        self.replay_with(rng, |experience, rng| {
+            let key = (experience.state, experience.action);
+            let old_q = *q_map.get(&key).unwrap_or(&0.0);
+
+            algo.observe(
+                &mut (),
+                q_map,
+                &experience.observation(discount_factor),
+                rng,
+            );
+
+            q_map.get(&key).unwrap_or(&0.0) - old_q
+        })
+    }
+}
+
+impl<S: GenericState, A: GenericAction> Experience<S, A> {
+    pub fn observation(&self, discount_factor: f64) -> Observation<S, A> {
+        Observation {
+            state: self.state,
+            action: self.action,
+            reward: self.reward,
+            next_state: self.next_state,
+            next_possible_actions: self.next_possible_actions.clone(),
+            terminal: self.terminal,
+            discount_factor,
+        }
+    }
+}
+
+// every real transition is stored in the buffer and followed by the replays that are due
+struct ReplayAgent<'a, G, S: GenericState, A: GenericAction> {
+    algorithm: &'a mut G,
+    q_map: BTreeMap<(S, A), f64>,
+    buffer: &'a mut ReplayBuffer<S, A>,
+}
+
+impl<G: TabularAlgorithm<S, A, Memory = ()>, S: GenericState, A: GenericAction> Agent<S, A>
+    for ReplayAgent<'_, G, S, A>
+{
+    fn act<R: Rng>(&mut self, state: S, possible_actions: &[A], rng: &mut R) -> Option<A> {
+        self.algorithm
+            .act(&mut (), &self.q_map, state, possible_actions, rng)
+    }
+
+    fn observe<R: Rng>(&mut self, observation: &Observation<S, A>, rng: &mut R) -> Option<f64> {
+        let td_error = self
+            .algorithm
+            .observe(&mut (), &mut self.q_map, observation, rng);
+
+        self.buffer.push(Experience {
+            state: observation.state,
+            action: observation.action,
+            reward: observation.reward,
+            next_state: observation.next_state,
+            next_possible_actions: observation.next_possible_actions.clone(),
+            terminal: observation.terminal,
+        });
+        self.buffer.replay(
+            self.algorithm,
+            &mut self.q_map,
+            observation.discount_factor,
+            rng,
+        );
+        td_error
+    }
+
+    fn q_map(&self) -> &BTreeMap<(S, A), f64> {
+        &self.q_map
+    }
+}
+
+// runs an algorithm without episode memory, e.g. q-learning, with experience replay
+pub fn run_with_replay<
+    G: TabularAlgorithm<S, A, Memory = ()>,
+    M: GenericMdp<S, A>,
+    S: GenericState,
+    A: GenericAction,
+    R: Rng,
+>(
+    algo: &mut G,
+    mdp: &M,
+    episodes: usize,
+    max_steps: usize,
+    rng: &mut R,
+    q_map: &mut BTreeMap<(S, A), f64>,
+    buffer: &mut ReplayBuffer<S, A>,
+) {
+    let mut agent = ReplayAgent {
+        algorithm: algo,
+        q_map: mem::take(q_map),
+        buffer,
+    };
+    Runner::new(max_steps).run(mdp, &mut agent, episodes, rng);
+    *q_map = agent.q_map;
+}
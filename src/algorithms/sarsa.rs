@@ -1,14 +1,17 @@
 use std::collections::BTreeMap;
 
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    mdp::{GenericAction, GenericMdp, GenericState},
-    policies::epsilon_greedy_policy,
+    agent::Observation,
+    mdp::{GenericAction, GenericState},
+    policies::epsilon_greedy_policy_ma,
 };
 
-use super::GenericStateActionAlgorithm;
+use super::{NextAction, TabularAlgorithm};
 
+#[derive(Serialize, Deserialize)]
 pub struct Sarsa {
     alpha: f64,
     epsilon: f64,
@@ -25,45 +28,67 @@ impl Sarsa {
     }
 }
 
-impl GenericStateActionAlgorithm for Sarsa {
-    fn run_with_q_map<M: GenericMdp<S, A>, S: GenericState, A: GenericAction, R: Rng>(
-        &self,
-        mdp: &M,
-        episodes: usize,
-        rng: &mut R,
-        q_map: &mut BTreeMap<(S, A), f64>,
-    ) {
-        for _ in 1..=episodes {
-            let (mut current_state, mut current_action) = (
-                mdp.get_initial_state(rng),
-                epsilon_greedy_policy(mdp, q_map, mdp.get_initial_state(rng), self.epsilon, rng)
-                    .unwrap(),
-            );
-            let mut steps = 0;
+impl<S: GenericState, A: GenericAction> TabularAlgorithm<S, A> for Sarsa {
+    type Memory = NextAction<S, A>;
 
-            while !mdp.is_terminal(current_state) && steps < self.max_steps {
-                let (next_state, reward) = mdp.perform_action((current_state, current_action), rng);
+    fn max_steps(&self) -> usize {
+        self.max_steps
+    }
 
-                let next_action = epsilon_greedy_policy(mdp, q_map, next_state, self.epsilon, rng);
-                let Some(next_action) = next_action else {
-                    break;
-                };
+    fn act<R: Rng>(
+        &mut self,
+        memory: &mut NextAction<S, A>,
+        q_map: &BTreeMap<(S, A), f64>,
+        state: S,
+        possible_actions: &[A],
+        rng: &mut R,
+    ) -> Option<A> {
+        memory
+            .take(state)
+            .or_else(|| epsilon_greedy_policy_ma(possible_actions, q_map, state, self.epsilon, rng))
+    }
 
-                // update q_map
-                let next_q = *q_map.get(&(next_state, next_action)).unwrap_or(&0.0);
-                let current_q = q_map.entry((current_state, current_action)).or_insert(0.0);
-                *current_q = *current_q
-                    + self.alpha * (reward + mdp.get_discount_factor() * next_q - *current_q);
+    // selects the next action right away since the update depends on it
+    fn observe<R: Rng>(
+        &mut self,
+        memory: &mut NextAction<S, A>,
+        q_map: &mut BTreeMap<(S, A), f64>,
+        observation: &Observation<S, A>,
+        rng: &mut R,
+    ) -> Option<f64> {
+        // a terminal state or a state without actions has no value
+        let next_action = if observation.terminal {
+            None
+        } else {
+            epsilon_greedy_policy_ma(
+                &observation.next_possible_actions,
+                q_map,
+                observation.next_state,
+                self.epsilon,
+                rng,
+            )
+        };
+        let next_q = next_action
+            .and_then(|action| q_map.get(&(observation.next_state, action)))
+            .copied()
+            .unwrap_or(0.0);
 
-                current_state = next_state;
-                current_action = next_action;
+        let current_q = q_map
+            .entry((observation.state, observation.action))
+            .or_insert(0.0);
+        let td_error = observation.reward + observation.discount_factor * next_q - *current_q;
+        *current_q += self.alpha * td_error;
 
-                steps += 1;
-            }
-        }
+        memory.set(observation.next_state, next_action);
+        Some(td_error)
     }
 
-    fn get_epsilon(&self) -> f64 {
-        self.epsilon
+    fn end_episode<R: Rng>(
+        &mut self,
+        memory: &mut NextAction<S, A>,
+        _q_map: &mut BTreeMap<(S, A), f64>,
+        _rng: &mut R,
+    ) {
+        *memory = NextAction::default();
     }
 }
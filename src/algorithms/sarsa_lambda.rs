@@ -1,14 +1,17 @@
 use std::collections::BTreeMap;
 
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    mdp::{GenericAction, GenericMdp, GenericState},
-    policies::epsilon_greedy_policy,
+    agent::Observation,
+    mdp::{GenericAction, GenericState},
+    policies::epsilon_greedy_policy_ma,
 };
 
-use super::{GenericStateActionAlgorithm, Trace};
+use super::{TabularAlgorithm, Trace, Traces};
 
+#[derive(Serialize, Deserialize)]
 pub struct SarsaLambda {
     alpha: f64,
     epsilon: f64,
@@ -29,61 +32,78 @@ impl SarsaLambda {
     }
 }
 
-impl GenericStateActionAlgorithm for SarsaLambda {
-    fn run_with_q_map<M: GenericMdp<S, A>, S: GenericState, A: GenericAction, R: Rng>(
-        &self,
-        mdp: &M,
-        episodes: usize,
-        rng: &mut R,
-        q_map: &mut BTreeMap<(S, A), f64>,
-    ) {
-        for _ in 0..episodes {
-            let mut e_map: BTreeMap<(S, A), f64> = BTreeMap::new();
+impl<S: GenericState, A: GenericAction> TabularAlgorithm<S, A> for SarsaLambda {
+    type Memory = Traces<S, A>;
 
-            let (mut current_state, mut current_action) = (
-                mdp.get_initial_state(rng),
-                epsilon_greedy_policy(mdp, q_map, mdp.get_initial_state(rng), self.epsilon, rng)
-                    .unwrap(),
-            );
-            let mut steps = 0;
-
-            while !mdp.is_terminal(current_state) && steps < self.max_steps {
-                let (next_state, reward) = mdp.perform_action((current_state, current_action), rng);
-
-                // select action epsilon greedy and break if no action is possible (episode ends)
-                let Some(next_action) =
-                    epsilon_greedy_policy(mdp, q_map, next_state, self.epsilon, rng)
-                else {
-                    break;
-                };
-                // update q_map
-                let next_q = *q_map.get(&(next_state, next_action)).unwrap();
-                let current_q = *q_map.get(&(current_state, current_action)).unwrap();
+    fn max_steps(&self) -> usize {
+        self.max_steps
+    }
 
-                let delta = reward + mdp.get_discount_factor() * next_q - current_q;
+    fn act<R: Rng>(
+        &mut self,
+        memory: &mut Traces<S, A>,
+        q_map: &BTreeMap<(S, A), f64>,
+        state: S,
+        possible_actions: &[A],
+        rng: &mut R,
+    ) -> Option<A> {
+        memory
+            .next_action
+            .take(state)
+            .or_else(|| epsilon_greedy_policy_ma(possible_actions, q_map, state, self.epsilon, rng))
+    }
 
-                e_map
-                    .entry((current_state, current_action))
-                    .and_modify(|entry| *entry = self.trace.calculate(*entry, self.alpha));
+    fn observe<R: Rng>(
+        &mut self,
+        memory: &mut Traces<S, A>,
+        q_map: &mut BTreeMap<(S, A), f64>,
+        observation: &Observation<S, A>,
+        rng: &mut R,
+    ) -> Option<f64> {
+        // a terminal state or a state without actions has no value
+        let next_action = if observation.terminal {
+            None
+        } else {
+            epsilon_greedy_policy_ma(
+                &observation.next_possible_actions,
+                q_map,
+                observation.next_state,
+                self.epsilon,
+                rng,
+            )
+        };
+        let next_q = next_action
+            .and_then(|action| q_map.get(&(observation.next_state, action)))
+            .copied()
+            .unwrap_or(0.0);
+        let current_q = *q_map
+            .get(&(observation.state, observation.action))
+            .unwrap_or(&0.0);
 
-                // update q and e for all (state, action) pairs
-                mdp.get_all_state_actions().iter().for_each(|key| {
-                    let e_entry = e_map.entry(*key).or_default();
-                    q_map.entry(*key).and_modify(|q_entry| {
-                        *q_entry += self.alpha * delta * *e_entry;
-                    });
-                    *e_entry *= mdp.get_discount_factor() * self.lambda;
-                });
+        let delta = observation.reward + observation.discount_factor * next_q - current_q;
 
-                current_state = next_state;
-                current_action = next_action;
+        let e_entry = memory
+            .traces
+            .entry((observation.state, observation.action))
+            .or_insert(0.0);
+        *e_entry = self.trace.calculate(*e_entry, self.alpha);
 
-                steps += 1;
-            }
+        // only pairs with a trace are affected
+        for (key, e_entry) in memory.traces.iter_mut() {
+            *q_map.entry(*key).or_insert(0.0) += self.alpha * delta * *e_entry;
+            *e_entry *= observation.discount_factor * self.lambda;
         }
+
+        memory.next_action.set(observation.next_state, next_action);
+        Some(delta)
     }
 
-    fn get_epsilon(&self) -> f64 {
-        self.epsilon
+    fn end_episode<R: Rng>(
+        &mut self,
+        memory: &mut Traces<S, A>,
+        _q_map: &mut BTreeMap<(S, A), f64>,
+        _rng: &mut R,
+    ) {
+        *memory = Traces::default();
     }
 }
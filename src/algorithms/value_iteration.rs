@@ -1,19 +1,37 @@
 use std::collections::BTreeMap;
 
-use crate::mdp::{GenericMdp, IndexMdp, IndexState};
+use crate::mdp::{GenericAction, GenericMdp, GenericState, MapMdp};
 
-pub fn value_iteration(mdp: &IndexMdp, tolerance: f64) -> BTreeMap<IndexState, f64> {
-    let mut value_map: BTreeMap<IndexState, f64> = BTreeMap::new();
+// returns the value of every state with transitions. Terminal states keep a value of 0 even if the
+// mdp has transitions for them, episodes end when they are entered so those transitions never pay
+// out.
+pub fn value_iteration<S: GenericState, A: GenericAction>(
+    mdp: &MapMdp<S, A>,
+    tolerance: f64,
+) -> BTreeMap<S, f64> {
+    let mut value_map: BTreeMap<S, f64> = BTreeMap::new();
     let mut delta = f64::MAX;
 
     while delta > tolerance {
         delta = 0.0;
 
-        for (state, _) in mdp.transitions.keys() {
-            let old_value = *value_map.get(state).unwrap_or(&0.0);
-            let new_value = best_action_value(mdp, *state, &value_map);
+        // keys are sorted by state, so the actions of a state are consecutive and one pass over
+        // the transitions is a full sweep. Looking up the actions of every state separately makes
+        // a sweep quadratic in the number of pairs, too slow for the car rental or the gambler's
+        // problem with thousands of them.
+        let mut keys = mdp.transitions.iter().peekable();
+        while let Some(((state, _), transitions)) = keys.next() {
+            let mut new_value = expected_value(mdp, transitions, &value_map);
+            while let Some(((_, _), transitions)) = keys.next_if(|((s, _), _)| s == state) {
+                new_value = new_value.max(expected_value(mdp, transitions, &value_map));
+            }
+
+            if mdp.terminal_states.contains(state) {
+                value_map.insert(*state, 0.0);
+                continue;
+            }
 
-            value_map.insert(*state, new_value);
+            let old_value = value_map.insert(*state, new_value).unwrap_or(0.0);
             delta = delta.max((old_value - new_value).abs());
         }
     }
@@ -21,26 +39,29 @@ pub fn value_iteration(mdp: &IndexMdp, tolerance: f64) -> BTreeMap<IndexState, f
     value_map
 }
 
-fn best_action_value(
-    mdp: &IndexMdp,
-    state: IndexState,
-    value_map: &BTreeMap<IndexState, f64>,
-) -> f64 {
+// one step lookahead on the state values, the greedy policy of the result is optimal if the
+// values are
+pub fn q_map_from_values<S: GenericState, A: GenericAction>(
+    mdp: &MapMdp<S, A>,
+    value_map: &BTreeMap<S, f64>,
+) -> BTreeMap<(S, A), f64> {
     mdp.transitions
         .iter()
-        .filter_map(|((s, _), transitions)| {
-            if *s == state {
-                let expected_value: f64 = transitions
-                    .iter()
-                    .map(|(prob, next_state, reward)| {
-                        prob * (reward
-                            + mdp.get_discount_factor() * value_map.get(next_state).unwrap_or(&0.0))
-                    })
-                    .sum();
-                Some(expected_value)
-            } else {
-                None
-            }
+        .map(|(state_action, transitions)| {
+            (*state_action, expected_value(mdp, transitions, value_map))
+        })
+        .collect()
+}
+
+fn expected_value<S: GenericState, A: GenericAction>(
+    mdp: &MapMdp<S, A>,
+    transitions: &[(f64, S, f64)],
+    value_map: &BTreeMap<S, f64>,
+) -> f64 {
+    transitions
+        .iter()
+        .map(|(prob, next_state, reward)| {
+            prob * (reward + mdp.get_discount_factor() * value_map.get(next_state).unwrap_or(&0.0))
         })
-        .fold(f64::MIN, f64::max)
+        .sum()
 }
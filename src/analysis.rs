@@ -0,0 +1,239 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    agent::{observer::Observer, runner::EpisodeStats},
+    algorithms::value_iteration::{q_map_from_values, value_iteration},
+    mdp::{GenericAction, GenericMdp, GenericState, MapMdp},
+    policies::{GreedyPolicy, Policy, TabularPolicy},
+};
+
+// compares learned q_maps with the exact optimum of an explicit model
+
+pub fn optimal_q_map<S: GenericState, A: GenericAction>(
+    mdp: &MapMdp<S, A>,
+    tolerance: f64,
+) -> BTreeMap<(S, A), f64> {
+    q_map_from_values(mdp, &value_iteration(mdp, tolerance))
+}
+
+// iterative policy evaluation, terminal states and states without actions have a value of 0. The
+// discount factor is passed separately so undiscounted returns can be computed as well, those only
+// converge if the policy reaches a terminal state.
+pub fn policy_values<S: GenericState, A: GenericAction, P: Policy<S, A>>(
+    mdp: &MapMdp<S, A>,
+    policy: &P,
+    discount_factor: f64,
+    tolerance: f64,
+    max_iterations: usize,
+) -> BTreeMap<S, f64> {
+    let actions = actions_by_state(mdp);
+    let mut value_map: BTreeMap<S, f64> = BTreeMap::new();
+
+    for _ in 0..max_iterations {
+        let mut delta: f64 = 0.0;
+        for (state, possible_actions) in actions.iter() {
+            if mdp.terminal_states.contains(state) {
+                continue;
+            }
+            let new_value = policy
+                .action_probabilities(*state, possible_actions)
+                .iter()
+                .map(|(action, prob)| {
+                    prob * mdp.transitions[&(*state, *action)]
+                        .iter()
+                        .map(|(p, next_state, reward)| {
+                            p * (reward
+                                + discount_factor * value_map.get(next_state).unwrap_or(&0.0))
+                        })
+                        .sum::<f64>()
+                })
+                .sum();
+            let old_value = value_map.insert(*state, new_value).unwrap_or(0.0);
+            delta = delta.max((new_value - old_value).abs());
+        }
+        if delta < tolerance {
+            break;
+        }
+    }
+
+    value_map
+}
+
+// expected undiscounted return of the optimal policy from the initial state, the target the
+// greedy evaluation of a learner reaches once its policy is optimal
+pub fn optimal_return<S: GenericState, A: GenericAction>(
+    mdp: &MapMdp<S, A>,
+    tolerance: f64,
+) -> f64 {
+    let optimal = optimal_q_map(mdp, tolerance);
+    let values = policy_values(mdp, &GreedyPolicy::new(&optimal), 1.0, tolerance, 100_000);
+    *values.get(&mdp.initial_state).unwrap_or(&0.0)
+}
+
+// every action within tie_tolerance of the best optimal q value, for all non terminal states.
+// Greedy policies pick one of several optimal actions at random, this keeps all of them. Value
+// iteration stops once a sweep changes the values by less than vi_tolerance, without discounting
+// the remaining error can be much larger than that. tie_tolerance has to stay well above it and
+// below the gap to the best suboptimal action.
+pub fn optimal_actions<S: GenericState, A: GenericAction>(
+    mdp: &MapMdp<S, A>,
+    vi_tolerance: f64,
+    tie_tolerance: f64,
+) -> BTreeMap<S, Vec<A>> {
+    let optimal = optimal_q_map(mdp, vi_tolerance);
+    actions_by_state(mdp)
+        .into_iter()
+        .filter(|(state, _)| !mdp.terminal_states.contains(state))
+        .map(|(state, actions)| {
+            let best = actions
+                .iter()
+                .map(|action| optimal[&(state, *action)])
+                .fold(f64::MIN, f64::max);
+            let optimal_actions = actions
+                .into_iter()
+                .filter(|action| best - optimal[&(state, *action)] <= tie_tolerance)
+                .collect();
+            (state, optimal_actions)
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Suboptimality<S: GenericState> {
+    // over the state action pairs of the mdp, missing q values count as 0
+    pub max_q_error: f64,
+    pub mean_q_error: f64,
+    // share of non terminal states whose greedy action is optimal
+    pub optimal_action_fraction: f64,
+    // V* - V^pi of the greedy policy for every non terminal state
+    pub value_loss: BTreeMap<S, f64>,
+    pub max_value_loss: f64,
+    pub mean_value_loss: f64,
+}
+
+// greedy actions within tolerance of the best optimal action count as optimal
+pub fn suboptimality<S: GenericState, A: GenericAction>(
+    mdp: &MapMdp<S, A>,
+    q_map: &BTreeMap<(S, A), f64>,
+    tolerance: f64,
+) -> Suboptimality<S> {
+    let optimal = optimal_q_map(mdp, tolerance);
+
+    let errors: Vec<f64> = optimal
+        .iter()
+        .map(|(state_action, q)| (q - q_map.get(state_action).unwrap_or(&0.0)).abs())
+        .collect();
+
+    // the greedy policy only sees the pairs of the mdp
+    let learned: BTreeMap<(S, A), f64> = optimal
+        .keys()
+        .map(|state_action| (*state_action, *q_map.get(state_action).unwrap_or(&0.0)))
+        .collect();
+    let greedy = TabularPolicy::from_q_map(&learned);
+    let states: BTreeSet<S> = actions_by_state(mdp)
+        .into_keys()
+        .filter(|state| !mdp.terminal_states.contains(state))
+        .collect();
+    let optimal_actions = states
+        .iter()
+        .filter(|state| {
+            let best = best_value(&optimal, **state);
+            greedy
+                .actions
+                .get(state)
+                .is_some_and(|action| best - optimal[&(**state, *action)] <= tolerance)
+        })
+        .count();
+
+    let optimal_values = value_iteration(mdp, tolerance);
+    let greedy_values = policy_values(
+        mdp,
+        &GreedyPolicy::new(&learned),
+        mdp.get_discount_factor(),
+        tolerance,
+        100_000,
+    );
+    let value_loss: BTreeMap<S, f64> = states
+        .iter()
+        .map(|state| {
+            (
+                *state,
+                optimal_values.get(state).unwrap_or(&0.0)
+                    - greedy_values.get(state).unwrap_or(&0.0),
+            )
+        })
+        .collect();
+
+    Suboptimality {
+        max_q_error: errors.iter().copied().fold(0.0, f64::max),
+        mean_q_error: mean(errors.iter().copied()),
+        optimal_action_fraction: if states.is_empty() {
+            0.0
+        } else {
+            optimal_actions as f64 / states.len() as f64
+        },
+        max_value_loss: value_loss.values().copied().fold(0.0, f64::max),
+        mean_value_loss: mean(value_loss.values().copied()),
+        value_loss,
+    }
+}
+
+// regret of every training episode, the optimal value of the initial state minus the discounted
+// return the agent actually collected, exploration included
+#[derive(Clone, Debug, Default)]
+pub struct RegretTracker {
+    optimal_value: f64,
+    pub regret: Vec<f64>,
+    pub cumulative_regret: Vec<f64>,
+}
+
+impl RegretTracker {
+    pub fn new(optimal_value: f64) -> Self {
+        Self {
+            optimal_value,
+            ..Default::default()
+        }
+    }
+
+    pub fn from_mdp<S: GenericState, A: GenericAction>(mdp: &MapMdp<S, A>, tolerance: f64) -> Self {
+        let values = value_iteration(mdp, tolerance);
+        Self::new(*values.get(&mdp.initial_state).unwrap_or(&0.0))
+    }
+
+    pub fn total(&self) -> f64 {
+        self.cumulative_regret.last().copied().unwrap_or(0.0)
+    }
+}
+
+impl<S: GenericState, A: GenericAction> Observer<S, A> for RegretTracker {
+    fn on_episode_end(&mut self, stats: &EpisodeStats, _q_map: &BTreeMap<(S, A), f64>) {
+        let regret = self.optimal_value - stats.discounted_return;
+        self.regret.push(regret);
+        self.cumulative_regret.push(self.total() + regret);
+    }
+}
+
+fn actions_by_state<S: GenericState, A: GenericAction>(mdp: &MapMdp<S, A>) -> BTreeMap<S, Vec<A>> {
+    let mut actions: BTreeMap<S, Vec<A>> = BTreeMap::new();
+    mdp.transitions
+        .keys()
+        .for_each(|(state, action)| actions.entry(*state).or_default().push(*action));
+    actions
+}
+
+fn best_value<S: GenericState, A: GenericAction>(q_map: &BTreeMap<(S, A), f64>, state: S) -> f64 {
+    q_map
+        .iter()
+        .filter(|((s, _), _)| *s == state)
+        .map(|(_, q)| *q)
+        .fold(f64::MIN, f64::max)
+}
+
+fn mean<I: Iterator<Item = f64>>(values: I) -> f64 {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), value| (sum + value, count + 1));
+    if count == 0 {
+        0.0
+    } else {
+        sum / count as f64
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::BTreeMap;
+
+use crate::mdp::{GenericAction, GenericMdp, GenericState};
+
+use super::{FeatureExtractor, Features};
+
+// one feature per (state, action) pair, linear methods on top of this are equivalent to tabular ones
+pub struct OneHot<S: GenericState, A: GenericAction> {
+    index: BTreeMap<(S, A), usize>,
+}
+
+impl<S: GenericState, A: GenericAction> OneHot<S, A> {
+    pub fn new(states_actions: &[(S, A)]) -> Self {
+        let index = states_actions
+            .iter()
+            .enumerate()
+            .map(|(index, state_action)| (*state_action, index))
+            .collect();
+
+        Self { index }
+    }
+
+    pub fn from_mdp<M: GenericMdp<S, A>>(mdp: &M) -> Self {
+        Self::new(mdp.get_all_state_actions())
+    }
+}
+
+impl<S: GenericState, A: GenericAction> FeatureExtractor<S, A> for OneHot<S, A> {
+    fn num_features(&self) -> usize {
+        self.index.len()
+    }
+
+    // unknown pairs have no active feature and therefore a value of 0
+    fn features(&self, state: S, action: A) -> Features {
+        match self.index.get(&(state, action)) {
+            Some(index) => vec![(*index, 1.0)],
+            None => vec![],
+        }
+    }
+}
+
+// hand-crafted state features copied into a separate block of weights for every action
+pub struct StateFeatures<S: GenericState, A: GenericAction> {
+    actions: Vec<A>,
+    state_features: Box<dyn Fn(S) -> Vec<f64>>,
+    features_per_action: usize,
+}
+
+impl<S: GenericState, A: GenericAction> StateFeatures<S, A> {
+    pub fn new(
+        actions: Vec<A>,
+        features_per_action: usize,
+        state_features: Box<dyn Fn(S) -> Vec<f64>>,
+    ) -> Self {
+        Self {
+            actions,
+            state_features,
+            features_per_action,
+        }
+    }
+}
+
+impl<S: GenericState, A: GenericAction> FeatureExtractor<S, A> for StateFeatures<S, A> {
+    fn num_features(&self) -> usize {
+        self.actions.len() * self.features_per_action
+    }
+
+    fn features(&self, state: S, action: A) -> Features {
+        let action_index = self
+            .actions
+            .iter()
+            .position(|a| *a == action)
+            .expect("unknown action");
+        let offset = action_index * self.features_per_action;
+
+        let values = (self.state_features)(state);
+        assert_eq!(values.len(), self.features_per_action);
+
+        values
+            .into_iter()
+            .enumerate()
+            .filter(|(_, value)| *value != 0.0)
+            .map(|(index, value)| (offset + index, value))
+            .collect()
+    }
+}
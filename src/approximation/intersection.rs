@@ -0,0 +1,93 @@
+use crate::{
+    envs::my_intersection::{IntersectionState, LightAction, LightState},
+    multiagent::intersection::{Action, MAState},
+};
+
+use super::features::StateFeatures;
+
+const LIGHT_STATES: [LightState; 4] = [
+    LightState::NorthSouthOpen,
+    LightState::EastWestOpen,
+    LightState::ChangingToNS,
+    LightState::ChangingToEW,
+];
+
+const LIGHT_ACTIONS: [LightAction; 3] = [
+    LightAction::Change,
+    LightAction::Stay,
+    LightAction::WaitForChange,
+];
+
+fn light_phase(light_state: LightState) -> impl Iterator<Item = f64> {
+    LIGHT_STATES
+        .into_iter()
+        .map(move |phase| if phase == light_state { 1.0 } else { 0.0 })
+}
+
+// bias, queue lengths and their squares relative to max_cars and the one-hot light phase
+pub fn intersection_features(max_cars: usize) -> StateFeatures<IntersectionState, LightAction> {
+    let max_cars = max_cars as f64;
+    StateFeatures::new(
+        LIGHT_ACTIONS.to_vec(),
+        9,
+        Box::new(move |state: IntersectionState| {
+            let ns = state.ns_cars as f64 / max_cars;
+            let ew = state.ew_cars as f64 / max_cars;
+            [1.0, ns, ew, ns * ns, ew * ew]
+                .into_iter()
+                .chain(light_phase(state.light_state))
+                .collect()
+        }),
+    )
+}
+
+fn ma_state_features(state: MAState, max_cars: f64) -> Vec<f64> {
+    let queues = [
+        state.ns_cars_1,
+        state.ew_cars_1,
+        state.ns_cars_2,
+        state.ew_cars_2,
+    ]
+    .map(|cars| cars as f64 / max_cars);
+
+    std::iter::once(1.0)
+        .chain(queues)
+        .chain(queues.map(|queue| queue * queue))
+        .chain(light_phase(state.light_state_1))
+        .chain(light_phase(state.light_state_2))
+        .collect()
+}
+
+// same features as above for both intersections, one block per joint action
+pub fn ma_intersection_features(max_cars: u8) -> StateFeatures<MAState, Action> {
+    let max_cars = max_cars as f64;
+    let actions = LIGHT_ACTIONS
+        .iter()
+        .flat_map(|a1| LIGHT_ACTIONS.iter().map(|a2| Action(*a1, *a2)))
+        .collect();
+
+    StateFeatures::new(
+        actions,
+        17,
+        Box::new(move |state: MAState| ma_state_features(state, max_cars)),
+    )
+}
+
+// features of the joint state for a single agent controlling one of the lights
+pub fn ma_intersection_agent_features(max_cars: u8) -> StateFeatures<MAState, LightAction> {
+    let max_cars = max_cars as f64;
+    StateFeatures::new(
+        LIGHT_ACTIONS.to_vec(),
+        17,
+        Box::new(move |state: MAState| ma_state_features(state, max_cars)),
+    )
+}
+
+// continuous projection of the single intersection state for tile coding
+pub fn intersection_state_vector(state: IntersectionState) -> Vec<f64> {
+    vec![
+        state.ns_cars as f64,
+        state.ew_cars as f64,
+        state.light_state as usize as f64,
+    ]
+}
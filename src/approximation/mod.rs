@@ -0,0 +1,190 @@
+pub mod features;
+pub mod intersection;
+pub mod semi_gradient_q_learning;
+pub mod semi_gradient_sarsa;
+pub mod tile_coding;
+
+use rand::Rng;
+
+use crate::{
+    mdp::{GenericAction, GenericMdp, GenericState},
+    policies::{random_policy_ma, Policy},
+};
+
+// sparse feature vector of (index, value) pairs, most extractors only activate a few features
+pub type Features = Vec<(usize, f64)>;
+
+pub trait FeatureExtractor<S: GenericState, A: GenericAction> {
+    fn num_features(&self) -> usize;
+
+    fn features(&self, state: S, action: A) -> Features;
+}
+
+pub trait GenericLinearAlgorithm {
+    // default implementation
+    fn run<
+        M: GenericMdp<S, A>,
+        S: GenericState,
+        A: GenericAction,
+        F: FeatureExtractor<S, A>,
+        R: Rng,
+    >(
+        &self,
+        mdp: &M,
+        extractor: &F,
+        episodes: usize,
+        rng: &mut R,
+    ) -> Vec<f64> {
+        let mut weights = vec![0.0; extractor.num_features()];
+
+        self.run_with_weights(mdp, extractor, episodes, rng, &mut weights);
+
+        weights
+    }
+
+    fn run_with_weights<
+        M: GenericMdp<S, A>,
+        S: GenericState,
+        A: GenericAction,
+        F: FeatureExtractor<S, A>,
+        R: Rng,
+    >(
+        &self,
+        mdp: &M,
+        extractor: &F,
+        episodes: usize,
+        rng: &mut R,
+        weights: &mut [f64],
+    );
+
+    fn get_epsilon(&self) -> f64;
+}
+
+pub fn linear_q(weights: &[f64], features: &Features) -> f64 {
+    features
+        .iter()
+        .map(|(index, value)| weights[*index] * value)
+        .sum()
+}
+
+pub fn gradient_step(weights: &mut [f64], features: &Features, step_size: f64) {
+    features
+        .iter()
+        .for_each(|(index, value)| weights[*index] += step_size * value);
+}
+
+pub fn max_linear_q<S: GenericState, A: GenericAction, F: FeatureExtractor<S, A>>(
+    possible_actions: &[A],
+    weights: &[f64],
+    extractor: &F,
+    state: S,
+) -> Option<f64> {
+    possible_actions
+        .iter()
+        .map(|action| linear_q(weights, &extractor.features(state, *action)))
+        .reduce(f64::max)
+}
+
+pub fn epsilon_greedy_linear_policy<
+    S: GenericState,
+    A: GenericAction,
+    F: FeatureExtractor<S, A>,
+    R: Rng,
+>(
+    possible_actions: &[A],
+    weights: &[f64],
+    extractor: &F,
+    state: S,
+    epsilon: f64,
+    rng: &mut R,
+) -> Option<A> {
+    let random_value = rng.gen_range(0.0..1.0);
+    if random_value < (1.0 - epsilon) {
+        greedy_linear_policy(possible_actions, weights, extractor, state, rng)
+    } else {
+        random_policy_ma(possible_actions, rng)
+    }
+}
+
+pub fn greedy_linear_policy<
+    S: GenericState,
+    A: GenericAction,
+    F: FeatureExtractor<S, A>,
+    R: Rng,
+>(
+    possible_actions: &[A],
+    weights: &[f64],
+    extractor: &F,
+    state: S,
+    rng: &mut R,
+) -> Option<A> {
+    // break ties randomly, same as the tabular greedy policy
+    let best_actions = best_linear_actions(possible_actions, weights, extractor, state);
+    random_policy_ma(&best_actions, rng)
+}
+
+fn best_linear_actions<S: GenericState, A: GenericAction, F: FeatureExtractor<S, A>>(
+    possible_actions: &[A],
+    weights: &[f64],
+    extractor: &F,
+    state: S,
+) -> Vec<A> {
+    let q_values: Vec<(A, f64)> = possible_actions
+        .iter()
+        .map(|action| {
+            (
+                *action,
+                linear_q(weights, &extractor.features(state, *action)),
+            )
+        })
+        .collect();
+    let Some(max_q) = q_values.iter().map(|(_, q)| *q).reduce(f64::max) else {
+        return vec![];
+    };
+
+    q_values
+        .iter()
+        .filter(|(_, q)| *q == max_q)
+        .map(|(action, _)| *action)
+        .collect()
+}
+
+// greedy policy of a linear action value function
+pub struct GreedyLinearPolicy<'a, F> {
+    weights: &'a [f64],
+    extractor: &'a F,
+}
+
+impl<'a, F> GreedyLinearPolicy<'a, F> {
+    pub fn new(weights: &'a [f64], extractor: &'a F) -> Self {
+        Self { weights, extractor }
+    }
+}
+
+impl<S: GenericState, A: GenericAction, F: FeatureExtractor<S, A>> Policy<S, A>
+    for GreedyLinearPolicy<'_, F>
+{
+    fn select_action<R: Rng>(
+        &mut self,
+        state: S,
+        possible_actions: &[A],
+        rng: &mut R,
+    ) -> Option<A> {
+        greedy_linear_policy(possible_actions, self.weights, self.extractor, state, rng)
+    }
+
+    fn action_probabilities(&self, state: S, possible_actions: &[A]) -> Vec<(A, f64)> {
+        let best = best_linear_actions(possible_actions, self.weights, self.extractor, state);
+        possible_actions
+            .iter()
+            .map(|action| {
+                let prob = if best.contains(action) {
+                    1.0 / best.len() as f64
+                } else {
+                    0.0
+                };
+                (*action, prob)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,87 @@
+use rand::Rng;
+
+use crate::mdp::{GenericAction, GenericMdp, GenericState};
+
+use super::{
+    epsilon_greedy_linear_policy, gradient_step, linear_q, max_linear_q, FeatureExtractor,
+    GenericLinearAlgorithm,
+};
+
+pub struct SemiGradientQLearning {
+    alpha: f64,
+    epsilon: f64,
+    max_steps: usize,
+}
+
+impl SemiGradientQLearning {
+    pub fn new(alpha: f64, epsilon: f64, max_steps: usize) -> Self {
+        SemiGradientQLearning {
+            alpha,
+            epsilon,
+            max_steps,
+        }
+    }
+}
+
+impl GenericLinearAlgorithm for SemiGradientQLearning {
+    fn run_with_weights<
+        M: GenericMdp<S, A>,
+        S: GenericState,
+        A: GenericAction,
+        F: FeatureExtractor<S, A>,
+        R: Rng,
+    >(
+        &self,
+        mdp: &M,
+        extractor: &F,
+        episodes: usize,
+        rng: &mut R,
+        weights: &mut [f64],
+    ) {
+        for _ in 0..episodes {
+            let mut current_state = mdp.get_initial_state(rng);
+            let mut steps = 0;
+
+            while !mdp.is_terminal(current_state) && steps < self.max_steps {
+                let Some(selected_action) = epsilon_greedy_linear_policy(
+                    &mdp.get_possible_actions(current_state),
+                    weights,
+                    extractor,
+                    current_state,
+                    self.epsilon,
+                    rng,
+                ) else {
+                    break;
+                };
+                let (next_state, reward) =
+                    mdp.perform_action((current_state, selected_action), rng);
+
+                // terminal states and states without actions have a value of 0
+                let best_q = if mdp.is_terminal(next_state) {
+                    0.0
+                } else {
+                    max_linear_q(
+                        &mdp.get_possible_actions(next_state),
+                        weights,
+                        extractor,
+                        next_state,
+                    )
+                    .unwrap_or(0.0)
+                };
+
+                let features = extractor.features(current_state, selected_action);
+                let delta =
+                    reward + mdp.get_discount_factor() * best_q - linear_q(weights, &features);
+                gradient_step(weights, &features, self.alpha * delta);
+
+                current_state = next_state;
+
+                steps += 1;
+            }
+        }
+    }
+
+    fn get_epsilon(&self) -> f64 {
+        self.epsilon
+    }
+}
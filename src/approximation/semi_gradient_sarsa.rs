@@ -0,0 +1,91 @@
+use rand::Rng;
+
+use crate::mdp::{GenericAction, GenericMdp, GenericState};
+
+use super::{
+    epsilon_greedy_linear_policy, gradient_step, linear_q, FeatureExtractor, GenericLinearAlgorithm,
+};
+
+pub struct SemiGradientSarsa {
+    alpha: f64,
+    epsilon: f64,
+    max_steps: usize,
+}
+
+impl SemiGradientSarsa {
+    pub fn new(alpha: f64, epsilon: f64, max_steps: usize) -> Self {
+        SemiGradientSarsa {
+            alpha,
+            epsilon,
+            max_steps,
+        }
+    }
+}
+
+impl GenericLinearAlgorithm for SemiGradientSarsa {
+    fn run_with_weights<
+        M: GenericMdp<S, A>,
+        S: GenericState,
+        A: GenericAction,
+        F: FeatureExtractor<S, A>,
+        R: Rng,
+    >(
+        &self,
+        mdp: &M,
+        extractor: &F,
+        episodes: usize,
+        rng: &mut R,
+        weights: &mut [f64],
+    ) {
+        for _ in 0..episodes {
+            let mut current_state = mdp.get_initial_state(rng);
+            let Some(mut current_action) = epsilon_greedy_linear_policy(
+                &mdp.get_possible_actions(current_state),
+                weights,
+                extractor,
+                current_state,
+                self.epsilon,
+                rng,
+            ) else {
+                continue;
+            };
+            let mut steps = 0;
+
+            while !mdp.is_terminal(current_state) && steps < self.max_steps {
+                let (next_state, reward) = mdp.perform_action((current_state, current_action), rng);
+                let features = extractor.features(current_state, current_action);
+                let current_q = linear_q(weights, &features);
+
+                // terminal states have a value of 0
+                if mdp.is_terminal(next_state) {
+                    gradient_step(weights, &features, self.alpha * (reward - current_q));
+                    break;
+                }
+
+                let Some(next_action) = epsilon_greedy_linear_policy(
+                    &mdp.get_possible_actions(next_state),
+                    weights,
+                    extractor,
+                    next_state,
+                    self.epsilon,
+                    rng,
+                ) else {
+                    break;
+                };
+
+                let next_q = linear_q(weights, &extractor.features(next_state, next_action));
+                let delta = reward + mdp.get_discount_factor() * next_q - current_q;
+                gradient_step(weights, &features, self.alpha * delta);
+
+                current_state = next_state;
+                current_action = next_action;
+
+                steps += 1;
+            }
+        }
+    }
+
+    fn get_epsilon(&self) -> f64 {
+        self.epsilon
+    }
+}
@@ -0,0 +1,80 @@
+use crate::mdp::{GenericAction, GenericState};
+
+use super::{FeatureExtractor, Features};
+
+// tile coding over a continuous projection of the state, each tiling is shifted by an asymmetric
+// offset (1, 3, 5, ...) / num_tilings of a tile width as recommended by Sutton & Barto
+pub struct TileCoding<S: GenericState, A: GenericAction> {
+    num_tilings: usize,
+    tiles_per_dim: usize,
+    bounds: Vec<(f64, f64)>,
+    actions: Vec<A>,
+    state_vector: fn(S) -> Vec<f64>,
+}
+
+impl<S: GenericState, A: GenericAction> TileCoding<S, A> {
+    pub fn new(
+        num_tilings: usize,
+        tiles_per_dim: usize,
+        bounds: Vec<(f64, f64)>,
+        actions: Vec<A>,
+        state_vector: fn(S) -> Vec<f64>,
+    ) -> Self {
+        if num_tilings == 0 || tiles_per_dim == 0 {
+            panic!("tile coding needs at least one tiling and one tile per dimension");
+        }
+        Self {
+            num_tilings,
+            tiles_per_dim,
+            bounds,
+            actions,
+            state_vector,
+        }
+    }
+
+    pub fn num_tilings(&self) -> usize {
+        self.num_tilings
+    }
+
+    // one extra tile per dimension catches the values pushed over the upper bound by the offset
+    fn tiles_per_tiling(&self) -> usize {
+        (self.tiles_per_dim + 1).pow(self.bounds.len() as u32)
+    }
+
+    fn tile_index(&self, values: &[f64], tiling: usize) -> usize {
+        values.iter().zip(self.bounds.iter()).enumerate().fold(
+            0,
+            |index, (dim, (value, (lower, upper)))| {
+                let tile_width = (upper - lower) / self.tiles_per_dim as f64;
+                let displacement = (tiling * (2 * dim + 1)) % self.num_tilings;
+                let offset = displacement as f64 / self.num_tilings as f64 * tile_width;
+                let scaled = ((value.clamp(*lower, *upper) - lower + offset) / tile_width) as usize;
+                index * (self.tiles_per_dim + 1) + scaled.min(self.tiles_per_dim)
+            },
+        )
+    }
+}
+
+impl<S: GenericState, A: GenericAction> FeatureExtractor<S, A> for TileCoding<S, A> {
+    fn num_features(&self) -> usize {
+        self.actions.len() * self.num_tilings * self.tiles_per_tiling()
+    }
+
+    fn features(&self, state: S, action: A) -> Features {
+        let action_index = self
+            .actions
+            .iter()
+            .position(|a| *a == action)
+            .expect("unknown action");
+
+        let values = (self.state_vector)(state);
+        assert_eq!(values.len(), self.bounds.len());
+
+        (0..self.num_tilings)
+            .map(|tiling| {
+                let offset = (action_index * self.num_tilings + tiling) * self.tiles_per_tiling();
+                (offset + self.tile_index(&values, tiling), 1.0)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,57 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use rayon::prelude::*;
+
+// rng of the run with the given index, every run gets its own stream of the base seed so runs
+// are independent of each other and of the thread they end up on
+pub fn seed_rng(base_seed: u64, index: usize) -> ChaCha20Rng {
+    let mut rng = ChaCha20Rng::seed_from_u64(base_seed);
+    rng.set_stream(index as u64);
+    rng
+}
+
+// runs the job once per seed on the rayon pool. Results are in seed order for any number of
+// threads.
+pub fn run_seeds<T, F>(base_seed: u64, num_seeds: usize, job: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(usize, &mut ChaCha20Rng) -> T + Sync,
+{
+    (0..num_seeds)
+        .into_par_iter()
+        .map(|index| job(index, &mut seed_rng(base_seed, index)))
+        .collect()
+}
+
+// runs every job with every seed in one pool, so a handful of jobs still keeps all cores busy.
+// Returns the results per job in seed order. All jobs see the same rng streams, so algorithms are
+// compared on the same seeds.
+pub fn run_jobs<J, T, F>(jobs: &[J], base_seed: u64, num_seeds: usize, run: F) -> Vec<Vec<T>>
+where
+    J: Sync,
+    T: Send,
+    F: Fn(&J, usize, &mut ChaCha20Rng) -> T + Sync,
+{
+    let results: Vec<(usize, T)> = (0..jobs.len() * num_seeds)
+        .into_par_iter()
+        .map(|run_index| {
+            let (job, index) = (run_index / num_seeds, run_index % num_seeds);
+            let result = run(&jobs[job], index, &mut seed_rng(base_seed, index));
+            (job, result)
+        })
+        .collect();
+
+    let mut grouped: Vec<Vec<T>> = jobs.iter().map(|_| vec![]).collect();
+    results
+        .into_iter()
+        .for_each(|(job, result)| grouped[job].push(result));
+    grouped
+}
+
+// sums in order so the mean does not depend on how the runs were scheduled
+pub fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().sum::<f64>() / values.len() as f64
+}
@@ -1,3 +1,4 @@
+pub mod executor;
 pub mod optimal_episodes;
 pub mod runtime;
 pub mod strategies;
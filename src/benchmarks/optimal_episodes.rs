@@ -2,111 +2,95 @@ use rand::SeedableRng;
 use rand_chacha::ChaCha20Rng;
 
 use crate::{
+    agent::{
+        runner::Runner,
+        stopping::{StopReason, TargetReturn},
+        Agent, TabularAgent,
+    },
     algorithms::{
-        dyna_q::{BetaDynaQ, Dyna, DynaQ},
+        dyna_q::{BetaDynaQ, DynaQ},
         monte_carlo::MonteCarlo,
         q_learning::QLearning,
         sarsa::Sarsa,
-        GenericStateActionAlgorithm, Trace,
+        sarsa_lambda::SarsaLambda,
+        TabularAlgorithm, Trace,
     },
-    envs,
+    analysis::optimal_return,
+    benchmarks::executor::{mean, run_jobs, run_seeds},
+    envs::{self, exploration::build_combination_lock, grid::Connectivity, windy_grid_world},
     eval::evaluate_greedy_policy,
     mdp::{GenericAction, GenericMdp, GenericState},
 };
 
-fn bench_until_optimal<M: GenericMdp<S, A>, S: GenericState, A: GenericAction>(
-    env: &M,
-    algo: &impl GenericStateActionAlgorithm,
-    seed: u64,
-    num_seeds: usize,
-    optimal_reward: f64,
-) -> f64 {
-    let mut eval_rng = ChaCha20Rng::seed_from_u64(seed);
-    let eval_max_steps = 200;
-    let eval_episodes = 10;
+// evaluated rewards within this distance of the optimum count as optimal. The evaluation averages
+// sums of float rewards and the optimum may come from value iteration, so exact equality can miss
+// an optimal policy.
+const OPTIMAL_TOLERANCE: f64 = 1e-6;
+// seeds that never reach the optimum give up after this many episodes
+const MAX_EPISODES: usize = 100_000;
 
-    let mut total_episodes = 0;
-    for i in 0..num_seeds {
-        let mut rng = ChaCha20Rng::seed_from_u64(seed + i as u64);
-        let mut q_map = algo.run(env, 1, &mut rng);
-
-        loop {
-            let avg_reward =
-                evaluate_greedy_policy(env, &q_map, eval_episodes, eval_max_steps, &mut eval_rng);
-            // dbg!(total_episodes);
-            algo.run_with_q_map(env, 1, &mut rng, &mut q_map);
-            if avg_reward == optimal_reward {
-                break;
-            }
-            // println!("episode: {q_counter}");
-            total_episodes += 1;
-        }
-    }
-    total_episodes as f64 / num_seeds as f64
-}
-
-fn bench_until_optimal_dynaq<M: GenericMdp<S, A>, S: GenericState, A: GenericAction>(
+// trains the agent until its greedy policy is optimal, evaluated with its own rng seeded by
+// seed + index
+fn episodes_until_optimal<M, S, A, G>(
     env: &M,
-    algo: &mut DynaQ<S, A>,
+    mut agent: G,
+    runner: Runner,
     seed: u64,
-    num_seeds: usize,
+    index: usize,
     optimal_reward: f64,
-) -> f64 {
-    let mut eval_rng = ChaCha20Rng::seed_from_u64(seed + 1);
+    rng: &mut ChaCha20Rng,
+) -> f64
+where
+    M: GenericMdp<S, A>,
+    S: GenericState,
+    A: GenericAction,
+    G: Agent<S, A>,
+{
     let eval_max_steps = 200;
     let eval_episodes = 10;
 
-    let mut total_episodes = 0;
-    for i in 0..num_seeds {
-        let mut rng = ChaCha20Rng::seed_from_u64(seed + i as u64);
-        algo.clear_model();
-        let mut q_map = algo.run(env, 1, &mut rng);
-
-        loop {
-            let avg_reward =
-                evaluate_greedy_policy(env, &q_map, eval_episodes, eval_max_steps, &mut eval_rng);
-            algo.run_with_q_map(env, 1, &mut rng, &mut q_map);
-            if avg_reward == optimal_reward {
-                break;
-            }
-            // println!("episode: {q_counter}");
-            total_episodes += 1;
-            // dbg!(i, total_episodes, avg_reward);
-        }
+    let mut target = TargetReturn::new(
+        env,
+        optimal_reward,
+        OPTIMAL_TOLERANCE,
+        1,
+        eval_episodes,
+        eval_max_steps,
+        seed + index as u64,
+    );
+    let (stats, reason) =
+        runner.run_until(env, &mut agent, MAX_EPISODES, rng, &mut target, &mut ());
+    if reason != StopReason::TargetReached {
+        println!(
+            "seed {} stopped without reaching the optimum: {:?}",
+            index, reason
+        );
     }
-    total_episodes as f64 / num_seeds as f64
+    // every trained episode counts, including the one after which the greedy policy became
+    // optimal. The old loop counted the failed evaluations instead, one less than this. Seeds
+    // that give up count MAX_EPISODES.
+    stats.len() as f64
 }
 
-fn bench_until_optimal_beta_dynaq<M: GenericMdp<S, A>, S: GenericState, A: GenericAction>(
+fn bench_until_optimal<M, S, A, G, F>(
     env: &M,
-    algo: &mut BetaDynaQ<S, A>,
+    new_agent: F,
+    runner: Runner,
     seed: u64,
     num_seeds: usize,
     optimal_reward: f64,
-) -> f64 {
-    let mut eval_rng = ChaCha20Rng::seed_from_u64(seed + 1);
-    let eval_max_steps = 200;
-    let eval_episodes = 10;
-
-    let mut total_episodes = 0;
-    for i in 0..num_seeds {
-        let mut rng = ChaCha20Rng::seed_from_u64(seed + i as u64);
-        algo.clear_model();
-        let mut q_map = algo.run(env, 1, &mut rng);
-
-        loop {
-            let avg_reward =
-                evaluate_greedy_policy(env, &q_map, eval_episodes, eval_max_steps, &mut eval_rng);
-            algo.run_with_q_map(env, 1, &mut rng, &mut q_map);
-            if avg_reward == optimal_reward {
-                break;
-            }
-            // println!("episode: {q_counter}");
-            total_episodes += 1;
-            // dbg!(i, total_episodes, avg_reward);
-        }
-    }
-    total_episodes as f64 / num_seeds as f64
+) -> f64
+where
+    M: GenericMdp<S, A> + Sync,
+    S: GenericState,
+    A: GenericAction,
+    G: Agent<S, A>,
+    F: Fn(&M) -> G + Sync,
+{
+    let episodes = run_seeds(seed, num_seeds, |i, rng| {
+        episodes_until_optimal(env, new_agent(env), runner, seed, i, optimal_reward, rng)
+    });
+    mean(&episodes)
 }
 
 pub fn bench_algos_until_optimal(lambda: f64, trace: Trace) {
@@ -122,64 +106,95 @@ pub fn bench_algos_until_optimal(lambda: f64, trace: Trace) {
     let k = 5;
     let deterministic = true;
     let max_steps = 500;
-    let optimal_reward = -13.0;
+    let optimal_reward = optimal_return(&cw_mdp, 1e-9);
+    let runner = Runner::new(max_steps);
     let mut results: Vec<(String, f64)> = vec![];
 
     // monte carlo
     println!("MC");
-    let mc_algo = MonteCarlo::new(epsilon, max_steps);
-    let mc_episodes = bench_until_optimal(&cw_mdp, &mc_algo, seed, num_seeds, optimal_reward);
+    let mc_episodes = bench_until_optimal(
+        &cw_mdp,
+        |env| TabularAgent::from_mdp(MonteCarlo::new(epsilon, max_steps), env),
+        runner,
+        seed,
+        num_seeds,
+        optimal_reward,
+    );
     results.push(("MC".to_owned(), mc_episodes));
 
     // Q-Learning
     println!("Q");
-    let q_algo = QLearning::new(alpha, epsilon, max_steps);
-    let q_episodes = bench_until_optimal(&cw_mdp, &q_algo, seed, num_seeds, optimal_reward);
+    let q_episodes = bench_until_optimal(
+        &cw_mdp,
+        |env| TabularAgent::from_mdp(QLearning::new(alpha, epsilon, max_steps), env),
+        runner,
+        seed,
+        num_seeds,
+        optimal_reward,
+    );
     results.push(("Q-Learning".to_owned(), q_episodes));
 
     // SARSA
     println!("SARSA");
-    let sarsa_algo = Sarsa::new(alpha, epsilon, max_steps);
-    let sarsa_episodes = bench_until_optimal(&cw_mdp, &sarsa_algo, seed, num_seeds, optimal_reward);
+    let sarsa_episodes = bench_until_optimal(
+        &cw_mdp,
+        |env| TabularAgent::from_mdp(Sarsa::new(alpha, epsilon, max_steps), env),
+        runner,
+        seed,
+        num_seeds,
+        optimal_reward,
+    );
     results.push(("SARSA".to_owned(), sarsa_episodes));
 
     // Q-Learning(lambda)
     // println!("Q lambda");
-    // let q_lambda_algo = QLearningLambda::new(alpha, epsilon, lambda, max_steps, trace);
-    // let q_lambda_episodes =
-    //     bench_until_optimal(&cw_mdp, &q_lambda_algo, seed, num_seeds, optimal_reward);
+    // let q_lambda_episodes = bench_until_optimal(
+    //     &cw_mdp,
+    //     |env| {
+    //         let algo = QLearningLambda::new(alpha, epsilon, lambda, max_steps, trace);
+    //         TabularAgent::from_mdp(algo, env)
+    //     },
+    //     runner,
+    //     seed,
+    //     num_seeds,
+    //     optimal_reward,
+    // );
     // results.push(("Q-Learning(lambda)".to_owned(), q_lambda_episodes));
 
-    // // Q-Learning(lambda)
-    // println!("SARSA lambda");
-    // let sarsa_lambda_algo = SarsaLambda::new(alpha, epsilon, lambda, max_steps, trace);
-    // let sarsa_lambda_episodes =
-    //     bench_until_optimal(&cw_mdp, &sarsa_lambda_algo, seed, num_seeds, optimal_reward);
-    // results.push(("SARSA(lambda)".to_owned(), sarsa_lambda_episodes));
-
     // DynaQ
     println!("DynaQ");
-    let mut dyna_q_algo = DynaQ::new(alpha, epsilon, k, max_steps, deterministic, true, &cw_mdp);
-    let dyna_q_episodes =
-        bench_until_optimal_dynaq(&cw_mdp, &mut dyna_q_algo, seed, num_seeds, optimal_reward);
+    let dyna_q_episodes = bench_until_optimal(
+        &cw_mdp,
+        |env| {
+            let algo = DynaQ::new(alpha, epsilon, k, max_steps, deterministic, true, env);
+            TabularAgent::from_mdp(algo, env)
+        },
+        runner,
+        seed,
+        num_seeds,
+        optimal_reward,
+    );
     results.push(("DynaQ".to_owned(), dyna_q_episodes));
 
     // DynaQ
     println!("BetaDynaQ, no converging alpha, no direct learning step");
-    let mut beta_dyna_q_algo = BetaDynaQ::new_with_settings(
-        alpha,
-        epsilon,
-        k,
-        max_steps,
-        deterministic,
+    let beta_dyna_q_episodes = bench_until_optimal(
         &cw_mdp,
-        1,
-        false,
-        true,
-    );
-    let beta_dyna_q_episodes = bench_until_optimal_beta_dynaq(
-        &cw_mdp,
-        &mut beta_dyna_q_algo,
+        |env| {
+            let algo = BetaDynaQ::new_with_settings(
+                alpha,
+                epsilon,
+                k,
+                max_steps,
+                deterministic,
+                env,
+                1,
+                false,
+                true,
+            );
+            TabularAgent::from_mdp(algo, env)
+        },
+        runner,
         seed,
         num_seeds,
         optimal_reward,
@@ -234,13 +249,143 @@ pub fn run_benchmark() {
     }
 }
 
+// episodes until the greedy policy opens combination locks of growing length. Dithering
+// exploration needs exponentially many steps to find the combination, the sweep shows how the
+// learners scale with it.
+pub fn sweep_chain_length() {
+    let seed: u64 = 1;
+    let num_seeds: usize = 20;
+    let alpha = 0.1;
+    let epsilon = 0.1;
+    let k = 5;
+    // short episodes, so finding the combination takes more episodes as the lock grows
+    let max_steps = 100;
+    let runner = Runner::new(max_steps);
+    let lengths = [1, 2, 3, 4, 5, 6];
+
+    let mut csv_writer =
+        csv::Writer::from_path("results/chain_length_sweep.csv").expect("csv file error");
+    csv_writer
+        .write_record(["length", "MC", "Q-Learning", "DynaQ"])
+        .expect("csv write record error");
+
+    // discounted, without discounting resetting the lock is optimal as well
+    let locks: Vec<_> = lengths
+        .iter()
+        .map(|length| {
+            let mdp = build_combination_lock(*length, 2, 0.99, seed).unwrap();
+            let optimal_reward = optimal_return(&mdp, 1e-9);
+            (mdp, optimal_reward)
+        })
+        .collect();
+
+    // all lengths and seeds share one pool, the long locks dominate the runtime. Every algorithm
+    // starts from the same rng stream of the seed.
+    let runs = run_jobs(&locks, seed, num_seeds, |(mdp, optimal_reward), i, rng| {
+        let mc = TabularAgent::from_mdp(MonteCarlo::new(epsilon, max_steps), mdp);
+        let q = TabularAgent::from_mdp(QLearning::new(alpha, epsilon, max_steps), mdp);
+        let dyna_q = TabularAgent::from_mdp(
+            DynaQ::new(alpha, epsilon, k, max_steps, true, true, mdp),
+            mdp,
+        );
+        [
+            episodes_until_optimal(mdp, mc, runner, seed, i, *optimal_reward, &mut rng.clone()),
+            episodes_until_optimal(mdp, q, runner, seed, i, *optimal_reward, &mut rng.clone()),
+            episodes_until_optimal(
+                mdp,
+                dyna_q,
+                runner,
+                seed,
+                i,
+                *optimal_reward,
+                &mut rng.clone(),
+            ),
+        ]
+    });
+
+    for (length, runs) in lengths.iter().zip(runs) {
+        let [mc_episodes, q_episodes, dyna_q_episodes] = [0, 1, 2].map(|algo| {
+            mean(
+                &runs
+                    .iter()
+                    .map(|episodes| episodes[algo])
+                    .collect::<Vec<_>>(),
+            )
+        });
+        println!("length {length}: MC {mc_episodes}, Q {q_episodes}, DynaQ {dyna_q_episodes}");
+
+        csv_writer
+            .serialize((length, mc_episodes, q_episodes, dyna_q_episodes))
+            .expect("csv error");
+    }
+    csv_writer.flush().expect("csv error");
+}
+
+// Sarsa against Q-Learning and Sarsa(lambda) on the windy gridworld, with and without king's moves
+pub fn windy_grid_world() {
+    let seed: u64 = 1;
+    let num_seeds: usize = 20;
+    let alpha = 0.5;
+    let epsilon = 0.1;
+    let lambda = 0.9;
+    let max_steps = 10_000;
+    let runner = Runner::new(max_steps);
+
+    let mut csv_writer =
+        csv::Writer::from_path("results/windy_grid_world_optimal.csv").expect("csv file error");
+    csv_writer
+        .write_record(["moves", "SARSA", "Q-Learning", "SARSA(lambda)"])
+        .expect("csv write record error");
+
+    for (name, connectivity) in [("4", Connectivity::Four), ("8", Connectivity::Eight)] {
+        let mdp = windy_grid_world::build_mdp(connectivity, false).unwrap();
+        let optimal_reward = optimal_return(&mdp, 1e-9);
+
+        println!("{name} moves, SARSA");
+        let sarsa_episodes = bench_until_optimal(
+            &mdp,
+            |env| TabularAgent::from_mdp(Sarsa::new(alpha, epsilon, max_steps), env),
+            runner,
+            seed,
+            num_seeds,
+            optimal_reward,
+        );
+        println!("{name} moves, Q");
+        let q_episodes = bench_until_optimal(
+            &mdp,
+            |env| TabularAgent::from_mdp(QLearning::new(alpha, epsilon, max_steps), env),
+            runner,
+            seed,
+            num_seeds,
+            optimal_reward,
+        );
+        println!("{name} moves, SARSA(lambda)");
+        let sarsa_lambda_episodes = bench_until_optimal(
+            &mdp,
+            |env| {
+                let algo = SarsaLambda::new(alpha, epsilon, lambda, max_steps, Trace::Replacing);
+                TabularAgent::from_mdp(algo, env)
+            },
+            runner,
+            seed,
+            num_seeds,
+            optimal_reward,
+        );
+
+        csv_writer
+            .serialize((name, sarsa_episodes, q_episodes, sarsa_lambda_episodes))
+            .expect("csv error");
+    }
+    csv_writer.flush().expect("csv error");
+}
+
 pub fn grid_world() {
     let mut rng = ChaCha20Rng::seed_from_u64(0);
     let alpha = 0.1;
     let epsilon = 0.1;
     let max_steps = 1000;
     let mdp = envs::grid_world::build_mdp().unwrap();
-    let q_algo = QLearning::new(alpha, epsilon, max_steps);
+    let mut q_algo = QLearning::new(alpha, epsilon, max_steps);
     let q_map = q_algo.run(&mdp, 1000, &mut rng);
     let avg_reward = evaluate_greedy_policy(&mdp, &q_map, 10, 1000, &mut rng);
     dbg!(avg_reward);
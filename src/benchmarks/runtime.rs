@@ -1,9 +1,12 @@
-use crate::algorithms::dyna_q::{Dyna, DynaQ};
+use crate::agent::{runner::Runner, Agent, TabularAgent};
+use crate::algorithms::dyna_q::DynaQ;
 use crate::algorithms::monte_carlo::MonteCarlo;
 use crate::algorithms::q_learning_lambda::QLearningLambda;
 use crate::algorithms::sarsa_lambda::SarsaLambda;
-use crate::algorithms::{GenericStateActionAlgorithm, Trace};
-use crate::mdp::{GenericAction, GenericMdp, GenericState, IndexAction, IndexState};
+use crate::algorithms::Trace;
+use crate::benchmarks::executor::seed_rng;
+use crate::envs::grid::Connectivity;
+use crate::mdp::{GenericAction, GenericMdp, GenericState, IndexAction, IndexMdp, IndexState};
 use std::collections::HashMap;
 use std::time::{Duration, Instant};
 
@@ -15,36 +18,29 @@ use crate::{algorithms::sarsa::Sarsa, generator::generate_random_mdp};
 use crate::algorithms::q_learning::QLearning;
 
 /// Bench runtime of algorithm on an mdp given set number of episodes
-fn bench_runtime<M: GenericMdp<S, A>, S: GenericState, A: GenericAction>(
+fn bench_runtime<M, S, A, G, F>(
     env: &M,
-    algo: &impl GenericStateActionAlgorithm,
+    new_agent: F,
+    runner: Runner,
     episodes: usize,
     seed: u64,
     num_seeds: usize,
-) -> Duration {
+) -> Duration
+where
+    M: GenericMdp<S, A>,
+    S: GenericState,
+    A: GenericAction,
+    G: Agent<S, A>,
+    F: Fn(&M) -> G,
+{
+    // seeds run one after another, running them in parallel would distort the timings
     let mut total_duration: Duration = Duration::new(0, 0);
     for i in 0..num_seeds {
-        let mut rng = ChaCha20Rng::seed_from_u64(seed + i as u64);
+        let mut rng = seed_rng(seed, i);
+        // every seed starts with a fresh agent, this also resets models of model based agents
+        let mut agent = new_agent(env);
         let start = Instant::now();
-        algo.run(env, episodes, &mut rng);
-        total_duration = total_duration.saturating_add(start.elapsed());
-    }
-    total_duration.div_f64(num_seeds as f64)
-}
-
-fn bench_runtime_dyna<M: GenericMdp<S, A>, S: GenericState, A: GenericAction>(
-    env: &M,
-    algo: &mut DynaQ<S, A>,
-    episodes: usize,
-    seed: u64,
-    num_seeds: usize,
-) -> Duration {
-    let mut total_duration: Duration = Duration::new(0, 0);
-    for i in 0..num_seeds {
-        let mut rng = ChaCha20Rng::seed_from_u64(seed + i as u64);
-        algo.clear_model();
-        let start = Instant::now();
-        algo.run(env, episodes, &mut rng);
+        runner.run(env, &mut agent, episodes, &mut rng);
         total_duration = total_duration.saturating_add(start.elapsed());
     }
     total_duration.div_f64(num_seeds as f64)
@@ -63,38 +59,82 @@ fn bench_all_algo<M: GenericMdp<S, A>, S: GenericState, A: GenericAction>(
     let lambda = 0.9;
     let k = 5;
     let max_steps = 1000;
+    let runner = Runner::new(max_steps);
     let mut results: Vec<(String, f64)> = vec![];
 
     // monte carlo
-    let mc_algo = MonteCarlo::new(epsilon, max_steps);
-    let mc_time = bench_runtime(env, &mc_algo, episodes, seed, num_seeds);
+    let mc_time = bench_runtime(
+        env,
+        |env| TabularAgent::from_mdp(MonteCarlo::new(epsilon, max_steps), env),
+        runner,
+        episodes,
+        seed,
+        num_seeds,
+    );
     results.push(("MC".to_owned(), mc_time.as_secs_f64()));
 
     // Q-Learning
-    let q_algo = QLearning::new(alpha, epsilon, max_steps);
-    let q_time = bench_runtime(env, &q_algo, episodes, seed, num_seeds);
+    let q_time = bench_runtime(
+        env,
+        |env| TabularAgent::from_mdp(QLearning::new(alpha, epsilon, max_steps), env),
+        runner,
+        episodes,
+        seed,
+        num_seeds,
+    );
     results.push(("Q-Learning".to_owned(), q_time.as_secs_f64()));
 
     // SARSA
-    let sarsa_algo = Sarsa::new(alpha, epsilon, max_steps);
-    let sarsa_time = bench_runtime(env, &sarsa_algo, episodes, seed, num_seeds);
+    let sarsa_time = bench_runtime(
+        env,
+        |env| TabularAgent::from_mdp(Sarsa::new(alpha, epsilon, max_steps), env),
+        runner,
+        episodes,
+        seed,
+        num_seeds,
+    );
     results.push(("SARSA".to_owned(), sarsa_time.as_secs_f64()));
 
     // Q-Learning(lambda)
-    let q_lambda_algo =
-        QLearningLambda::new(alpha, epsilon, lambda, max_steps, Trace::Accumulating);
-    let q_lambda_time = bench_runtime(env, &q_lambda_algo, episodes, seed, num_seeds);
+    let q_lambda_time = bench_runtime(
+        env,
+        |env| {
+            let algo = QLearningLambda::new(alpha, epsilon, lambda, max_steps, Trace::Accumulating);
+            TabularAgent::from_mdp(algo, env)
+        },
+        runner,
+        episodes,
+        seed,
+        num_seeds,
+    );
     results.push(("Q-Learning(lambda)".to_owned(), q_lambda_time.as_secs_f64()));
 
-    // Q-Learning(lambda)
-    let sarsa_lambda_algo =
-        SarsaLambda::new(alpha, epsilon, lambda, max_steps, Trace::Accumulating);
-    let sarsa_lambda_time = bench_runtime(env, &sarsa_lambda_algo, episodes, seed, num_seeds);
+    // SARSA(lambda)
+    let sarsa_lambda_time = bench_runtime(
+        env,
+        |env| {
+            let algo = SarsaLambda::new(alpha, epsilon, lambda, max_steps, Trace::Accumulating);
+            TabularAgent::from_mdp(algo, env)
+        },
+        runner,
+        episodes,
+        seed,
+        num_seeds,
+    );
     results.push(("SARSA(lambda)".to_owned(), sarsa_lambda_time.as_secs_f64()));
 
     // DynaQ
-    let mut dyna_q_algo = DynaQ::new(alpha, epsilon, k, max_steps, deterministic, true, env);
-    let dyna_q_time = bench_runtime_dyna(env, &mut dyna_q_algo, episodes, seed, num_seeds);
+    let dyna_q_time = bench_runtime(
+        env,
+        |env| {
+            let algo = DynaQ::new(alpha, epsilon, k, max_steps, deterministic, true, env);
+            TabularAgent::from_mdp(algo, env)
+        },
+        runner,
+        episodes,
+        seed,
+        num_seeds,
+    );
     results.push(("DynaQ".to_owned(), dyna_q_time.as_secs_f64()));
 
     //
@@ -105,36 +145,18 @@ fn bench_all_algo<M: GenericMdp<S, A>, S: GenericState, A: GenericAction>(
     results
 }
 
-fn bench_runtime_algo_random_mdp(
-    algo: &impl GenericStateActionAlgorithm,
-    episodes: usize,
-    seed: u64,
-    iterations: usize,
-    num_seeds: usize,
-) -> Duration {
-    let mut total_duration: Duration = Duration::new(0, 0);
-
-    for i in 0..num_seeds {
-        let mut mdp_rng = ChaCha20Rng::seed_from_u64(seed + i as u64);
-        for _ in 0..iterations {
-            let mdp = generate_random_mdp(5, 2, 1, (2, 2), (1, 3), (-1.0, 10.0), &mut mdp_rng);
-            let mut algo_rng = ChaCha20Rng::seed_from_u64(seed + i as u64);
-            let start = Instant::now();
-            algo.run(&mdp, episodes, &mut algo_rng);
-            total_duration = total_duration.saturating_add(start.elapsed());
-        }
-    }
-
-    total_duration.div_f64(num_seeds as f64)
-}
-
-fn bench_runtime_algo_random_mdp_dyna(
-    algo: &mut DynaQ<IndexState, IndexAction>,
+fn bench_runtime_algo_random_mdp<G, F>(
+    new_agent: F,
+    runner: Runner,
     episodes: usize,
     seed: u64,
     iterations: usize,
     num_seeds: usize,
-) -> Duration {
+) -> Duration
+where
+    G: Agent<IndexState, IndexAction>,
+    F: Fn(&IndexMdp) -> G,
+{
     let mut total_duration: Duration = Duration::new(0, 0);
 
     for i in 0..num_seeds {
@@ -142,9 +164,9 @@ fn bench_runtime_algo_random_mdp_dyna(
         for _ in 0..iterations {
             let mdp = generate_random_mdp(5, 2, 1, (2, 2), (1, 3), (-1.0, 10.0), &mut mdp_rng);
             let mut algo_rng = ChaCha20Rng::seed_from_u64(seed + i as u64);
-            algo.clear_model();
+            let mut agent = new_agent(&mdp);
             let start = Instant::now();
-            algo.run(&mdp, episodes, &mut algo_rng);
+            runner.run(&mdp, &mut agent, episodes, &mut algo_rng);
             total_duration = total_duration.saturating_add(start.elapsed());
         }
     }
@@ -165,44 +187,82 @@ fn bench_all_algo_random_mdp(
     let lambda = 0.9;
     let k = 5;
     let max_steps = 1000;
+    let runner = Runner::new(max_steps);
     let mut results: Vec<(String, f64)> = vec![];
 
     // monte carlo
-    let mc_algo = MonteCarlo::new(epsilon, max_steps);
-    let mc_time = bench_runtime_algo_random_mdp(&mc_algo, episodes, seed, iterations, num_seeds);
+    let mc_time = bench_runtime_algo_random_mdp(
+        |mdp| TabularAgent::from_mdp(MonteCarlo::new(epsilon, max_steps), mdp),
+        runner,
+        episodes,
+        seed,
+        iterations,
+        num_seeds,
+    );
     results.push(("MC".to_owned(), mc_time.as_secs_f64()));
 
     // Q-Learning
-    let q_algo = QLearning::new(alpha, epsilon, max_steps);
-    let q_time = bench_runtime_algo_random_mdp(&q_algo, episodes, seed, iterations, num_seeds);
+    let q_time = bench_runtime_algo_random_mdp(
+        |mdp| TabularAgent::from_mdp(QLearning::new(alpha, epsilon, max_steps), mdp),
+        runner,
+        episodes,
+        seed,
+        iterations,
+        num_seeds,
+    );
     results.push(("Q-Learning".to_owned(), q_time.as_secs_f64()));
 
     // SARSA
-    let sarsa_algo = Sarsa::new(alpha, epsilon, max_steps);
-    let sarsa_time =
-        bench_runtime_algo_random_mdp(&sarsa_algo, episodes, seed, iterations, num_seeds);
+    let sarsa_time = bench_runtime_algo_random_mdp(
+        |mdp| TabularAgent::from_mdp(Sarsa::new(alpha, epsilon, max_steps), mdp),
+        runner,
+        episodes,
+        seed,
+        iterations,
+        num_seeds,
+    );
     results.push(("SARSA".to_owned(), sarsa_time.as_secs_f64()));
 
     // Q-Learning(lambda)
-    let q_lambda_algo =
-        QLearningLambda::new(alpha, epsilon, lambda, max_steps, Trace::Accumulating);
-    let q_lambda_time =
-        bench_runtime_algo_random_mdp(&q_lambda_algo, episodes, seed, iterations, num_seeds);
+    let q_lambda_time = bench_runtime_algo_random_mdp(
+        |mdp| {
+            let algo = QLearningLambda::new(alpha, epsilon, lambda, max_steps, Trace::Accumulating);
+            TabularAgent::from_mdp(algo, mdp)
+        },
+        runner,
+        episodes,
+        seed,
+        iterations,
+        num_seeds,
+    );
     results.push(("Q-Learning(lambda)".to_owned(), q_lambda_time.as_secs_f64()));
 
-    // Q-Learning(lambda)
-    let sarsa_lambda_algo =
-        SarsaLambda::new(alpha, epsilon, lambda, max_steps, Trace::Accumulating);
-    let sarsa_lambda_time =
-        bench_runtime_algo_random_mdp(&sarsa_lambda_algo, episodes, seed, iterations, num_seeds);
+    // SARSA(lambda)
+    let sarsa_lambda_time = bench_runtime_algo_random_mdp(
+        |mdp| {
+            let algo = SarsaLambda::new(alpha, epsilon, lambda, max_steps, Trace::Accumulating);
+            TabularAgent::from_mdp(algo, mdp)
+        },
+        runner,
+        episodes,
+        seed,
+        iterations,
+        num_seeds,
+    );
     results.push(("SARSA(lambda)".to_owned(), sarsa_lambda_time.as_secs_f64()));
 
     // DynaQ
-    let mut mdp_rng = ChaCha20Rng::seed_from_u64(seed);
-    let mdp = generate_random_mdp(5, 2, 1, (2, 2), (1, 3), (-1.0, 10.0), &mut mdp_rng);
-    let mut dyna_q_algo = DynaQ::new(alpha, epsilon, k, max_steps, deterministic, true, &mdp);
-    let dyna_q_time =
-        bench_runtime_algo_random_mdp_dyna(&mut dyna_q_algo, episodes, seed, iterations, num_seeds);
+    let dyna_q_time = bench_runtime_algo_random_mdp(
+        |mdp| {
+            let algo = DynaQ::new(alpha, epsilon, k, max_steps, deterministic, true, mdp);
+            TabularAgent::from_mdp(algo, mdp)
+        },
+        runner,
+        episodes,
+        seed,
+        iterations,
+        num_seeds,
+    );
     results.push(("DynaQ".to_owned(), dyna_q_time.as_secs_f64()));
 
     //
@@ -255,6 +315,27 @@ pub fn bench_runtime_all_env() {
             .or_insert(vec![*time]);
     });
 
+    // windy gridworld, the stochastic wind is not deterministic
+    let wgw_mdp = crate::envs::windy_grid_world::build_mdp(Connectivity::Four, false).unwrap();
+    let wgw_results = bench_environment(&wgw_mdp, episodes, seed, num_seeds, true);
+
+    wgw_results.iter().for_each(|(algo, time)| {
+        results
+            .entry(algo)
+            .and_modify(|vec| vec.push(*time))
+            .or_insert(vec![*time]);
+    });
+
+    let swgw_mdp = crate::envs::windy_grid_world::build_mdp(Connectivity::Four, true).unwrap();
+    let swgw_results = bench_environment(&swgw_mdp, episodes, seed, num_seeds, false);
+
+    swgw_results.iter().for_each(|(algo, time)| {
+        results
+            .entry(algo)
+            .and_modify(|vec| vec.push(*time))
+            .or_insert(vec![*time]);
+    });
+
     // intersection
     let intersection_mdp = crate::envs::my_intersection::MyIntersectionMdp::new(0.6, 0.2, 10);
     let intersection_results =
@@ -267,6 +348,17 @@ pub fn bench_runtime_all_env() {
             .or_insert(vec![*time]);
     });
 
+    // traffic signal
+    let traffic_mdp = crate::envs::traffic::TrafficMdp::new([0.4, 0.4, 0.2, 0.2]);
+    let traffic_results = bench_environment(&traffic_mdp, episodes, seed, num_seeds, false);
+
+    traffic_results.iter().for_each(|(algo, time)| {
+        results
+            .entry(algo)
+            .and_modify(|vec| vec.push(*time))
+            .or_insert(vec![*time]);
+    });
+
     // random mdps
     let random_mdp_results =
         bench_all_algo_random_mdp(episodes, seed, iterations, num_seeds, false);
@@ -283,7 +375,10 @@ pub fn bench_runtime_all_env() {
             "Algorithm",
             "Cliff Walking",
             "Slippery Cliff Walking",
+            "Windy Grid World",
+            "Stochastic Windy Grid World",
             "Intersection",
+            "Traffic",
             "Arbitrary MDPs",
         ])
         .expect("csv write record error");
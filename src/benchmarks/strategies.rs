@@ -1,16 +1,10 @@
-use rand::SeedableRng;
-use rand_chacha::ChaCha20Rng;
-
 use crate::{
+    agent::{runner::Runner, Agent, TabularAgent},
     algorithms::{
-        dyna_q::{Dyna, DynaQ},
-        monte_carlo::MonteCarlo,
-        q_learning::QLearning,
-        q_learning_lambda::QLearningLambda,
-        sarsa::Sarsa,
-        sarsa_lambda::SarsaLambda,
-        GenericStateActionAlgorithm, Trace,
+        dyna_q::DynaQ, monte_carlo::MonteCarlo, q_learning::QLearning,
+        q_learning_lambda::QLearningLambda, sarsa::Sarsa, sarsa_lambda::SarsaLambda, Trace,
     },
+    benchmarks::executor::{mean, run_seeds},
     envs::my_intersection::MyIntersectionMdp,
     eval::{evaluate_greedy_policy, evaluate_random_policy},
     experiments::intersection::fixed_cycle,
@@ -34,71 +28,87 @@ pub fn compare_intersection() {
     let deterministic = true;
     let max_steps = 2000;
     let train_episodes = 1000;
+    let runner = Runner::new(max_steps);
     let mut results: Vec<(String, f64)> = vec![];
 
     // monte carlo
     println!("MC");
-    let mc_algo = MonteCarlo::new(epsilon, max_steps);
-    let mc_reward =
-        bench_average_strategy(&mdp, &mc_algo, seed, num_seeds, train_episodes, max_steps);
+    let mc_reward = bench_average_strategy(
+        &mdp,
+        |env| TabularAgent::from_mdp(MonteCarlo::new(epsilon, max_steps), env),
+        runner,
+        seed,
+        num_seeds,
+        train_episodes,
+    );
     results.push(("MC".to_owned(), mc_reward));
 
     // Q-Learning
     println!("Q");
-    let q_algo = QLearning::new(alpha, epsilon, max_steps);
-    let q_reward =
-        bench_average_strategy(&mdp, &q_algo, seed, num_seeds, train_episodes, max_steps);
+    let q_reward = bench_average_strategy(
+        &mdp,
+        |env| TabularAgent::from_mdp(QLearning::new(alpha, epsilon, max_steps), env),
+        runner,
+        seed,
+        num_seeds,
+        train_episodes,
+    );
     results.push(("Q-Learning".to_owned(), q_reward));
 
     // SARSA
     println!("SARSA");
-    let sarsa_algo = Sarsa::new(alpha, epsilon, max_steps);
     let sarsa_reward = bench_average_strategy(
         &mdp,
-        &sarsa_algo,
+        |env| TabularAgent::from_mdp(Sarsa::new(alpha, epsilon, max_steps), env),
+        runner,
         seed,
         num_seeds,
         train_episodes,
-        max_steps,
     );
     results.push(("SARSA".to_owned(), sarsa_reward));
 
     // Q-Learning(lambda)
     println!("Q lambda");
-    let q_lambda_algo = QLearningLambda::new(alpha, epsilon, lambda, max_steps, trace);
     let q_lambda_reward = bench_average_strategy(
         &mdp,
-        &q_lambda_algo,
+        |env| {
+            let algo = QLearningLambda::new(alpha, epsilon, lambda, max_steps, trace);
+            TabularAgent::from_mdp(algo, env)
+        },
+        runner,
         seed,
         num_seeds,
         train_episodes,
-        max_steps,
     );
     results.push(("Q-Learning(lambda)".to_owned(), q_lambda_reward));
 
-    // Q-Learning(lambda)
+    // SARSA(lambda)
     println!("SARSA lambda");
-    let sarsa_lambda_algo = SarsaLambda::new(alpha, epsilon, lambda, max_steps, trace);
     let sarsa_lambda_reward = bench_average_strategy(
         &mdp,
-        &sarsa_lambda_algo,
+        |env| {
+            let algo = SarsaLambda::new(alpha, epsilon, lambda, max_steps, trace);
+            TabularAgent::from_mdp(algo, env)
+        },
+        runner,
         seed,
         num_seeds,
         train_episodes,
-        max_steps,
     );
     results.push(("SARSA(lambda)".to_owned(), sarsa_lambda_reward));
 
     // DynaQ
     println!("DynaQ");
-    let mut dyna_q_algo = DynaQ::new(alpha, epsilon, k, max_steps, deterministic, true, &mdp);
-    let dyna_q_reward = bench_average_dynaq(
+    let dyna_q_reward = bench_average_strategy(
         &mdp,
-        &mut dyna_q_algo,
+        |env| {
+            let algo = DynaQ::new(alpha, epsilon, k, max_steps, deterministic, true, env);
+            TabularAgent::from_mdp(algo, env)
+        },
+        runner,
         seed,
         num_seeds,
         train_episodes,
-        max_steps,
     );
     results.push(("DynaQ".to_owned(), dyna_q_reward));
 
@@ -136,47 +146,35 @@ pub fn compare_intersection() {
     csv_writer.serialize(result).expect("csv error");
 }
 
-fn bench_average_strategy<M: GenericMdp<S, A>, S: GenericState, A: GenericAction>(
-    env: &M,
-    algo: &impl GenericStateActionAlgorithm,
-    seed: u64,
-    num_seeds: usize,
-    train_episodes: usize,
-    max_steps: usize,
-) -> f64 {
-    let mut eval_rng = ChaCha20Rng::seed_from_u64(seed);
-    let eval_episodes = 10;
-    let mut total_rewards = 0.0;
-
-    for i in 0..num_seeds {
-        let mut rng = ChaCha20Rng::seed_from_u64(seed + i as u64);
-        let q_map = algo.run(env, train_episodes, &mut rng);
-        total_rewards +=
-            evaluate_greedy_policy(env, &q_map, eval_episodes, max_steps, &mut eval_rng);
-    }
-    total_rewards / num_seeds as f64
-}
-
-fn bench_average_dynaq<M: GenericMdp<S, A>, S: GenericState, A: GenericAction>(
+fn bench_average_strategy<M, S, A, G, F>(
     env: &M,
-    algo: &mut DynaQ<S, A>,
+    new_agent: F,
+    runner: Runner,
     seed: u64,
     num_seeds: usize,
     train_episodes: usize,
-    max_steps: usize,
-) -> f64 {
-    let mut eval_rng = ChaCha20Rng::seed_from_u64(seed);
+) -> f64
+where
+    M: GenericMdp<S, A> + Sync,
+    S: GenericState,
+    A: GenericAction,
+    G: Agent<S, A>,
+    F: Fn(&M) -> G + Sync,
+{
     let eval_episodes = 10;
-    let mut total_rewards = 0.0;
 
-    for i in 0..num_seeds {
-        let mut rng = ChaCha20Rng::seed_from_u64(seed + i as u64);
-        algo.clear_model();
-        let q_map = algo.run(env, train_episodes, &mut rng);
-        total_rewards +=
-            evaluate_greedy_policy(env, &q_map, eval_episodes, max_steps, &mut eval_rng);
-    }
-    total_rewards / num_seeds as f64
+    let rewards = run_seeds(seed, num_seeds, |_, rng| {
+        let mut agent = new_agent(env);
+        runner.run(env, &mut agent, train_episodes, rng);
+        evaluate_greedy_policy(
+            env,
+            agent.q_map(),
+            eval_episodes,
+            runner.get_max_steps(),
+            rng,
+        )
+    });
+    mean(&rewards)
 }
 
 fn bench_average_fixed_cycle(
@@ -188,13 +186,11 @@ fn bench_average_fixed_cycle(
     ew_time: usize,
 ) -> f64 {
     let eval_episodes = 10;
-    let mut total_rewards = 0.0;
 
-    for i in 0..num_seeds {
-        let mut rng = ChaCha20Rng::seed_from_u64(seed + i as u64);
-        total_rewards += fixed_cycle(env, eval_episodes, max_steps, ns_time, ew_time, &mut rng);
-    }
-    total_rewards / num_seeds as f64
+    let rewards = run_seeds(seed, num_seeds, |_, rng| {
+        fixed_cycle(env, eval_episodes, max_steps, ns_time, ew_time, rng)
+    });
+    mean(&rewards)
 }
 
 fn bench_average_random(
@@ -204,13 +200,11 @@ fn bench_average_random(
     max_steps: usize,
 ) -> f64 {
     let eval_episodes = 10;
-    let mut total_rewards = 0.0;
 
-    for i in 0..num_seeds {
-        let mut rng = ChaCha20Rng::seed_from_u64(seed + i as u64);
-        total_rewards += evaluate_random_policy(env, eval_episodes, max_steps, &mut rng);
-    }
-    total_rewards / num_seeds as f64
+    let rewards = run_seeds(seed, num_seeds, |_, rng| {
+        evaluate_random_policy(env, eval_episodes, max_steps, rng)
+    });
+    mean(&rewards)
 }
 
 pub fn test_intersection_params() {
@@ -250,28 +244,34 @@ fn test_params_lambda_trace(lambda: f64, trace: Trace) -> (f64, f64) {
     let max_steps = 2000;
     let train_episodes = 100;
 
+    let runner = Runner::new(max_steps);
+
     // Q-Learning(lambda)
     println!("Q lambda");
-    let q_lambda_algo = QLearningLambda::new(alpha, epsilon, lambda, max_steps, trace);
     let q_lambda_reward = bench_average_strategy(
         &mdp,
-        &q_lambda_algo,
+        |env| {
+            let algo = QLearningLambda::new(alpha, epsilon, lambda, max_steps, trace);
+            TabularAgent::from_mdp(algo, env)
+        },
+        runner,
         seed,
         num_seeds,
         train_episodes,
-        max_steps,
     );
 
-    // Q-Learning(lambda)
+    // SARSA(lambda)
     println!("SARSA lambda");
-    let sarsa_lambda_algo = SarsaLambda::new(alpha, epsilon, lambda, max_steps, trace);
     let sarsa_lambda_reward = bench_average_strategy(
         &mdp,
-        &sarsa_lambda_algo,
+        |env| {
+            let algo = SarsaLambda::new(alpha, epsilon, lambda, max_steps, trace);
+            TabularAgent::from_mdp(algo, env)
+        },
+        runner,
         seed,
         num_seeds,
         train_episodes,
-        max_steps,
     );
 
     (q_lambda_reward, sarsa_lambda_reward)
@@ -1,32 +1,256 @@
-#![allow(unused_variables)]
+use std::collections::BTreeMap;
 
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
-use crate::mdp::GenericMdp;
+use crate::mdp::{GenericMdp, MapMdp, Reward};
 
-pub struct BlackjackMdp;
+// Blackjack as in Sutton & Barto, example 5.1. Cards are drawn from an infinite deck, face cards
+// count as 10 and an ace counts as 11 as long as the hand does not bust. The player automatically
+// hits below 12, the dealer hits below 17 and sticks on any 17 including a soft one. A natural,
+// 21 with the first two cards, leaves the player nothing to decide but beats any other 21. Two
+// naturals draw.
+pub struct BlackjackMdp {
+    states_actions: Vec<(BlackjackState, BlackjackAction)>,
+}
+
+impl Default for BlackjackMdp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl BlackjackMdp {
+    pub fn new() -> Self {
+        let states_actions = Self::running_states()
+            .into_iter()
+            .flat_map(|state| ACTIONS.map(|action| (state, action)))
+            .chain((1..=10).map(|dealer| (BlackjackState::Natural(dealer), BlackjackAction::Stick)))
+            .collect();
+        Self { states_actions }
+    }
+
+    // ace = 1, jack, queen and king count as 10
     pub fn draw_card<R: Rng>(&self, rng: &mut R) -> u8 {
-        rng.gen_range(1..=10)
+        rng.gen_range(1..=13).min(10)
+    }
+
+    // the 200 non terminal states
+    pub fn running_states() -> Vec<BlackjackState> {
+        let mut states = vec![];
+        for player in 12..=21 {
+            for dealer in 1..=10 {
+                for usable_ace in [false, true] {
+                    states.push(BlackjackState::Running(player, dealer, usable_ace));
+                }
+            }
+        }
+        states
+    }
+
+    // exact distribution of get_initial_state
+    pub fn initial_distribution(&self) -> Vec<(f64, BlackjackState)> {
+        let mut player: BTreeMap<(u8, bool, bool), f64> = BTreeMap::new();
+        deal_player(0, false, 0, 1.0, &mut player);
+
+        let mut distribution = vec![];
+        for ((sum, usable_ace, natural), prob) in player {
+            for (card, card_prob) in card_distribution() {
+                let state = if natural {
+                    BlackjackState::Natural(card)
+                } else {
+                    BlackjackState::Running(sum, card, usable_ace)
+                };
+                distribution.push((prob * card_prob, state));
+            }
+        }
+        distribution
+    }
+
+    // exact model of the game. A MapMdp has a single initial state, so it starts in the first
+    // running state, use initial_distribution to weigh the values of the starting states.
+    pub fn build_model(&self) -> anyhow::Result<MapMdp<BlackjackState, BlackjackAction>> {
+        let mut mdp = MapMdp::new(
+            self.get_discount_factor(),
+            BlackjackState::Running(12, 1, false),
+        );
+        let mut stick_outcomes: BTreeMap<u8, BTreeMap<Hand, f64>> = BTreeMap::new();
+
+        for state in Self::running_states() {
+            let BlackjackState::Running(player, dealer, usable_ace) = state else {
+                unreachable!()
+            };
+
+            let hit = card_distribution()
+                .into_iter()
+                .map(|(card, prob)| {
+                    let (next_state, reward) = hit_outcome(player, dealer, usable_ace, card);
+                    (prob, next_state, reward)
+                })
+                .collect();
+            mdp.add_transition_vector((state, BlackjackAction::Hit), hit)?;
+
+            let dealer_hands = stick_outcomes
+                .entry(dealer)
+                .or_insert_with(|| dealer_distribution(dealer));
+            mdp.add_transition_vector(
+                (state, BlackjackAction::Stick),
+                stick_transitions((player, false), dealer_hands),
+            )?;
+        }
+        for dealer in 1..=10 {
+            let dealer_hands = stick_outcomes
+                .entry(dealer)
+                .or_insert_with(|| dealer_distribution(dealer));
+            mdp.add_transition_vector(
+                (BlackjackState::Natural(dealer), BlackjackAction::Stick),
+                stick_transitions((21, true), dealer_hands),
+            )?;
+        }
+        [
+            BlackjackState::Win,
+            BlackjackState::Draw,
+            BlackjackState::Loss,
+        ]
+        .into_iter()
+        .for_each(|state| mdp.add_terminal_state(state));
+
+        Ok(mdp)
     }
 }
 
 //BlackjackState = (current_sum: 12-21, dealer card: 1-10, usable ace: true/false)
-#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Copy)]
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Copy, Serialize, Deserialize)]
 pub enum BlackjackState {
     Running(u8, u8, bool),
+    // the player was dealt a natural, only sticking is possible. Holds the dealer card.
+    Natural(u8),
     Win,
     Draw,
     Loss,
 }
 
-#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Copy)]
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Copy, Serialize, Deserialize)]
 pub enum BlackjackAction {
     Hit = 0,
     Stick = 1,
 }
 
+const ACTIONS: [BlackjackAction; 2] = [BlackjackAction::Hit, BlackjackAction::Stick];
+
+// dealer sums above 21 are busts
+const BUST: u8 = 22;
+
+// final sum of a hand and whether it is a natural. Hands compare by sum first, so a natural only
+// beats another 21.
+type Hand = (u8, bool);
+
+fn card_distribution() -> Vec<(u8, f64)> {
+    (1..=10)
+        .map(|card| (card, if card == 10 { 4.0 / 13.0 } else { 1.0 / 13.0 }))
+        .collect()
+}
+
+// adds a card to a hand, an ace counts as 11 if that does not bust and an usable ace falls back to
+// 1 once the hand would bust
+fn add_card(sum: u8, usable_ace: bool, card: u8) -> (u8, bool) {
+    let (mut sum, mut usable_ace) = if card == 1 && !usable_ace && sum + 11 <= 21 {
+        (sum + 11, true)
+    } else {
+        (sum + card, usable_ace)
+    };
+    if sum > 21 && usable_ace {
+        sum -= 10;
+        usable_ace = false;
+    }
+    (sum, usable_ace)
+}
+
+fn hit_outcome(player: u8, dealer: u8, usable_ace: bool, card: u8) -> (BlackjackState, Reward) {
+    let (sum, usable_ace) = add_card(player, usable_ace, card);
+    if sum > 21 {
+        (BlackjackState::Loss, -1.0)
+    } else {
+        (BlackjackState::Running(sum, dealer, usable_ace), 0.0)
+    }
+}
+
+fn showdown(player: Hand, dealer: Hand) -> (BlackjackState, Reward) {
+    let state = if dealer.0 > 21 || player > dealer {
+        BlackjackState::Win
+    } else if player == dealer {
+        BlackjackState::Draw
+    } else {
+        BlackjackState::Loss
+    };
+    (state, terminal_reward(state))
+}
+
+fn stick_transitions(
+    player: Hand,
+    dealer_hands: &BTreeMap<Hand, f64>,
+) -> Vec<(f64, BlackjackState, Reward)> {
+    let mut stick: BTreeMap<BlackjackState, f64> = BTreeMap::new();
+    dealer_hands
+        .iter()
+        .for_each(|(dealer, prob)| *stick.entry(showdown(player, *dealer).0).or_default() += prob);
+    stick
+        .into_iter()
+        .map(|(next_state, prob)| (prob, next_state, terminal_reward(next_state)))
+        .collect()
+}
+
+fn terminal_reward(state: BlackjackState) -> Reward {
+    match state {
+        BlackjackState::Win => 1.0,
+        BlackjackState::Loss => -1.0,
+        _ => 0.0,
+    }
+}
+
+// the two cards of the player and further cards while the sum is below 12, keyed by sum, usable
+// ace and natural
+fn deal_player(
+    sum: u8,
+    usable_ace: bool,
+    cards: usize,
+    prob: f64,
+    hands: &mut BTreeMap<(u8, bool, bool), f64>,
+) {
+    if cards >= 2 && sum >= 12 {
+        let natural = cards == 2 && sum == 21;
+        *hands.entry((sum, usable_ace, natural)).or_default() += prob;
+        return;
+    }
+    for (card, card_prob) in card_distribution() {
+        let (sum, usable_ace) = add_card(sum, usable_ace, card);
+        deal_player(sum, usable_ace, cards + 1, prob * card_prob, hands);
+    }
+}
+
+// final dealer hands for the showing card, BUST for every sum above 21
+fn dealer_distribution(showing: u8) -> BTreeMap<Hand, f64> {
+    fn play(sum: u8, usable_ace: bool, cards: usize, prob: f64, hands: &mut BTreeMap<Hand, f64>) {
+        if sum >= 17 {
+            *hands.entry(dealer_hand(sum, cards)).or_default() += prob;
+            return;
+        }
+        for (card, card_prob) in card_distribution() {
+            let (sum, usable_ace) = add_card(sum, usable_ace, card);
+            play(sum, usable_ace, cards + 1, prob * card_prob, hands);
+        }
+    }
+
+    let mut hands = BTreeMap::new();
+    let (sum, usable_ace) = add_card(0, false, showing);
+    play(sum, usable_ace, 1, 1.0, &mut hands);
+    hands
+}
+
+fn dealer_hand(sum: u8, cards: usize) -> Hand {
+    (sum.min(BUST), cards == 2 && sum == 21)
+}
+
 impl GenericMdp<BlackjackState, BlackjackAction> for BlackjackMdp {
     fn perform_action<R: rand::Rng>(
         &self,
@@ -34,31 +258,50 @@ impl GenericMdp<BlackjackState, BlackjackAction> for BlackjackMdp {
         rng: &mut R,
     ) -> (BlackjackState, crate::mdp::Reward) {
         let (state, action) = state_action;
-        let BlackjackState::Running(player, dealer, useable_ace) = state else {
-            panic!("what are you doing here")
-        };
-
-        let card = match action {
-            BlackjackAction::Hit => Some(self.draw_card(rng)),
-            BlackjackAction::Stick => None,
+        let (player, dealer, usable_ace, natural) = match state {
+            BlackjackState::Running(player, dealer, usable_ace) => {
+                (player, dealer, usable_ace, false)
+            }
+            BlackjackState::Natural(dealer) => (21, dealer, true, true),
+            _ => panic!("no actions in terminal state {:?}", state),
         };
 
-        if let Some(card) = card {}
-
-        todo!()
+        match action {
+            BlackjackAction::Hit => {
+                assert!(!natural, "a natural can only stick");
+                hit_outcome(player, dealer, usable_ace, self.draw_card(rng))
+            }
+            BlackjackAction::Stick => {
+                // the hidden card is drawn once the dealer plays, with an infinite deck that is
+                // the same as drawing it at the start
+                let (mut dealer_sum, mut dealer_ace) = add_card(0, false, dealer);
+                let mut dealer_cards = 1;
+                while dealer_sum < 17 {
+                    (dealer_sum, dealer_ace) =
+                        add_card(dealer_sum, dealer_ace, self.draw_card(rng));
+                    dealer_cards += 1;
+                }
+                showdown((player, natural), dealer_hand(dealer_sum, dealer_cards))
+            }
+        }
     }
 
-    fn get_possible_actions(&self, _: BlackjackState) -> Vec<BlackjackAction> {
-        vec![BlackjackAction::Hit, BlackjackAction::Stick]
+    fn get_possible_actions(&self, state: BlackjackState) -> Vec<BlackjackAction> {
+        match state {
+            BlackjackState::Running(_, _, _) => ACTIONS.to_vec(),
+            BlackjackState::Natural(_) => vec![BlackjackAction::Stick],
+            _ => vec![],
+        }
     }
 
     fn get_all_state_actions(&self) -> &[(BlackjackState, BlackjackAction)] {
-        todo!()
+        &self.states_actions
     }
 
     fn is_terminal(&self, state: BlackjackState) -> bool {
         match state {
             BlackjackState::Running(_, _, _) => false,
+            BlackjackState::Natural(_) => false,
             BlackjackState::Win => true,
             BlackjackState::Draw => true,
             BlackjackState::Loss => true,
@@ -66,28 +309,19 @@ impl GenericMdp<BlackjackState, BlackjackAction> for BlackjackMdp {
     }
 
     fn get_initial_state<R: Rng>(&self, rng: &mut R) -> BlackjackState {
-        let player_card_1 = self.draw_card(rng);
-        let player_card_2 = self.draw_card(rng);
-        let player_sum = player_card_1 + player_card_2;
-
-        let dealer_card_1 = self.draw_card(rng);
-        let dealer_card_2 = self.draw_card(rng);
-        let dealer_sum = dealer_card_1 + dealer_card_2;
-
-        // check for immediate end condition
-        if player_sum == 11 {
-            if dealer_sum == 11 {
-                return BlackjackState::Draw;
-            } else {
-                return BlackjackState::Win;
-            }
+        let (mut player_sum, mut useable_ace) = (0, false);
+        let mut cards = 0;
+        while cards < 2 || player_sum < 12 {
+            (player_sum, useable_ace) = add_card(player_sum, useable_ace, self.draw_card(rng));
+            cards += 1;
         }
+        let dealer_card = self.draw_card(rng);
 
-        let player_state = if player_sum < 12 { 12 } else { player_sum };
-        let dealer_state = dealer_card_2;
-        let useable_ace = player_card_1 == 1 || player_card_2 == 1;
-
-        BlackjackState::Running(player_state, dealer_state, useable_ace)
+        if cards == 2 && player_sum == 21 {
+            BlackjackState::Natural(dealer_card)
+        } else {
+            BlackjackState::Running(player_sum, dealer_card, useable_ace)
+        }
     }
 
     fn get_discount_factor(&self) -> f64 {
@@ -0,0 +1,213 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::mdp::{MapMdp, Probability, Reward};
+
+// Jack's car rental (Sutton & Barto, example 4.2). Every night cars are moved between the two
+// locations, every day customers rent and return cars, both Poisson distributed. Requests beyond
+// the available cars are lost and returns beyond the capacity vanish, so the tails of the
+// distributions are folded into the last outcome and the model is exact. Rentals are paid out as
+// their expectation conditioned on the next state, which keeps every expected value exact.
+
+// cars at the first and second location at the end of the day
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Copy, Serialize, Deserialize)]
+pub struct CarRentalState(pub usize, pub usize);
+
+// cars moved overnight from the first to the second location, negative moves go the other way
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Copy, Serialize, Deserialize)]
+pub struct CarRentalAction(pub isize);
+
+#[derive(Debug, Clone)]
+pub struct CarRentalConfig {
+    pub max_cars: usize,
+    pub max_move: usize,
+    pub move_cost: Reward,
+    pub rental_reward: Reward,
+    pub request_rates: [f64; 2],
+    pub return_rates: [f64; 2],
+    pub discount_factor: f64,
+    // an employee drives one car from the first to the second location for free
+    pub free_shuttle: bool,
+    // (limit, fee), every location with more than limit cars overnight pays the fee
+    pub parking_fee: Option<(usize, Reward)>,
+}
+
+impl Default for CarRentalConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CarRentalConfig {
+    // the original problem
+    pub fn new() -> Self {
+        Self {
+            max_cars: 20,
+            max_move: 5,
+            move_cost: 2.0,
+            rental_reward: 10.0,
+            request_rates: [3.0, 4.0],
+            return_rates: [3.0, 2.0],
+            discount_factor: 0.9,
+            free_shuttle: false,
+            parking_fee: None,
+        }
+    }
+
+    // the variant of exercise 4.7, a free shuttle and a $4 fee above 10 cars
+    pub fn modified() -> Self {
+        Self::new()
+            .with_free_shuttle(true)
+            .with_parking_fee(10, 4.0)
+    }
+
+    pub fn with_max_cars(mut self, max_cars: usize) -> Self {
+        self.max_cars = max_cars;
+        self
+    }
+
+    pub fn with_max_move(mut self, max_move: usize) -> Self {
+        self.max_move = max_move;
+        self
+    }
+
+    pub fn with_move_cost(mut self, move_cost: Reward) -> Self {
+        self.move_cost = move_cost;
+        self
+    }
+
+    pub fn with_rental_reward(mut self, rental_reward: Reward) -> Self {
+        self.rental_reward = rental_reward;
+        self
+    }
+
+    pub fn with_request_rates(mut self, request_rates: [f64; 2]) -> Self {
+        self.request_rates = request_rates;
+        self
+    }
+
+    pub fn with_return_rates(mut self, return_rates: [f64; 2]) -> Self {
+        self.return_rates = return_rates;
+        self
+    }
+
+    pub fn with_discount_factor(mut self, discount_factor: f64) -> Self {
+        self.discount_factor = discount_factor;
+        self
+    }
+
+    pub fn with_free_shuttle(mut self, free_shuttle: bool) -> Self {
+        self.free_shuttle = free_shuttle;
+        self
+    }
+
+    pub fn with_parking_fee(mut self, limit: usize, fee: Reward) -> Self {
+        self.parking_fee = Some((limit, fee));
+        self
+    }
+
+    pub fn build(&self) -> anyhow::Result<MapMdp<CarRentalState, CarRentalAction>> {
+        if self.max_cars < 1 {
+            anyhow::bail!("max_cars needs to be at least 1");
+        }
+        let initial_state = CarRentalState(self.max_cars / 2, self.max_cars / 2);
+        let mut mdp = MapMdp::new(self.discount_factor, initial_state);
+
+        // outcomes of a day for every number of cars in the morning, per location
+        let days: [Vec<Vec<(Probability, usize, Reward)>>; 2] = [0, 1].map(|location| {
+            (0..=self.max_cars)
+                .map(|cars| self.day(location, cars))
+                .collect()
+        });
+
+        for first in 0..=self.max_cars {
+            for second in 0..=self.max_cars {
+                for action in self.actions(first, second) {
+                    let moved = action.0;
+                    // cars moved beyond the capacity are returned to the company
+                    let morning = [
+                        ((first as isize - moved) as usize).min(self.max_cars),
+                        ((second as isize + moved) as usize).min(self.max_cars),
+                    ];
+                    let cost = self.cost(moved, morning);
+
+                    let mut transitions = vec![];
+                    for (prob_first, next_first, rented_first) in days[0][morning[0]].iter() {
+                        for (prob_second, next_second, rented_second) in days[1][morning[1]].iter()
+                        {
+                            transitions.push((
+                                prob_first * prob_second,
+                                CarRentalState(*next_first, *next_second),
+                                rented_first + rented_second - cost,
+                            ));
+                        }
+                    }
+                    mdp.add_transition_vector(
+                        (CarRentalState(first, second), action),
+                        transitions,
+                    )?;
+                }
+            }
+        }
+
+        Ok(mdp)
+    }
+
+    // moves that do not need more cars than the giving location has
+    pub fn actions(&self, first: usize, second: usize) -> Vec<CarRentalAction> {
+        let max_move = self.max_move as isize;
+        (-max_move.min(second as isize)..=max_move.min(first as isize))
+            .map(CarRentalAction)
+            .collect()
+    }
+
+    fn cost(&self, moved: isize, morning: [usize; 2]) -> Reward {
+        let paid_moves = if self.free_shuttle && moved > 0 {
+            moved.unsigned_abs() - 1
+        } else {
+            moved.unsigned_abs()
+        };
+        let parking = match self.parking_fee {
+            Some((limit, fee)) => morning.iter().filter(|cars| **cars > limit).count() as f64 * fee,
+            None => 0.0,
+        };
+        paid_moves as f64 * self.move_cost + parking
+    }
+
+    // distribution of the cars in the evening at a location that starts the day with cars, with the
+    // expected rental income given that number
+    fn day(&self, location: usize, cars: usize) -> Vec<(Probability, usize, Reward)> {
+        let mut outcomes: BTreeMap<usize, (Probability, Reward)> = BTreeMap::new();
+        for (rented_prob, rented) in truncated_poisson(self.request_rates[location], cars) {
+            let left = cars - rented;
+            for (returned_prob, returned) in
+                truncated_poisson(self.return_rates[location], self.max_cars - left)
+            {
+                let prob = rented_prob * returned_prob;
+                let outcome = outcomes.entry(left + returned).or_default();
+                outcome.0 += prob;
+                outcome.1 += prob * rented as f64 * self.rental_reward;
+            }
+        }
+        outcomes
+            .into_iter()
+            .filter(|(_, (prob, _))| *prob > 0.0)
+            .map(|(next, (prob, income))| (prob, next, income / prob))
+            .collect()
+    }
+}
+
+// poisson distribution over 0..=max, the last entry takes the whole tail
+fn truncated_poisson(rate: f64, max: usize) -> Vec<(Probability, usize)> {
+    let mut prob = (-rate).exp();
+    let mut distribution = vec![];
+    let mut total = 0.0;
+    for n in 0..max {
+        distribution.push((prob, n));
+        total += prob;
+        prob *= rate / (n + 1) as f64;
+    }
+    distribution.push(((1.0 - total).max(0.0), max));
+    distribution
+}
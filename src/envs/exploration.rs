@@ -0,0 +1,165 @@
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+use crate::mdp::{IndexAction, IndexMdp, IndexState};
+
+// hard exploration problems, the rewarding behaviour is rarely found by dithering exploration.
+// The optimal values in the comments come from value_iteration with a tolerance of 1e-9, see
+// test_exploration_mdps.
+
+pub const LEFT: IndexAction = IndexAction(0);
+pub const RIGHT: IndexAction = IndexAction(1);
+
+// RiverSwim (Strehl and Littman 2008). Swimming left always works and pays a little in the
+// leftmost state, swimming right against the current mostly fails but pays a lot in the
+// rightmost state. Continuing task, starts in state 0.
+// optimal value of state 0 with n = 6 and discount 0.95: 2.8016
+pub fn build_river_swim(n_states: usize, discount_factor: f64) -> anyhow::Result<IndexMdp> {
+    if n_states < 2 {
+        anyhow::bail!("RiverSwim needs at least two states");
+    }
+    let last = n_states - 1;
+    let mut mdp = IndexMdp::new(discount_factor, IndexState(0));
+
+    for i in 0..n_states {
+        let state = IndexState(i);
+        let left_reward = if i == 0 { 5.0 / 1000.0 } else { 0.0 };
+        mdp.add_transition_vector(
+            (state, LEFT),
+            vec![(1.0, IndexState(i.saturating_sub(1)), left_reward)],
+        )?;
+
+        let right = if i == 0 {
+            vec![(0.4, state, 0.0), (0.6, IndexState(1), 0.0)]
+        } else if i == last {
+            vec![(0.4, IndexState(i - 1), 0.0), (0.6, state, 1.0)]
+        } else {
+            vec![
+                (0.05, IndexState(i - 1), 0.0),
+                (0.6, state, 0.0),
+                (0.35, IndexState(i + 1), 0.0),
+            ]
+        };
+        mdp.add_transition_vector((state, RIGHT), right)?;
+    }
+
+    Ok(mdp)
+}
+
+// the n-chain of Strens (2000). Action 0 moves forward, at the end of the chain it stays and pays
+// 10, action 1 returns to the start and pays 2. With probability slip the other action happens.
+// Continuing task, starts in state 0.
+// optimal value of state 0 with n = 5, slip 0.2 and discount 0.99: 354.7681
+pub fn build_n_chain(n_states: usize, slip: f64, discount_factor: f64) -> anyhow::Result<IndexMdp> {
+    if n_states < 2 {
+        anyhow::bail!("the chain needs at least two states");
+    }
+    let last = n_states - 1;
+    let mut mdp = IndexMdp::new(discount_factor, IndexState(0));
+
+    for i in 0..n_states {
+        let state = IndexState(i);
+        let forward = if i == last {
+            (state, 10.0)
+        } else {
+            (IndexState(i + 1), 0.0)
+        };
+        let back = (IndexState(0), 2.0);
+
+        mdp.add_transition_vector(
+            (state, IndexAction(0)),
+            vec![(1.0 - slip, forward.0, forward.1), (slip, back.0, back.1)],
+        )?;
+        mdp.add_transition_vector(
+            (state, IndexAction(1)),
+            vec![(1.0 - slip, back.0, back.1), (slip, forward.0, forward.1)],
+        )?;
+    }
+
+    Ok(mdp)
+}
+
+// combination lock, only the right action of every state advances, any other action sends the
+// agent back to state 0. Opening the lock in state length pays 1 and ends the episode. The
+// combination is drawn from the seed.
+// optimal value of state 0 with discount 1: 1, with discount 0.99 and length 10: 0.99^9 = 0.9135.
+// Without discounting a reset costs nothing, so greedy policies need a discount below 1.
+pub fn build_combination_lock(
+    length: usize,
+    n_actions: usize,
+    discount_factor: f64,
+    seed: u64,
+) -> anyhow::Result<IndexMdp> {
+    if length == 0 || n_actions < 2 {
+        anyhow::bail!("the lock needs at least one state and two actions");
+    }
+    let mut rng = ChaCha20Rng::seed_from_u64(seed);
+    let mut mdp = IndexMdp::new(discount_factor, IndexState(0));
+
+    for i in 0..length {
+        let state = IndexState(i);
+        let correct = rng.gen_range(0..n_actions);
+        for a in 0..n_actions {
+            let transition = if a != correct {
+                (1.0, IndexState(0), 0.0)
+            } else if i + 1 == length {
+                (1.0, IndexState(length), 1.0)
+            } else {
+                (1.0, IndexState(i + 1), 0.0)
+            };
+            mdp.add_transition_vector((state, IndexAction(a)), vec![transition])?;
+        }
+    }
+    mdp.add_terminal_state(IndexState(length));
+
+    Ok(mdp)
+}
+
+// deep sea (Osband et al. 2019), an n x n grid the agent falls down one row per step while
+// moving left or right. Moving right costs 0.01 / n, reaching the bottom right corner pays 1.
+// Which action moves right is drawn per cell from the seed. The state of row r and column c is
+// r * n + c, the bottom row n is terminal.
+// optimal value of state 0 with discount 1: 1 - 0.01 (n - 1) / n, 0.991 with n = 10
+pub fn build_deep_sea(size: usize, discount_factor: f64, seed: u64) -> anyhow::Result<IndexMdp> {
+    if size == 0 {
+        anyhow::bail!("deep sea needs at least one row");
+    }
+    let mut rng = ChaCha20Rng::seed_from_u64(seed);
+    let index = |row: usize, col: usize| IndexState(row * size + col);
+    let move_cost = 0.01 / size as f64;
+    let mut mdp = IndexMdp::new(discount_factor, index(0, 0));
+
+    for row in 0..size {
+        for col in 0..size {
+            let state = index(row, col);
+            let right_col = (col + 1).min(size - 1);
+            let left_col = col.saturating_sub(1);
+            let goal_reward = |next_col: usize| {
+                if row + 1 == size && next_col == size - 1 {
+                    1.0
+                } else {
+                    0.0
+                }
+            };
+
+            let mut actions = [LEFT, RIGHT];
+            actions.shuffle(&mut rng);
+            let [moves_left, moves_right] = actions;
+            mdp.add_transition_vector(
+                (state, moves_left),
+                vec![(1.0, index(row + 1, left_col), goal_reward(left_col))],
+            )?;
+            mdp.add_transition_vector(
+                (state, moves_right),
+                vec![(
+                    1.0,
+                    index(row + 1, right_col),
+                    goal_reward(right_col) - move_cost,
+                )],
+            )?;
+        }
+    }
+    (0..size).for_each(|col| mdp.add_terminal_state(index(size, col)));
+
+    Ok(mdp)
+}
@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+
+use crate::mdp::{MapMdp, Probability};
+
+// the gambler's problem (Sutton & Barto, example 4.3). A gambler with capital 1..goal - 1 stakes
+// a whole number of dollars on a coin flip, heads with probability p_heads doubles the stake and
+// tails loses it. Reaching the goal pays 1 and ends the episode, so without discounting the value
+// of a state is the probability of winning. Capital 0 and goal are terminal.
+// Many states have several optimal stakes, e.g. with p_heads < 0.5 and goal 100 both 1 and 49
+// are optimal with a capital of 51, see analysis::optimal_actions.
+
+// capital of the gambler
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Copy, Serialize, Deserialize)]
+pub struct GamblerState(pub usize);
+
+// stake, at least 1 and at most what is needed to reach the goal
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Copy, Serialize, Deserialize)]
+pub struct GamblerAction(pub usize);
+
+pub fn build_mdp(
+    goal: usize,
+    p_heads: Probability,
+) -> anyhow::Result<MapMdp<GamblerState, GamblerAction>> {
+    if goal < 2 {
+        anyhow::bail!("the goal needs to be at least 2");
+    }
+    if !(0.0..=1.0).contains(&p_heads) {
+        anyhow::bail!("p_heads {} is not a probability", p_heads);
+    }
+
+    let mut mdp = MapMdp::new(1.0, GamblerState(goal / 2));
+    for capital in 1..goal {
+        for stake in 1..=capital.min(goal - capital) {
+            let won = capital + stake;
+            let transitions = vec![
+                (
+                    p_heads,
+                    GamblerState(won),
+                    if won == goal { 1.0 } else { 0.0 },
+                ),
+                (1.0 - p_heads, GamblerState(capital - stake), 0.0),
+            ]
+            .into_iter()
+            .filter(|(prob, _, _)| *prob > 0.0)
+            .collect();
+            mdp.add_transition_vector((GamblerState(capital), GamblerAction(stake)), transitions)?;
+        }
+    }
+    mdp.add_terminal_state(GamblerState(0));
+    mdp.add_terminal_state(GamblerState(goal));
+
+    Ok(mdp)
+}
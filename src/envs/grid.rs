@@ -0,0 +1,333 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::mdp::{MapMdp, Probability, Reward};
+
+// grid worlds described by ASCII maps, one character per cell:
+//   .  regular cell
+//   S  start, exactly one per map
+//   G  goal, terminal
+//   #  wall, not a state, moves into it stay in place
+//   C  cliff, terminal
+// any other character has to be registered with with_reward_cell. Entering a cell pays its reward,
+// the step reward for regular and start cells. Moves off the map stay in place.
+
+// (row, col), row 0 is the top of the map
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Copy, Serialize, Deserialize)]
+pub struct GridState(pub usize, pub usize);
+
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Copy, Serialize, Deserialize)]
+pub enum GridAction {
+    Up = 0,
+    Down = 1,
+    Left = 2,
+    Right = 3,
+    UpLeft = 4,
+    UpRight = 5,
+    DownLeft = 6,
+    DownRight = 7,
+}
+
+impl GridAction {
+    // (row, col) offset of a single move
+    pub fn offset(&self) -> (isize, isize) {
+        match self {
+            GridAction::Up => (-1, 0),
+            GridAction::Down => (1, 0),
+            GridAction::Left => (0, -1),
+            GridAction::Right => (0, 1),
+            GridAction::UpLeft => (-1, -1),
+            GridAction::UpRight => (-1, 1),
+            GridAction::DownLeft => (1, -1),
+            GridAction::DownRight => (1, 1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Connectivity {
+    Four,
+    // adds the diagonal king's moves
+    Eight,
+}
+
+impl Connectivity {
+    pub fn actions(&self) -> Vec<GridAction> {
+        let mut actions = vec![
+            GridAction::Up,
+            GridAction::Down,
+            GridAction::Left,
+            GridAction::Right,
+        ];
+        if *self == Connectivity::Eight {
+            actions.extend([
+                GridAction::UpLeft,
+                GridAction::UpRight,
+                GridAction::DownLeft,
+                GridAction::DownRight,
+            ]);
+        }
+        actions
+    }
+}
+
+// moves across a slippery cell, perpendicular to the slip direction, end in the neighbouring cell
+// in slip direction with the given probability, e.g. sliding off a ledge
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Slip {
+    pub probability: Probability,
+    pub direction: GridAction,
+}
+
+// pushes the agent after its move from this cell, by a number of cells drawn from strengths
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Wind {
+    pub direction: GridAction,
+    pub strengths: Vec<(Probability, usize)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Cell {
+    Regular,
+    Start,
+    Goal,
+    Wall,
+    Cliff,
+    Reward(Reward),
+}
+
+#[derive(Debug, Clone)]
+pub struct GridConfig {
+    map: Vec<Vec<char>>,
+    pub step_reward: Reward,
+    pub cliff_reward: Reward,
+    pub goal_reward: Reward,
+    pub discount_factor: f64,
+    pub connectivity: Connectivity,
+    reward_cells: BTreeMap<char, Reward>,
+    slips: BTreeMap<GridState, Slip>,
+    winds: BTreeMap<GridState, Wind>,
+}
+
+impl GridConfig {
+    pub fn parse(map: &str) -> anyhow::Result<Self> {
+        let map: Vec<Vec<char>> = map
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .map(|line| line.chars().collect())
+            .collect();
+        let Some(cols) = map.first().map(|row| row.len()) else {
+            anyhow::bail!("the map is empty");
+        };
+        if let Some(row) = map.iter().position(|row| row.len() != cols) {
+            anyhow::bail!(
+                "row {} has {} cells instead of {}",
+                row,
+                map[row].len(),
+                cols
+            );
+        }
+
+        Ok(Self {
+            map,
+            step_reward: -1.0,
+            cliff_reward: -100.0,
+            goal_reward: 0.0,
+            discount_factor: 1.0,
+            connectivity: Connectivity::Four,
+            reward_cells: BTreeMap::new(),
+            slips: BTreeMap::new(),
+            winds: BTreeMap::new(),
+        })
+    }
+
+    pub fn rows(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn cols(&self) -> usize {
+        self.map[0].len()
+    }
+
+    pub fn with_step_reward(mut self, reward: Reward) -> Self {
+        self.step_reward = reward;
+        self
+    }
+
+    pub fn with_cliff_reward(mut self, reward: Reward) -> Self {
+        self.cliff_reward = reward;
+        self
+    }
+
+    pub fn with_goal_reward(mut self, reward: Reward) -> Self {
+        self.goal_reward = reward;
+        self
+    }
+
+    pub fn with_discount_factor(mut self, discount_factor: f64) -> Self {
+        self.discount_factor = discount_factor;
+        self
+    }
+
+    pub fn with_connectivity(mut self, connectivity: Connectivity) -> Self {
+        self.connectivity = connectivity;
+        self
+    }
+
+    // cells marked with symbol are regular cells that pay reward when entered
+    pub fn with_reward_cell(mut self, symbol: char, reward: Reward) -> Self {
+        self.reward_cells.insert(symbol, reward);
+        self
+    }
+
+    pub fn with_slip(
+        mut self,
+        state: GridState,
+        probability: Probability,
+        direction: GridAction,
+    ) -> Self {
+        self.slips.insert(
+            state,
+            Slip {
+                probability,
+                direction,
+            },
+        );
+        self
+    }
+
+    pub fn with_wind(mut self, state: GridState, wind: Wind) -> Self {
+        self.winds.insert(state, wind);
+        self
+    }
+
+    // the same wind in every cell of the column
+    pub fn with_column_wind(mut self, col: usize, wind: Wind) -> Self {
+        for row in 0..self.rows() {
+            self.winds.insert(GridState(row, col), wind.clone());
+        }
+        self
+    }
+
+    pub fn build(&self) -> anyhow::Result<MapMdp<GridState, GridAction>> {
+        let cells = self.cells()?;
+        let states: Vec<GridState> = cells
+            .keys()
+            .filter(|state| cells[state] != Cell::Wall)
+            .copied()
+            .collect();
+        let starts: Vec<GridState> = states
+            .iter()
+            .filter(|state| cells[state] == Cell::Start)
+            .copied()
+            .collect();
+        let [start] = starts[..] else {
+            anyhow::bail!("the map needs exactly one start, found {}", starts.len());
+        };
+
+        let mut mdp = MapMdp::new(self.discount_factor, start);
+        for state in states.iter() {
+            for action in self.connectivity.actions() {
+                let transitions = self
+                    .outcomes(&cells, *state, action)
+                    .into_iter()
+                    .map(|(next_state, prob)| (prob, next_state, self.reward(cells[&next_state])))
+                    .collect();
+                mdp.add_transition_vector((*state, action), transitions)?;
+            }
+            if matches!(cells[state], Cell::Goal | Cell::Cliff) {
+                mdp.add_terminal_state(*state);
+            }
+        }
+
+        Ok(mdp)
+    }
+
+    fn cells(&self) -> anyhow::Result<BTreeMap<GridState, Cell>> {
+        let mut cells = BTreeMap::new();
+        for (row, line) in self.map.iter().enumerate() {
+            for (col, symbol) in line.iter().enumerate() {
+                let cell = match symbol {
+                    '.' => Cell::Regular,
+                    'S' => Cell::Start,
+                    'G' => Cell::Goal,
+                    '#' => Cell::Wall,
+                    'C' => Cell::Cliff,
+                    other => match self.reward_cells.get(other) {
+                        Some(reward) => Cell::Reward(*reward),
+                        None => anyhow::bail!("unknown cell {:?} at ({}, {})", other, row, col),
+                    },
+                };
+                cells.insert(GridState(row, col), cell);
+            }
+        }
+        Ok(cells)
+    }
+
+    fn reward(&self, cell: Cell) -> Reward {
+        match cell {
+            Cell::Regular | Cell::Start | Cell::Wall => self.step_reward,
+            Cell::Goal => self.goal_reward,
+            Cell::Cliff => self.cliff_reward,
+            Cell::Reward(reward) => reward,
+        }
+    }
+
+    // the cell one move away, or the same cell for walls and the border
+    fn step(
+        &self,
+        cells: &BTreeMap<GridState, Cell>,
+        state: GridState,
+        action: GridAction,
+    ) -> GridState {
+        let (d_row, d_col) = action.offset();
+        let row = state.0.checked_add_signed(d_row);
+        let col = state.1.checked_add_signed(d_col);
+        match (row, col) {
+            (Some(row), Some(col)) => match cells.get(&GridState(row, col)) {
+                Some(Cell::Wall) | None => state,
+                Some(_) => GridState(row, col),
+            },
+            _ => state,
+        }
+    }
+
+    // distribution of next states, slips first and the wind of the cell the move started in after
+    fn outcomes(
+        &self,
+        cells: &BTreeMap<GridState, Cell>,
+        state: GridState,
+        action: GridAction,
+    ) -> Vec<(GridState, Probability)> {
+        let mut moves = vec![(self.step(cells, state, action), 1.0)];
+        if let Some(slip) = self.slips.get(&state) {
+            let (a_row, a_col) = action.offset();
+            let (s_row, s_col) = slip.direction.offset();
+            if a_row * s_row + a_col * s_col == 0 {
+                moves = vec![
+                    (moves[0].0, 1.0 - slip.probability),
+                    (self.step(cells, state, slip.direction), slip.probability),
+                ];
+            }
+        }
+
+        let mut outcomes: BTreeMap<GridState, Probability> = BTreeMap::new();
+        for (next_state, prob) in moves {
+            match self.winds.get(&state) {
+                Some(wind) => wind.strengths.iter().for_each(|(wind_prob, strength)| {
+                    let pushed = (0..*strength).fold(next_state, |pushed, _| {
+                        self.step(cells, pushed, wind.direction)
+                    });
+                    *outcomes.entry(pushed).or_default() += prob * wind_prob;
+                }),
+                None => *outcomes.entry(next_state).or_default() += prob,
+            }
+        }
+        outcomes
+            .into_iter()
+            .filter(|(_, prob)| *prob > 0.0)
+            .collect()
+    }
+}
@@ -1,226 +1,190 @@
-#![allow(unused_variables)]
-#![allow(unused_mut)]
 use std::collections::BTreeMap;
 
-use crate::mdp::{self, IndexState, Probability, Reward, Transition};
-
-enum IntersectionAction {
-    Stay = 0,
-    Change = 1,
-}
-
-#[derive(Debug, Copy, Clone)]
-enum TrafficSignalState {
-    NorthSouthOpen = 0,
-    EastWestOpen = 1,
-}
-
-pub fn build_mdp(max_cars: isize) {
+use crate::{
+    envs::my_intersection::{IntersectionState, LightAction, LightState, MyIntersectionMdp},
+    mdp::{GenericMdp, IndexAction, IndexMdp, IndexState, Probability},
+};
+
+// explicit model of MyIntersectionMdp, so the signal control problem can be solved exactly. Every
+// step a car arrives on each closed road with its arrival probability and on an open road a car
+// leaves unless one arrives at the same time.
+
+const LIGHT_STATES: [LightState; 4] = [
+    LightState::NorthSouthOpen,
+    LightState::EastWestOpen,
+    LightState::ChangingToNS,
+    LightState::ChangingToEW,
+];
+
+// takes the arrival probabilities, road capacity and discount factor from the sampled environment
+pub fn build_mdp(env: &MyIntersectionMdp) -> anyhow::Result<IndexMdp> {
+    let (new_car_prob_ns, new_car_prob_ew) = env.new_car_probs();
+    let max_cars = env.max_cars();
     if max_cars < 1 {
-        panic!("max_cars needs to be at least 1");
+        anyhow::bail!("max_cars needs to be at least 1");
     }
-    let mut transitions: BTreeMap<(IndexState, mdp::IndexAction), Vec<Transition>> =
-        BTreeMap::new();
-
-    let mut states = vec![];
-
-    let car_combinations = (max_cars + 1).pow(2);
-    for ns_cars in 0..=max_cars {
-        for ew_cars in 0..=max_cars {
-            let ns_state = build_state(
-                TrafficSignalState::NorthSouthOpen,
-                ns_cars,
-                ew_cars,
-                max_cars,
-            );
-            let ew_state =
-                build_state(TrafficSignalState::EastWestOpen, ns_cars, ew_cars, max_cars);
-
-            states.push(ns_state);
-            println!(
-                "State: {:?}, ns_cars: {:?}, ew_cars: {:?}",
-                ns_state, ns_cars, ew_cars
-            );
-            reconstruct_state(ns_state, max_cars);
-            reconstruct_state(ew_state, max_cars);
-            states.push(ew_state);
+    let initial_state = IntersectionState {
+        light_state: LightState::NorthSouthOpen,
+        ns_cars: 0,
+        ew_cars: 0,
+    };
+    let mut mdp = IndexMdp::new(
+        env.get_discount_factor(),
+        build_state(initial_state, max_cars),
+    );
+
+    for light_state in LIGHT_STATES {
+        for ns_cars in 0..=max_cars {
+            for ew_cars in 0..=max_cars {
+                let state = IntersectionState {
+                    light_state,
+                    ns_cars,
+                    ew_cars,
+                };
+                for action in possible_actions(light_state) {
+                    let transitions = build_transitions(
+                        state,
+                        action,
+                        new_car_prob_ns,
+                        new_car_prob_ew,
+                        max_cars,
+                    );
+                    mdp.add_transition_vector(
+                        (build_state(state, max_cars), build_action(action)),
+                        transitions,
+                    )?;
+                }
+            }
         }
     }
 
-    println!("{:?}", states);
-    println!("{:?}", f(3, 3));
-    u(3, 3);
+    Ok(mdp)
 }
 
-type IntersectionState = (TrafficSignalState, isize, isize);
-
-fn build_state(
-    traffic_signal_state: TrafficSignalState,
-    ns_cars: isize,
-    ew_cars: isize,
-    max_cars: isize,
-) -> IndexState {
-    match traffic_signal_state {
-        TrafficSignalState::NorthSouthOpen => {
-            IndexState((ns_cars * (max_cars + 1) + ew_cars) as usize)
-        }
-        TrafficSignalState::EastWestOpen => {
-            IndexState((ns_cars * (max_cars + 1) + ew_cars + (max_cars + 1).pow(2)) as usize)
-        }
-    }
+pub fn build_state(state: IntersectionState, max_cars: usize) -> IndexState {
+    let combinations = (max_cars + 1).pow(2);
+    IndexState(
+        state.light_state as usize * combinations + state.ns_cars * (max_cars + 1) + state.ew_cars,
+    )
 }
 
-fn reconstruct_state(state: IndexState, max_cars: isize) -> IntersectionState {
-    let mut index = state.0 as isize;
-
+pub fn reconstruct_state(state: IndexState, max_cars: usize) -> IntersectionState {
     let combinations = (max_cars + 1).pow(2);
-    let intersection_state = if index < combinations {
-        TrafficSignalState::NorthSouthOpen
-    } else {
-        index -= combinations;
-        TrafficSignalState::EastWestOpen
-    };
-
-    let ew_cars = index % (max_cars + 1);
-    let ns_cars = index / (max_cars + 1);
-
-    println!(
-        "function, ns_cars: {:?}, ew_cars: {:?}, intersection_state: {:?}",
-        ns_cars, ew_cars, intersection_state
-    );
-
-    (intersection_state, ns_cars, ew_cars)
-}
+    let index = state.0 % combinations;
 
-fn calculate_reward(action: IntersectionAction, intersection_state: IntersectionState) -> Reward {
-    let (state, ns_cars, ew_cars) = intersection_state;
-    match (action, state) {
-        (IntersectionAction::Change, TrafficSignalState::NorthSouthOpen) => {
-            if ew_cars >= 2 * ns_cars {
-                1.0
-            } else {
-                -1.0
-            }
-        }
-        (IntersectionAction::Change, TrafficSignalState::EastWestOpen) => {
-            if ns_cars >= 2 * ew_cars {
-                1.0
-            } else {
-                -1.0
-            }
-        }
-        (IntersectionAction::Stay, TrafficSignalState::NorthSouthOpen) => {
-            if ew_cars >= 2 * ns_cars {
-                0.0
-            } else {
-                1.0
-            }
-        }
-        (IntersectionAction::Stay, TrafficSignalState::EastWestOpen) => {
-            if ns_cars >= 2 * ew_cars {
-                0.0
-            } else {
-                1.0
-            }
-        }
+    IntersectionState {
+        light_state: LIGHT_STATES[state.0 / combinations],
+        ns_cars: index / (max_cars + 1),
+        ew_cars: index % (max_cars + 1),
     }
 }
 
-fn calc_change_prob(cars: isize, car_change: isize) -> Probability {
-    let new_cars = cars + car_change;
+pub fn build_action(action: LightAction) -> IndexAction {
+    IndexAction(action as usize)
+}
 
-    if new_cars == cars {
-        1.0 / 2.0_f64.sqrt()
-    } else if new_cars == cars + 1 || new_cars == cars - 1 {
-        let divisor = if cars == 0 { 1.0 } else { 2.0 };
-        (1.0 - (1.0 / 2.0_f64.sqrt())) * (1.0 / divisor)
-    } else {
-        0.0
+pub fn reconstruct_action(action: IndexAction) -> LightAction {
+    match action.0 {
+        0 => LightAction::Change,
+        1 => LightAction::Stay,
+        2 => LightAction::WaitForChange,
+        _ => panic!("invalid intersection action {:?}", action),
     }
 }
 
-fn calc_stay_prob_open_road(car_change: isize) -> Probability {
-    match car_change {
-        -2 => 3.0 / 16.0,
-        -1 | 0 => 9.0 / 32.0,
-        2 => 1.0 / 12.0,
-        1 => 1.0 / 6.0,
-        _ => 0.0,
-    }
+// q_map of the model in terms of MyIntersectionMdp, e.g. to run the exact optimum on it
+pub fn to_intersection_q_map(
+    q_map: &BTreeMap<(IndexState, IndexAction), f64>,
+    max_cars: usize,
+) -> BTreeMap<(IntersectionState, LightAction), f64> {
+    q_map
+        .iter()
+        .map(|((state, action), q)| {
+            (
+                (
+                    reconstruct_state(*state, max_cars),
+                    reconstruct_action(*action),
+                ),
+                *q,
+            )
+        })
+        .collect()
 }
 
-fn calc_stay_prob_closed_road(car_change: isize) -> Probability {
-    match car_change {
-        -2 => 1.0 / 60.0,
-        -1 => 1.0 / 30.0,
-        2 => 19.0 / 80.0,
-        1 | 0 => 57.0 / 160.0,
-        _ => 0.0,
+fn possible_actions(light_state: LightState) -> Vec<LightAction> {
+    match light_state {
+        LightState::NorthSouthOpen | LightState::EastWestOpen => {
+            vec![LightAction::Change, LightAction::Stay]
+        }
+        LightState::ChangingToNS | LightState::ChangingToEW => vec![LightAction::WaitForChange],
     }
 }
 
-fn f(ns_cars: isize, ew_cars: isize) {
-    let mut total_prob: f64 = 0.0;
-    for i in -2..=2 {
-        for j in -2..=2 {
-            total_prob += calc_change_prob(ns_cars, i) * calc_change_prob(ew_cars, j);
-        }
+// distribution of the next number of cars on a road with an open light
+fn open_road(cars: usize, new_car_prob: f64) -> Vec<(Probability, usize)> {
+    if cars == 0 {
+        vec![(1.0, 0)]
+    } else {
+        vec![(1.0 - new_car_prob, cars - 1), (new_car_prob, cars)]
     }
-    println!("total_prob: {total_prob}");
 }
 
-fn u(ns_cars: isize, ew_cars: isize) {
-    let mut total_prob: f64 = 0.0;
-    for i in -2..=2 {
-        for j in -2..=2 {
-            total_prob += calc_stay_prob_open_road(i) * calc_stay_prob_closed_road(j);
-        }
+fn closed_road(cars: usize, new_car_prob: f64, max_cars: usize) -> Vec<(Probability, usize)> {
+    if cars == max_cars {
+        vec![(1.0, max_cars)]
+    } else {
+        vec![(1.0 - new_car_prob, cars), (new_car_prob, cars + 1)]
     }
-    println!("total_prob: {total_prob}");
 }
 
 fn build_transitions(
-    intersection_state: IntersectionState,
-    intersection_action: IntersectionAction,
-    max_cars: isize,
-) -> Vec<Transition> {
-    let (traffic_signal_state, ns_cars, ew_cars) = intersection_state;
-    let mut transitions = vec![];
+    state: IntersectionState,
+    action: LightAction,
+    new_car_prob_ns: f64,
+    new_car_prob_ew: f64,
+    max_cars: usize,
+) -> Vec<(Probability, IndexState, f64)> {
+    let light_state = match (action, state.light_state) {
+        (LightAction::Stay, light_state) => light_state,
+        (LightAction::Change, LightState::NorthSouthOpen) => LightState::ChangingToEW,
+        (LightAction::Change, LightState::EastWestOpen) => LightState::ChangingToNS,
+        (LightAction::WaitForChange, LightState::ChangingToNS) => LightState::NorthSouthOpen,
+        (LightAction::WaitForChange, LightState::ChangingToEW) => LightState::EastWestOpen,
+        _ => panic!("{:?} not possible in {:?}", action, state.light_state),
+    };
 
-    let new_traffic_signal_state = match intersection_action {
-        IntersectionAction::Stay => traffic_signal_state,
-        IntersectionAction::Change => match traffic_signal_state {
-            TrafficSignalState::NorthSouthOpen => TrafficSignalState::EastWestOpen,
-            TrafficSignalState::EastWestOpen => TrafficSignalState::NorthSouthOpen,
-        },
+    let (ns_roads, ew_roads) = match light_state {
+        LightState::NorthSouthOpen => (
+            open_road(state.ns_cars, new_car_prob_ns),
+            closed_road(state.ew_cars, new_car_prob_ew, max_cars),
+        ),
+        LightState::EastWestOpen => (
+            closed_road(state.ns_cars, new_car_prob_ns, max_cars),
+            open_road(state.ew_cars, new_car_prob_ew),
+        ),
+        LightState::ChangingToNS | LightState::ChangingToEW => (
+            closed_road(state.ns_cars, new_car_prob_ns, max_cars),
+            closed_road(state.ew_cars, new_car_prob_ew, max_cars),
+        ),
     };
 
-    for ns_car_change in -2..=2 {
-        for ew_car_change in -2..=2 {
-            let new_state = build_state(
-                new_traffic_signal_state,
-                ns_cars + ns_car_change,
-                ew_cars + ew_car_change,
-                max_cars,
-            );
-            let prob = match intersection_action {
-                IntersectionAction::Stay => match traffic_signal_state {
-                    TrafficSignalState::NorthSouthOpen => {
-                        calc_stay_prob_open_road(ns_car_change)
-                            * calc_stay_prob_closed_road(ew_car_change)
-                    }
-                    TrafficSignalState::EastWestOpen => {
-                        calc_stay_prob_open_road(ew_car_change)
-                            * calc_stay_prob_closed_road(ns_car_change)
-                    }
-                },
-                IntersectionAction::Change => {
-                    calc_change_prob(ns_cars, ns_car_change)
-                        * calc_change_prob(ew_cars, ew_car_change)
-                }
-            };
+    let mut transitions = vec![];
+    for (ns_prob, ns_cars) in ns_roads.iter() {
+        for (ew_prob, ew_cars) in ew_roads.iter() {
+            let prob = ns_prob * ew_prob;
+            if prob > 0.0 {
+                let next_state = IntersectionState {
+                    light_state,
+                    ns_cars: *ns_cars,
+                    ew_cars: *ew_cars,
+                };
+                transitions.push((
+                    prob,
+                    build_state(next_state, max_cars),
+                    -((ns_cars + ew_cars) as f64),
+                ));
+            }
         }
     }
-
     transitions
 }
@@ -1,7 +1,12 @@
 pub mod blackjack;
+pub mod car_rental;
 pub mod cliff_walking;
+pub mod exploration;
+pub mod gamblers_problem;
+pub mod grid;
 pub mod grid_world;
 pub mod intersection;
 pub mod my_intersection;
 pub mod slippery_cliff_walking;
 pub mod traffic;
+pub mod windy_grid_world;
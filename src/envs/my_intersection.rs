@@ -1,17 +1,18 @@
 #![allow(unused_variables)]
 
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
 use crate::mdp::GenericMdp;
 
-#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Copy)]
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Copy, Serialize, Deserialize)]
 pub struct IntersectionState {
     pub light_state: LightState,
     pub ns_cars: usize,
     pub ew_cars: usize,
 }
 
-#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Copy)]
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Copy, Serialize, Deserialize)]
 pub enum LightState {
     NorthSouthOpen = 0,
     EastWestOpen = 1,
@@ -19,7 +20,7 @@ pub enum LightState {
     ChangingToEW = 3,
 }
 
-#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Copy)]
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Copy, Serialize, Deserialize)]
 pub enum LightAction {
     Change = 0,
     Stay = 1,
@@ -85,6 +86,15 @@ impl MyIntersectionMdp {
         }
     }
 
+    // arrival probabilities of the north south and the east west road
+    pub fn new_car_probs(&self) -> (f64, f64) {
+        (self.new_car_prob_ns, self.new_car_prob_ew)
+    }
+
+    pub fn max_cars(&self) -> usize {
+        self.max_cars
+    }
+
     fn open_road_transition<R: Rng>(&self, old_cars: usize, new_prob: f64, rng: &mut R) -> usize {
         if old_cars == 0 {
             0
@@ -1,36 +1,156 @@
-#![allow(unused_variables)]
-
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
 use crate::mdp::GenericMdp;
 
-const NUM_FLOWS: usize = 4;
-const NUM_LIGHTS: usize = 2;
-const MAX_CARS: usize = 4;
-
-#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Copy)]
-struct State {
-    vehicles: [usize; NUM_FLOWS],
-    lights: [Light; NUM_LIGHTS],
+pub const NUM_FLOWS: usize = 4;
+pub const NUM_LIGHTS: usize = 2;
+pub const MAX_CARS: usize = 4;
+
+// single intersection with two signal groups, flows 0 and 1 (north and south) wait at light 0,
+// flows 2 and 3 (east and west) at light 1. Only one light is ever not Red, it cycles
+// Green -> Yellow -> AllRed -> Red and the other light turns Green, so every change passes a yellow
+// and an all-red clearance step in which no car moves.
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Copy, Serialize, Deserialize)]
+pub struct State {
+    pub vehicles: [usize; NUM_FLOWS],
+    pub lights: [Light; NUM_LIGHTS],
 }
 
-#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Copy)]
-enum Light {
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Copy, Serialize, Deserialize)]
+pub enum Light {
     Green = 0,
     Yellow = 1,
     AllRed = 2,
     Red = 3,
 }
 
-type Action = Light;
+// the next phase of the active light, Green keeps the light green and Yellow starts a change
+pub type Action = Light;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TrafficReward {
+    // minus the number of waiting cars after the step
+    QueueLength,
+    // number of cars that left the intersection during the step
+    Throughput,
+}
 
 pub struct TrafficMdp {
     arrival_probs: [f64; NUM_FLOWS],
+    max_cars: usize,
+    reward: TrafficReward,
+    discount_factor: f64,
+    states_actions: Vec<(State, Action)>,
 }
 
 impl TrafficMdp {
     pub fn new(arrival_probs: [f64; NUM_FLOWS]) -> Self {
-        Self { arrival_probs }
+        Self {
+            arrival_probs,
+            max_cars: MAX_CARS,
+            reward: TrafficReward::QueueLength,
+            discount_factor: 0.8,
+            states_actions: Self::enumerate_states_actions(MAX_CARS),
+        }
+    }
+
+    pub fn with_max_cars(mut self, max_cars: usize) -> Self {
+        self.max_cars = max_cars;
+        self.states_actions = Self::enumerate_states_actions(max_cars);
+        self
+    }
+
+    pub fn with_reward(mut self, reward: TrafficReward) -> Self {
+        self.reward = reward;
+        self
+    }
+
+    pub fn with_discount_factor(mut self, discount_factor: f64) -> Self {
+        self.discount_factor = discount_factor;
+        self
+    }
+
+    // the six phases of the signal cycle
+    pub fn light_phases() -> [[Light; NUM_LIGHTS]; 6] {
+        [
+            [Light::Green, Light::Red],
+            [Light::Yellow, Light::Red],
+            [Light::AllRed, Light::Red],
+            [Light::Red, Light::Green],
+            [Light::Red, Light::Yellow],
+            [Light::Red, Light::AllRed],
+        ]
+    }
+
+    fn enumerate_states_actions(max_cars: usize) -> Vec<(State, Action)> {
+        let mut queues = vec![[0; NUM_FLOWS]];
+        for flow in 0..NUM_FLOWS {
+            queues = queues
+                .into_iter()
+                .flat_map(|vehicles| {
+                    (0..=max_cars).map(move |cars| {
+                        let mut vehicles = vehicles;
+                        vehicles[flow] = cars;
+                        vehicles
+                    })
+                })
+                .collect();
+        }
+
+        let mut states_actions = vec![];
+        for lights in Self::light_phases() {
+            for vehicles in queues.iter() {
+                Self::phase_actions(lights).into_iter().for_each(|action| {
+                    states_actions.push((
+                        State {
+                            vehicles: *vehicles,
+                            lights,
+                        },
+                        action,
+                    ))
+                });
+            }
+        }
+        states_actions
+    }
+
+    // the light that is not Red
+    fn active_light(lights: [Light; NUM_LIGHTS]) -> usize {
+        lights
+            .iter()
+            .position(|light| *light != Light::Red)
+            .expect("one light is not red")
+    }
+
+    fn phase_actions(lights: [Light; NUM_LIGHTS]) -> Vec<Action> {
+        match lights[Self::active_light(lights)] {
+            Light::Green => vec![Light::Green, Light::Yellow],
+            Light::Yellow => vec![Light::AllRed],
+            Light::AllRed => vec![Light::Red],
+            Light::Red => unreachable!(),
+        }
+    }
+
+    fn next_lights(lights: [Light; NUM_LIGHTS], action: Action) -> [Light; NUM_LIGHTS] {
+        if !Self::phase_actions(lights).contains(&action) {
+            panic!(
+                "Unreachable state: {:?} not allowed with lights {:?}",
+                action, lights
+            );
+        }
+        let active = Self::active_light(lights);
+        let mut next = lights;
+        next[active] = action;
+        if action == Light::Red {
+            // the all-red interval is over, the other light takes over
+            next[1 - active] = Light::Green;
+        }
+        next
+    }
+
+    fn light_of_flow(flow: usize) -> usize {
+        flow * NUM_LIGHTS / NUM_FLOWS
     }
 
     fn green_transition<R: Rng>(old_cars: usize, new_prob: f64, rng: &mut R) -> usize {
@@ -43,9 +163,14 @@ impl TrafficMdp {
         }
     }
 
-    fn red_transition<R: Rng>(old_cars: usize, new_prob: f64, rng: &mut R) -> usize {
-        if old_cars == MAX_CARS {
-            MAX_CARS
+    fn red_transition<R: Rng>(
+        old_cars: usize,
+        new_prob: f64,
+        max_cars: usize,
+        rng: &mut R,
+    ) -> usize {
+        if old_cars == max_cars {
+            max_cars
         } else if rng.gen_range(0.0..1.0) < (1.0 - new_prob) {
             old_cars
         } else {
@@ -61,33 +186,51 @@ impl GenericMdp<State, Action> for TrafficMdp {
         rng: &mut R,
     ) -> (State, crate::mdp::Reward) {
         let (state, action) = state_action;
-        for flow in 0..NUM_FLOWS {
-            let old_cars = state.vehicles[flow];
-            let new_cars = match action {
-                Light::Green => Self::green_transition(old_cars, self.arrival_probs[flow], rng),
-                Light::Yellow | Light::AllRed | Light::Red => todo!(),
+        let lights = Self::next_lights(state.lights, action);
+
+        let mut vehicles = state.vehicles;
+        let mut departed = 0;
+        for (flow, cars) in vehicles.iter_mut().enumerate() {
+            // a car arriving at an empty green queue passes without waiting
+            *cars = match lights[Self::light_of_flow(flow)] {
+                Light::Green => {
+                    departed += usize::from(*cars > 0);
+                    Self::green_transition(*cars, self.arrival_probs[flow], rng)
+                }
+                Light::Yellow | Light::AllRed | Light::Red => {
+                    Self::red_transition(*cars, self.arrival_probs[flow], self.max_cars, rng)
+                }
             };
         }
-        todo!()
+
+        let reward = match self.reward {
+            TrafficReward::QueueLength => -(vehicles.iter().sum::<usize>() as f64),
+            TrafficReward::Throughput => departed as f64,
+        };
+
+        (State { vehicles, lights }, reward)
     }
 
     fn get_possible_actions(&self, current_state: State) -> Vec<Action> {
-        todo!()
+        Self::phase_actions(current_state.lights)
     }
 
     fn get_all_state_actions(&self) -> &[(State, Light)] {
-        todo!()
+        &self.states_actions
     }
 
-    fn is_terminal(&self, state: State) -> bool {
-        todo!()
+    fn is_terminal(&self, _state: State) -> bool {
+        false
     }
 
-    fn get_initial_state<R: rand::Rng>(&self, rng: &mut R) -> State {
-        todo!()
+    fn get_initial_state<R: rand::Rng>(&self, _rng: &mut R) -> State {
+        State {
+            vehicles: [0; NUM_FLOWS],
+            lights: [Light::Green, Light::Red],
+        }
     }
 
     fn get_discount_factor(&self) -> f64 {
-        todo!()
+        self.discount_factor
     }
 }
@@ -0,0 +1,62 @@
+use crate::mdp::MapMdp;
+
+use super::grid::{Connectivity, GridAction, GridConfig, GridState, Wind};
+
+// windy gridworld of Sutton & Barto, example 6.5. The wind of the column the agent leaves pushes it
+// up, every step costs 1 until the goal is reached. Exercise 6.9 adds king's moves, exercise 6.10
+// a stochastic wind that is one weaker or stronger a third of the time each.
+// optimal return: -15 with 4 moves, -7 with king's moves, with stochastic wind -20.628 and -11.820
+
+pub const WIND: [usize; 10] = [0, 0, 0, 1, 1, 1, 2, 2, 1, 0];
+
+const MAP: &str = include_str!("maps/windy_grid_world.txt");
+
+pub fn build_mdp(
+    connectivity: Connectivity,
+    stochastic: bool,
+) -> anyhow::Result<MapMdp<GridState, GridAction>> {
+    build_mdp_with_layout(MAP, &WIND, connectivity, stochastic)
+}
+
+// any map, wind holds the upward wind strength of every column
+pub fn build_mdp_with_layout(
+    map: &str,
+    wind: &[usize],
+    connectivity: Connectivity,
+    stochastic: bool,
+) -> anyhow::Result<MapMdp<GridState, GridAction>> {
+    let config = GridConfig::parse(map)?
+        .with_step_reward(-1.0)
+        .with_goal_reward(-1.0)
+        .with_connectivity(connectivity);
+    if wind.len() != config.cols() {
+        anyhow::bail!(
+            "{} wind strengths for a map with {} columns",
+            wind.len(),
+            config.cols()
+        );
+    }
+
+    wind.iter()
+        .enumerate()
+        .filter(|(_, strength)| **strength > 0)
+        .fold(config, |config, (col, strength)| {
+            let strengths = if stochastic {
+                vec![
+                    (1.0 / 3.0, strength - 1),
+                    (1.0 / 3.0, *strength),
+                    (1.0 / 3.0, strength + 1),
+                ]
+            } else {
+                vec![(1.0, *strength)]
+            };
+            config.with_column_wind(
+                col,
+                Wind {
+                    direction: GridAction::Up,
+                    strengths,
+                },
+            )
+        })
+        .build()
+}
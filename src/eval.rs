@@ -1,102 +1,227 @@
-use crate::{mdp::GenericMdp, policies::random_policy};
+use crate::{
+    approximation::{FeatureExtractor, GreedyLinearPolicy},
+    mdp::GenericMdp,
+    policies::{EpsilonGreedyPolicy, GreedyPolicy, Policy, RandomPolicy},
+};
 use std::collections::BTreeMap;
 
-use rand_chacha::ChaCha20Rng;
+use rand::Rng;
 
-use crate::{
-    mdp::{GenericAction, GenericState},
-    policies::{epsilon_greedy_policy, greedy_policy},
-};
+use crate::mdp::{GenericAction, GenericState};
+
+// outcome of evaluating a policy, one entry per episode in the vectors
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EvalReport {
+    pub returns: Vec<f64>,
+    pub discounted_returns: Vec<f64>,
+    pub lengths: Vec<usize>,
+    pub mean: f64,
+    pub discounted_mean: f64,
+    pub std_dev: f64,
+    pub std_error: f64,
+    // episodes that ran into max_steps before reaching a terminal state
+    pub truncated_fraction: f64,
+}
+
+impl EvalReport {
+    fn new(
+        returns: Vec<f64>,
+        discounted_returns: Vec<f64>,
+        lengths: Vec<usize>,
+        truncated: usize,
+    ) -> Self {
+        let n = returns.len() as f64;
+        let mean = mean_of(&returns);
+        // sample standard deviation, 0 for a single episode
+        let std_dev = if returns.len() > 1 {
+            (returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt()
+        } else {
+            0.0
+        };
+
+        Self {
+            mean,
+            discounted_mean: mean_of(&discounted_returns),
+            std_dev,
+            std_error: if n > 0.0 { std_dev / n.sqrt() } else { 0.0 },
+            truncated_fraction: if n > 0.0 { truncated as f64 / n } else { 0.0 },
+            returns,
+            discounted_returns,
+            lengths,
+        }
+    }
+
+    pub fn mean_length(&self) -> f64 {
+        mean_of(&self.lengths.iter().map(|l| *l as f64).collect::<Vec<_>>())
+    }
+
+    // percentile of the undiscounted returns for p in [0, 100], interpolates between episodes
+    pub fn percentile(&self, p: f64) -> f64 {
+        percentile(&self.returns, p)
+    }
+
+    // percentile bootstrap confidence interval of the mean return, e.g. confidence 0.95. Takes its
+    // own rng so computing it does not change later evaluations.
+    pub fn bootstrap_ci<R: Rng>(
+        &self,
+        confidence: f64,
+        resamples: usize,
+        rng: &mut R,
+    ) -> (f64, f64) {
+        if self.returns.is_empty() {
+            return (0.0, 0.0);
+        }
+        let means: Vec<f64> = (0..resamples.max(1))
+            .map(|_| {
+                let total: f64 = (0..self.returns.len())
+                    .map(|_| self.returns[rng.gen_range(0..self.returns.len())])
+                    .sum();
+                total / self.returns.len() as f64
+            })
+            .collect();
+        let tail = (1.0 - confidence) / 2.0 * 100.0;
+        (percentile(&means, tail), percentile(&means, 100.0 - tail))
+    }
+}
+
+fn mean_of(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().sum::<f64>() / values.len() as f64
+}
 
-pub fn evaluate_epsilon_greedy_policy<M: GenericMdp<S, A>, S: GenericState, A: GenericAction>(
+fn percentile(values: &[f64], p: f64) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let rank = p.clamp(0.0, 100.0) / 100.0 * (sorted.len() - 1) as f64;
+    let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+}
+
+// rolls out any policy and collects the returns of every episode
+pub fn evaluate<M, S, A, P, R>(
     mdp: &M,
-    q_map: &BTreeMap<(S, A), f64>,
+    policy: &mut P,
     episodes: usize,
     max_steps: usize,
-    epsilon: f64,
-    rng: &mut ChaCha20Rng,
-) -> f64 {
-    let mut total_reward = 0.0;
+    rng: &mut R,
+) -> EvalReport
+where
+    M: GenericMdp<S, A>,
+    S: GenericState,
+    A: GenericAction,
+    P: Policy<S, A>,
+    R: Rng,
+{
+    let mut returns = Vec::with_capacity(episodes);
+    let mut discounted_returns = Vec::with_capacity(episodes);
+    let mut lengths = Vec::with_capacity(episodes);
+    let mut truncated = 0;
 
     for _episode in 1..=episodes {
+        policy.begin_episode();
         let mut current_state = mdp.get_initial_state(rng);
         let mut episode_reward = 0.0;
+        let mut discounted_reward = 0.0;
+        let mut discount = 1.0;
         let mut steps = 0;
 
         while !mdp.is_terminal(current_state) && steps < max_steps {
-            let selected_action = epsilon_greedy_policy(mdp, q_map, current_state, epsilon, rng);
+            let possible_actions = mdp.get_possible_actions(current_state);
+            let selected_action = policy.select_action(current_state, &possible_actions, rng);
             if let Some(selected_action) = selected_action {
                 let (next_state, reward) =
                     mdp.perform_action((current_state, selected_action), rng);
                 episode_reward += reward;
+                discounted_reward += discount * reward;
+                discount *= mdp.get_discount_factor();
                 current_state = next_state;
                 steps += 1;
             } else {
                 break;
             }
         }
-        total_reward += episode_reward;
+        if steps >= max_steps && !mdp.is_terminal(current_state) {
+            truncated += 1;
+        }
+        returns.push(episode_reward);
+        discounted_returns.push(discounted_reward);
+        lengths.push(steps);
     }
-    total_reward / episodes as f64
+    EvalReport::new(returns, discounted_returns, lengths, truncated)
 }
 
-pub fn evaluate_greedy_policy<M: GenericMdp<S, A>, S: GenericState, A: GenericAction>(
+// average undiscounted reward per episode of any policy
+pub fn evaluate_policy<
+    M: GenericMdp<S, A>,
+    S: GenericState,
+    A: GenericAction,
+    P: Policy<S, A>,
+    R: Rng,
+>(
     mdp: &M,
-    q_map: &BTreeMap<(S, A), f64>,
+    policy: &mut P,
     episodes: usize,
     max_steps: usize,
-    rng: &mut ChaCha20Rng,
+    rng: &mut R,
 ) -> f64 {
-    let mut total_reward = 0.0;
-
-    for _episode in 1..=episodes {
-        let mut current_state = mdp.get_initial_state(rng);
-        let mut episode_reward = 0.0;
-        let mut steps = 0;
+    evaluate(mdp, policy, episodes, max_steps, rng).mean
+}
 
-        while !mdp.is_terminal(current_state) && steps < max_steps {
-            let selected_action = greedy_policy(mdp, q_map, current_state, rng);
-            if let Some(selected_action) = selected_action {
-                let (next_state, reward) =
-                    mdp.perform_action((current_state, selected_action), rng);
-                episode_reward += reward;
-                current_state = next_state;
-                steps += 1;
-            } else {
-                break;
-            }
-        }
-        total_reward += episode_reward;
-    }
-    total_reward / episodes as f64
+pub fn evaluate_epsilon_greedy_policy<
+    M: GenericMdp<S, A>,
+    S: GenericState,
+    A: GenericAction,
+    R: Rng,
+>(
+    mdp: &M,
+    q_map: &BTreeMap<(S, A), f64>,
+    episodes: usize,
+    max_steps: usize,
+    epsilon: f64,
+    rng: &mut R,
+) -> f64 {
+    let mut policy = EpsilonGreedyPolicy::new(q_map, epsilon);
+    evaluate_policy(mdp, &mut policy, episodes, max_steps, rng)
 }
 
-pub fn evaluate_random_policy<M: GenericMdp<S, A>, S: GenericState, A: GenericAction>(
+pub fn evaluate_greedy_policy<M: GenericMdp<S, A>, S: GenericState, A: GenericAction, R: Rng>(
     mdp: &M,
+    q_map: &BTreeMap<(S, A), f64>,
     episodes: usize,
     max_steps: usize,
-    rng: &mut ChaCha20Rng,
+    rng: &mut R,
 ) -> f64 {
-    let mut total_reward = 0.0;
+    evaluate_policy(mdp, &mut GreedyPolicy::new(q_map), episodes, max_steps, rng)
+}
 
-    for _episode in 1..=episodes {
-        let mut current_state = mdp.get_initial_state(rng);
-        let mut episode_reward = 0.0;
-        let mut steps = 0;
+pub fn evaluate_random_policy<M: GenericMdp<S, A>, S: GenericState, A: GenericAction, R: Rng>(
+    mdp: &M,
+    episodes: usize,
+    max_steps: usize,
+    rng: &mut R,
+) -> f64 {
+    evaluate_policy(mdp, &mut RandomPolicy, episodes, max_steps, rng)
+}
 
-        while !mdp.is_terminal(current_state) && steps < max_steps {
-            let selected_action = random_policy(mdp, current_state, rng);
-            if let Some(selected_action) = selected_action {
-                let (next_state, reward) =
-                    mdp.perform_action((current_state, selected_action), rng);
-                episode_reward += reward;
-                current_state = next_state;
-                steps += 1;
-            } else {
-                break;
-            }
-        }
-        total_reward += episode_reward;
-    }
-    total_reward / episodes as f64
+pub fn evaluate_greedy_linear_policy<
+    M: GenericMdp<S, A>,
+    S: GenericState,
+    A: GenericAction,
+    F: FeatureExtractor<S, A>,
+    R: Rng,
+>(
+    mdp: &M,
+    weights: &[f64],
+    extractor: &F,
+    episodes: usize,
+    max_steps: usize,
+    rng: &mut R,
+) -> f64 {
+    let mut policy = GreedyLinearPolicy::new(weights, extractor);
+    evaluate_policy(mdp, &mut policy, episodes, max_steps, rng)
 }
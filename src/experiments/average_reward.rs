@@ -0,0 +1,60 @@
+use rand::SeedableRng;
+
+use crate::{
+    algorithms::{
+        differential_sarsa::DifferentialSarsa, q_learning::QLearning, r_learning::RLearning,
+        TabularAlgorithm,
+    },
+    envs::my_intersection::MyIntersectionMdp,
+    eval::evaluate_greedy_policy,
+    experiments::intersection::fixed_cycle,
+};
+
+// compares the average reward learners against discounted Q-learning on the never terminating
+// intersection, all policies are evaluated by their average reward per step
+pub fn run_experiment() {
+    let mdp = MyIntersectionMdp::new(0.6, 0.2, 10);
+
+    let alpha = 0.1;
+    let beta = 0.01;
+    let epsilon = 0.1;
+    let max_steps = 2000;
+    // one continuous run, the intersection never terminates
+    let train_steps = 200_000;
+    let eval_episodes = 10;
+    let eval_steps = 10000;
+
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+
+    let mut r_algo = RLearning::new(alpha, beta, epsilon, max_steps);
+    let q_map = r_algo.run_continuing(&mdp, train_steps, &mut rng);
+    let avg_reward = evaluate_greedy_policy(&mdp, &q_map, eval_episodes, eval_steps, &mut rng);
+    println!("R-learning rho: {}", r_algo.get_rho());
+    println!(
+        "R-learning average reward per step: {}",
+        avg_reward / eval_steps as f64
+    );
+
+    let mut sarsa_algo = DifferentialSarsa::new(alpha, beta, epsilon, max_steps);
+    let q_map = sarsa_algo.run_continuing(&mdp, train_steps, &mut rng);
+    let avg_reward = evaluate_greedy_policy(&mdp, &q_map, eval_episodes, eval_steps, &mut rng);
+    println!("Differential SARSA rho: {}", sarsa_algo.get_rho());
+    println!(
+        "Differential SARSA average reward per step: {}",
+        avg_reward / eval_steps as f64
+    );
+
+    let mut q_algo = QLearning::new(alpha, epsilon, max_steps);
+    let q_map = q_algo.run_continuing(&mdp, train_steps, &mut rng);
+    let avg_reward = evaluate_greedy_policy(&mdp, &q_map, eval_episodes, eval_steps, &mut rng);
+    println!(
+        "Discounted Q-learning average reward per step: {}",
+        avg_reward / eval_steps as f64
+    );
+
+    let avg_reward = fixed_cycle(&mdp, eval_episodes, eval_steps, 6, 2, &mut rng);
+    println!(
+        "Fixed cycle average reward per step: {}",
+        avg_reward / eval_steps as f64
+    );
+}
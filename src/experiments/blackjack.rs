@@ -0,0 +1,73 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+
+use crate::{
+    algorithms::{monte_carlo::MonteCarlo, TabularAlgorithm},
+    analysis::{optimal_q_map, suboptimality},
+    envs::blackjack::{BlackjackAction, BlackjackMdp, BlackjackState},
+    eval::evaluate_greedy_policy,
+    mdp::GenericMdp,
+    policies::TabularPolicy,
+};
+
+// Monte Carlo control on Blackjack compared with the exact optimum, the policies are printed like
+// figure 5.2 of Sutton & Barto
+pub fn run_experiment() {
+    let mut rng = ChaCha20Rng::seed_from_u64(0);
+    let episodes = 500_000;
+    let epsilon = 0.1;
+
+    let env = BlackjackMdp::new();
+    let model = env.build_model().unwrap();
+    let optimal = optimal_q_map(&model, 1e-9);
+    let optimal_value: f64 = env
+        .initial_distribution()
+        .iter()
+        .map(|(prob, state)| {
+            prob * env
+                .get_possible_actions(*state)
+                .iter()
+                .map(|action| optimal[&(*state, *action)])
+                .fold(f64::NEG_INFINITY, f64::max)
+        })
+        .sum();
+
+    let q_map = MonteCarlo::new(epsilon, 100).run(&env, episodes, &mut rng);
+    let evaluated = evaluate_greedy_policy(&env, &q_map, 100_000, 100, &mut rng);
+    let stats = suboptimality(&model, &q_map, 1e-9);
+
+    println!("optimal policy");
+    print_policy(&TabularPolicy::from_q_map(&optimal));
+    println!("Monte Carlo after {} episodes", episodes);
+    print_policy(&TabularPolicy::from_q_map(&q_map));
+    println!("optimal value: {:.4}", optimal_value);
+    println!("evaluated Monte Carlo value: {:.4}", evaluated);
+    println!(
+        "optimal actions: {:.1}%",
+        stats.optimal_action_fraction * 100.0
+    );
+}
+
+// one grid per usable ace, player sums from top to bottom, dealer cards from ace to 10
+fn print_policy(policy: &TabularPolicy<BlackjackState, BlackjackAction>) {
+    for usable_ace in [true, false] {
+        println!("usable ace: {}", usable_ace);
+        println!("    A 2 3 4 5 6 7 8 9 10");
+        for player in (12..=21).rev() {
+            let row = (1..=10)
+                .map(|dealer| {
+                    match policy
+                        .actions
+                        .get(&BlackjackState::Running(player, dealer, usable_ace))
+                    {
+                        Some(BlackjackAction::Hit) => "H",
+                        Some(BlackjackAction::Stick) => "S",
+                        None => "?",
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            println!("{:>2}  {}", player, row);
+        }
+    }
+}
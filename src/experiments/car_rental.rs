@@ -0,0 +1,53 @@
+use std::{collections::BTreeMap, time::Instant};
+
+use crate::{
+    algorithms::{policy_iteration::policy_iteration, value_iteration::value_iteration},
+    envs::car_rental::{CarRentalAction, CarRentalConfig, CarRentalState},
+};
+
+// solves Jack's car rental with policy and value iteration, the policies are printed like figure
+// 4.2 of Sutton & Barto
+pub fn run_experiment() {
+    for (name, config) in [
+        ("original", CarRentalConfig::new()),
+        ("free shuttle and parking fee", CarRentalConfig::modified()),
+    ] {
+        let start = Instant::now();
+        let mdp = config.build().unwrap();
+        println!(
+            "{}: {} transitions built in {:.2?}",
+            name,
+            mdp.transitions.values().map(Vec::len).sum::<usize>(),
+            start.elapsed()
+        );
+
+        let start = Instant::now();
+        let (policy, policy_values) = policy_iteration(&mdp, 1e-6, 10_000);
+        println!("policy iteration: {:.2?}", start.elapsed());
+        let start = Instant::now();
+        let values = value_iteration(&mdp, 1e-6);
+        println!("value iteration: {:.2?}", start.elapsed());
+
+        let max_difference = values
+            .iter()
+            .map(|(state, value)| (value - policy_values[state]).abs())
+            .fold(0.0, f64::max);
+        println!("largest value difference: {:.2e}", max_difference);
+        println!(
+            "value of {:?}: {:.2}",
+            mdp.initial_state, policy_values[&mdp.initial_state]
+        );
+        print_policy(&policy, config.max_cars);
+    }
+}
+
+// cars at the first location from top to bottom, at the second location from left to right
+fn print_policy(policy: &BTreeMap<CarRentalState, CarRentalAction>, max_cars: usize) {
+    for first in (0..=max_cars).rev() {
+        let row = (0..=max_cars)
+            .map(|second| format!("{:>3}", policy[&CarRentalState(first, second)].0))
+            .collect::<Vec<_>>()
+            .join("");
+        println!("{:>3} {}", first, row);
+    }
+}
@@ -1,5 +1,5 @@
 use crate::{
-    algorithms::GenericStateActionAlgorithm,
+    algorithms::TabularAlgorithm,
     eval::{evaluate_epsilon_greedy_policy, evaluate_greedy_policy},
 };
 use rand::SeedableRng;
@@ -33,7 +33,7 @@ pub fn run_cliff_walking() {
 
     let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
 
-    let q_learning_algo = QLearning::new(alpha, epsilon, learning_max_steps);
+    let mut q_learning_algo = QLearning::new(alpha, epsilon, learning_max_steps);
     let q_map = q_learning_algo.run(&cliff_walking_mdp, learning_episodes, &mut rng);
 
     let avg_reward = evaluate_epsilon_greedy_policy(
@@ -61,7 +61,7 @@ pub fn run_cliff_walking() {
         .serialize(("Q-Learning greedy", avg_reward))
         .expect("csv error");
 
-    let sarsa_algo = Sarsa::new(alpha, epsilon, learning_max_steps);
+    let mut sarsa_algo = Sarsa::new(alpha, epsilon, learning_max_steps);
     let q_map = sarsa_algo.run(&cliff_walking_mdp, learning_episodes, &mut rng);
 
     let avg_reward = evaluate_epsilon_greedy_policy(
@@ -89,7 +89,7 @@ pub fn run_cliff_walking() {
         .serialize(("SARSA greedy", avg_reward))
         .expect("csv error");
 
-    let mc_algo = MonteCarlo::new(epsilon, learning_max_steps);
+    let mut mc_algo = MonteCarlo::new(epsilon, learning_max_steps);
     let q_map = mc_algo.run(&cliff_walking_mdp, learning_episodes, &mut rng);
 
     let avg_reward = evaluate_epsilon_greedy_policy(
@@ -119,7 +119,7 @@ pub fn run_cliff_walking() {
 
     // SARSA Lambda
     let lambda = 0.1;
-    let sarsa_lambda_algo = SarsaLambda::new(
+    let mut sarsa_lambda_algo = SarsaLambda::new(
         alpha,
         epsilon,
         lambda,
@@ -155,7 +155,7 @@ pub fn run_cliff_walking() {
 
     // Q-Learning Lambda
     let lambda = 0.1;
-    let q_learning_lambda_algo = QLearningLambda::new(
+    let mut q_learning_lambda_algo = QLearningLambda::new(
         alpha,
         epsilon,
         lambda,
@@ -212,7 +212,7 @@ pub fn run_slippery_cliff_walking() {
 
     let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(1);
 
-    let q_learning_algo = QLearning::new(alpha, epsilon, learning_max_steps);
+    let mut q_learning_algo = QLearning::new(alpha, epsilon, learning_max_steps);
     let q_map = q_learning_algo.run(&slippy_cliff_walking_mdp, learning_episodes, &mut rng);
 
     let avg_reward = evaluate_epsilon_greedy_policy(
@@ -240,7 +240,7 @@ pub fn run_slippery_cliff_walking() {
         .serialize(("Q-Learning greedy", avg_reward))
         .expect("csv error");
 
-    let sarsa_algo = Sarsa::new(alpha, epsilon, learning_max_steps);
+    let mut sarsa_algo = Sarsa::new(alpha, epsilon, learning_max_steps);
     let q_map = sarsa_algo.run(&slippy_cliff_walking_mdp, learning_episodes, &mut rng);
 
     let avg_reward = evaluate_epsilon_greedy_policy(
@@ -268,7 +268,7 @@ pub fn run_slippery_cliff_walking() {
         .serialize(("SARSA greedy", avg_reward))
         .expect("csv error");
 
-    let mc_algo = MonteCarlo::new(epsilon, learning_max_steps);
+    let mut mc_algo = MonteCarlo::new(epsilon, learning_max_steps);
     let q_map = mc_algo.run(&slippy_cliff_walking_mdp, learning_episodes, &mut rng);
 
     let avg_reward = evaluate_epsilon_greedy_policy(
@@ -313,7 +313,7 @@ pub fn run_cliff_walking_episodic() {
 
     let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(1);
 
-    let q_learning_algo = QLearning::new(alpha, epsilon, learning_max_steps);
+    let mut q_learning_algo = QLearning::new(alpha, epsilon, learning_max_steps);
 
     let mut q_map = q_learning_algo.run(&cliff_walking_mdp, 1, &mut rng);
     let mut csv_writer = csv::Writer::from_path("q_learning.csv").expect("csv error");
@@ -340,7 +340,7 @@ pub fn run_cliff_walking_episodic() {
 
     let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(1);
 
-    let sarsa_algo = Sarsa::new(alpha, epsilon, learning_max_steps);
+    let mut sarsa_algo = Sarsa::new(alpha, epsilon, learning_max_steps);
 
     let mut q_map = sarsa_algo.run(&cliff_walking_mdp, 1, &mut rng);
 
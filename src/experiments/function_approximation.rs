@@ -0,0 +1,69 @@
+use rand::SeedableRng;
+
+use crate::{
+    approximation::{
+        intersection::{intersection_state_vector, ma_intersection_features},
+        semi_gradient_q_learning::SemiGradientQLearning,
+        semi_gradient_sarsa::SemiGradientSarsa,
+        tile_coding::TileCoding,
+        GenericLinearAlgorithm,
+    },
+    envs::my_intersection::{LightAction, MyIntersectionMdp},
+    eval::{evaluate_greedy_linear_policy, evaluate_random_policy},
+    multiagent::intersection::MAIntersectionMdp,
+};
+
+pub fn run_experiment() {
+    let train_episodes = 200;
+    let eval_episodes = 10;
+    let max_steps = 2000;
+    let epsilon = 0.1;
+
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+
+    // tile coding on the single intersection
+    let max_cars = 10;
+    let mdp = MyIntersectionMdp::new(0.6, 0.2, max_cars);
+    let num_tilings = 8;
+    let tile_coding = TileCoding::new(
+        num_tilings,
+        4,
+        vec![(0.0, max_cars as f64), (0.0, max_cars as f64), (0.0, 4.0)],
+        vec![
+            LightAction::Change,
+            LightAction::Stay,
+            LightAction::WaitForChange,
+        ],
+        intersection_state_vector,
+    );
+    let sarsa_algo = SemiGradientSarsa::new(0.1 / num_tilings as f64, epsilon, max_steps);
+    let weights = sarsa_algo.run(&mdp, &tile_coding, train_episodes, &mut rng);
+    let avg_reward = evaluate_greedy_linear_policy(
+        &mdp,
+        &weights,
+        &tile_coding,
+        eval_episodes,
+        max_steps,
+        &mut rng,
+    );
+    println!("Tile coded SARSA on intersection: {avg_reward}");
+
+    // hand-crafted features on a multi-agent intersection too large to enumerate
+    let max_cars = 30;
+    let ma_mdp = MAIntersectionMdp::new_lazy(0.5, 0.4, 0.5, 0.4, max_cars);
+    let features = ma_intersection_features(max_cars);
+    let q_algo = SemiGradientQLearning::new(0.01, epsilon, max_steps);
+    let weights = q_algo.run(&ma_mdp, &features, train_episodes, &mut rng);
+    let avg_reward = evaluate_greedy_linear_policy(
+        &ma_mdp,
+        &weights,
+        &features,
+        eval_episodes,
+        max_steps,
+        &mut rng,
+    );
+    println!("Linear Q-learning on multi-agent intersection: {avg_reward}");
+
+    let avg_reward = evaluate_random_policy(&ma_mdp, eval_episodes, max_steps, &mut rng);
+    println!("Random policy on multi-agent intersection: {avg_reward}");
+}
@@ -1,13 +1,17 @@
 use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
 
 use crate::{
-    algorithms::{q_learning::QLearning, GenericStateActionAlgorithm},
-    envs::{
-        self,
-        my_intersection::{LightAction, MyIntersectionMdp},
+    algorithms::{q_learning::QLearning, TabularAlgorithm},
+    analysis::optimal_q_map,
+    envs::intersection::{build_mdp, to_intersection_q_map},
+    envs::my_intersection::{IntersectionState, LightAction, LightState, MyIntersectionMdp},
+    eval::{
+        evaluate_epsilon_greedy_policy, evaluate_greedy_policy, evaluate_policy,
+        evaluate_random_policy,
     },
-    eval::{evaluate_epsilon_greedy_policy, evaluate_greedy_policy, evaluate_random_policy},
     mdp::GenericMdp,
+    policies::Policy,
 };
 
 pub fn run_experiment() {
@@ -15,7 +19,7 @@ pub fn run_experiment() {
     let train_episodes = 1000;
     let episode_length = 2000;
     let generic_mdp = MyIntersectionMdp::new(0.5, 0.3, 50);
-    let generic_q_learning = QLearning::new(0.1, 0.1, episode_length);
+    let mut generic_q_learning = QLearning::new(0.1, 0.1, episode_length);
     // let generic_q_learning = SarsaLambda::new(0.1, 0.1, 0.1, episode_length, Trace::Accumulating);
     let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(1);
     let q_map = generic_q_learning.run(&generic_mdp, train_episodes, &mut rng);
@@ -57,10 +61,85 @@ pub fn run_experiment() {
         &mut rng,
     );
 
+    // exact optimum of the explicit model of the same intersection
+    let model = build_mdp(&generic_mdp).unwrap();
+    let optimal_q_map = to_intersection_q_map(&optimal_q_map(&model, 1e-6), generic_mdp.max_cars());
+    let avg_reward_optimal = evaluate_greedy_policy(
+        &generic_mdp,
+        &optimal_q_map,
+        eval_episodes,
+        episode_length,
+        &mut rng,
+    );
+
     println!("epsilon: {:?}", avg_reward_epsilon);
     println!("greedy: {:?}", avg_reward_greedy);
     println!("random: {:?}", avg_reward_random);
     println!("fixed cycle reward: {:?}", avg_reward_fixed_cycle);
+    println!("optimal (value iteration): {:?}", avg_reward_optimal);
+}
+
+// keeps each direction open for a fixed number of steps before switching the lights
+#[derive(Clone, Copy, Debug)]
+pub struct FixedCyclePolicy {
+    ns_time: usize,
+    ew_time: usize,
+    cycle_counter: usize,
+}
+
+impl FixedCyclePolicy {
+    pub fn new(ns_time: usize, ew_time: usize) -> Self {
+        Self {
+            ns_time,
+            ew_time,
+            cycle_counter: 0,
+        }
+    }
+
+    fn next_action(&self, state: IntersectionState) -> (LightAction, usize) {
+        let open_time = match state.light_state {
+            LightState::NorthSouthOpen => self.ns_time,
+            LightState::EastWestOpen => self.ew_time,
+            LightState::ChangingToNS | LightState::ChangingToEW => {
+                return (LightAction::WaitForChange, self.cycle_counter)
+            }
+        };
+
+        if self.cycle_counter < open_time {
+            (LightAction::Stay, self.cycle_counter + 1)
+        } else {
+            (LightAction::Change, 0)
+        }
+    }
+}
+
+impl Policy<IntersectionState, LightAction> for FixedCyclePolicy {
+    fn begin_episode(&mut self) {
+        self.cycle_counter = 0;
+    }
+
+    fn select_action<R: Rng>(
+        &mut self,
+        state: IntersectionState,
+        _possible_actions: &[LightAction],
+        _rng: &mut R,
+    ) -> Option<LightAction> {
+        let (action, cycle_counter) = self.next_action(state);
+        self.cycle_counter = cycle_counter;
+        Some(action)
+    }
+
+    fn action_probabilities(
+        &self,
+        state: IntersectionState,
+        possible_actions: &[LightAction],
+    ) -> Vec<(LightAction, f64)> {
+        let (selected, _) = self.next_action(state);
+        possible_actions
+            .iter()
+            .map(|action| (*action, if *action == selected { 1.0 } else { 0.0 }))
+            .collect()
+    }
 }
 
 pub fn fixed_cycle(
@@ -69,48 +148,8 @@ pub fn fixed_cycle(
     max_steps: usize,
     ns_time: usize,
     ew_time: usize,
-    rng: &mut (impl SeedableRng + Rng),
+    rng: &mut ChaCha20Rng,
 ) -> f64 {
-    let mut total_reward = 0.0;
-    for _ in 0..episodes {
-        let mut steps = 0;
-        let mut cycle_counter = 0;
-        let mut state = mdp.get_initial_state(rng);
-        let mut episode_reward = 0.0;
-
-        while steps < max_steps {
-            let action = match state.light_state {
-                envs::my_intersection::LightState::NorthSouthOpen => {
-                    if cycle_counter < ns_time {
-                        cycle_counter += 1;
-                        LightAction::Stay
-                    } else {
-                        cycle_counter = 0;
-                        LightAction::Change
-                    }
-                }
-                envs::my_intersection::LightState::EastWestOpen => {
-                    if cycle_counter < ew_time {
-                        cycle_counter += 1;
-                        LightAction::Stay
-                    } else {
-                        cycle_counter = 0;
-                        LightAction::Change
-                    }
-                }
-                envs::my_intersection::LightState::ChangingToNS => LightAction::WaitForChange,
-                envs::my_intersection::LightState::ChangingToEW => LightAction::WaitForChange,
-            };
-
-            // println!("step: {:?}, action: {:?}", steps, action);
-
-            let (next_state, reward) = mdp.perform_action((state, action), rng);
-            state = next_state;
-            episode_reward += reward;
-
-            steps += 1;
-        }
-        total_reward += episode_reward;
-    }
-    total_reward / episodes as f64
+    let mut policy = FixedCyclePolicy::new(ns_time, ew_time);
+    evaluate_policy(mdp, &mut policy, episodes, max_steps, rng)
 }
@@ -1,6 +1,12 @@
+pub mod average_reward;
+pub mod blackjack;
+pub mod car_rental;
 pub mod cliff_walking;
+pub mod function_approximation;
 pub mod intersection;
 pub mod multiagent;
 pub mod non_contractive;
+pub mod offline;
+pub mod psrl;
 pub mod q_learning_beta;
 pub mod q_learning_dynamic;
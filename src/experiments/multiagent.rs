@@ -11,7 +11,7 @@ pub fn single_agent_rl() {
     let q_algo_1 = QLearning::new(0.1, 0.1, max_steps);
     let q_algo_2 = QLearning::new(0.1, 0.1, max_steps);
 
-    let runner = MAIntersectionRunnerSingleAgentRL::new(
+    let mut runner = MAIntersectionRunnerSingleAgentRL::new(
         0.5, 0.4, 0.5, 0.4, 10, q_algo_1, q_algo_2, max_steps,
     );
 
@@ -36,7 +36,7 @@ pub fn regular_rl() {
     let q_algo_1 = QLearning::new(0.1, 0.1, max_steps);
     let q_algo_2 = QLearning::new(0.1, 0.1, max_steps);
 
-    let runner =
+    let mut runner =
         MAIntersectionRunnerRegularRL::new(0.5, 0.4, 0.5, 0.4, 10, q_algo_1, q_algo_2, max_steps);
 
     let (mut q_map_1, mut q_map_2) = runner.gen_q_maps();
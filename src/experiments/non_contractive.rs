@@ -1,10 +1,11 @@
 use crate::{
+    agent::Observation,
     algorithms::{
-        dyna_q::{BetaDynaQ, Dyna, DynaQ},
-        GenericStateActionAlgorithm, GenericStateActionAlgorithmStateful,
+        dyna_q::{BetaDynaQ, DynaQ},
+        greedy_next_q, TabularAlgorithm,
     },
     eval::evaluate_greedy_policy,
-    mdp::{GenericAction, GenericMdp, GenericState},
+    mdp::{GenericAction, GenericState},
 };
 use std::collections::{BTreeMap, HashSet};
 
@@ -14,7 +15,7 @@ use rand_chacha::ChaCha20Rng;
 use crate::{
     algorithms::value_iteration::value_iteration,
     mdp::{IndexAction, IndexMdp, IndexState, Transition},
-    policies::{epsilon_greedy_policy, greedy_policy},
+    policies::epsilon_greedy_policy_ma,
     utils::{print_q_map, print_transition_map},
 };
 
@@ -207,62 +208,45 @@ impl QLearningClipped {
     }
 }
 
-impl GenericStateActionAlgorithm for QLearningClipped {
-    fn run_with_q_map<M: GenericMdp<S, A>, S: GenericState, A: GenericAction, R: Rng>(
-        &self,
-        mdp: &M,
-        episodes: usize,
+impl<S: GenericState, A: GenericAction> TabularAlgorithm<S, A> for QLearningClipped {
+    type Memory = ();
+
+    fn max_steps(&self) -> usize {
+        self.max_steps
+    }
+
+    fn act<R: Rng>(
+        &mut self,
+        _memory: &mut (),
+        q_map: &BTreeMap<(S, A), f64>,
+        state: S,
+        possible_actions: &[A],
         rng: &mut R,
-        q_map: &mut BTreeMap<(S, A), f64>,
-    ) {
-        for _ in 1..=episodes {
-            let mut current_state = mdp.get_initial_state(rng);
-            let mut steps = 0;
-
-            while !mdp.is_terminal(current_state) && steps < self.max_steps {
-                let Some(selected_action) =
-                    epsilon_greedy_policy(mdp, q_map, current_state, self.epsilon, rng)
-                else {
-                    break;
-                };
-                let (next_state, reward) =
-                    mdp.perform_action((current_state, selected_action), rng);
-
-                // update q_map
-                let Some(best_action) = greedy_policy(mdp, q_map, next_state, rng) else {
-                    break;
-                };
-                let best_q = *q_map
-                    .get(&(next_state, best_action))
-                    .expect("No qmap entry found");
-
-                let current_q = q_map.entry((current_state, selected_action)).or_insert(0.0);
-                *current_q += (self.alpha
-                    * (reward + mdp.get_discount_factor() * best_q - *current_q))
-                    .clamp(-self.clip, self.clip);
-
-                // println!(
-                //     "update: {:?}, state: {:?}, action: {:?}, clipped reward: {:?}, discounted: {:?}, current_q: {:?} ",
-                //     self.alpha
-                //         * (reward.clamp(-self.clip, self.clip)
-                //             + mdp.get_discount_factor() * best_q
-                //             - *current_q),
-                //     current_state,
-                //     selected_action,
-                //     reward.clamp(-self.clip, self.clip),
-                //     mdp.get_discount_factor() * best_q,
-                //     *current_q
-                // );
-
-                current_state = next_state;
-
-                steps += 1;
-            }
-        }
+    ) -> Option<A> {
+        epsilon_greedy_policy_ma(possible_actions, q_map, state, self.epsilon, rng)
     }
 
-    fn get_epsilon(&self) -> f64 {
-        self.epsilon
+    fn observe<R: Rng>(
+        &mut self,
+        _memory: &mut (),
+        q_map: &mut BTreeMap<(S, A), f64>,
+        observation: &Observation<S, A>,
+        rng: &mut R,
+    ) -> Option<f64> {
+        let best_q = greedy_next_q(
+            q_map,
+            observation.next_state,
+            &observation.next_possible_actions,
+            observation.terminal,
+            rng,
+        );
+
+        let current_q = q_map
+            .entry((observation.state, observation.action))
+            .or_insert(0.0);
+        let td_error = observation.reward + observation.discount_factor * best_q - *current_q;
+        *current_q += (self.alpha * td_error).clamp(-self.clip, self.clip);
+        Some(td_error)
     }
 }
 
@@ -0,0 +1,105 @@
+use rand::SeedableRng;
+
+use crate::{
+    envs::my_intersection::MyIntersectionMdp,
+    eval::{evaluate, evaluate_greedy_policy},
+    experiments::intersection::fixed_cycle,
+    mdp::GenericMdp,
+    offline::{
+        batch_q_learning::batch_q_learning_with_q_map,
+        fitted_q_iteration::fitted_q_iteration_with_q_map,
+        model::EstimatedModel,
+        ope::{collect_episodes, estimate_all},
+        Dataset,
+    },
+    policies::EpsilonGreedyPolicy,
+};
+
+// learns intersection policies from transitions logged by a random controller, the simulator is
+// only used to collect the logs and to evaluate the learned policies
+pub fn run_experiment() {
+    let mdp = MyIntersectionMdp::new(0.6, 0.2, 10);
+
+    let log_episodes = 20;
+    let log_steps = 1000;
+    let tolerance = 1e-6;
+    let eval_episodes = 10;
+    let eval_steps = 1000;
+
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+
+    let dataset = Dataset::collect_random(&mdp, log_episodes, log_steps, &mut rng);
+    println!("logged transitions: {}", dataset.len());
+    dataset.coverage(mdp.get_all_state_actions()).print_report();
+
+    let mut q_map = mdp
+        .get_all_state_actions()
+        .iter()
+        .map(|sa| (*sa, 0.0))
+        .collect();
+    let iterations = fitted_q_iteration_with_q_map(
+        &dataset,
+        mdp.get_discount_factor(),
+        tolerance,
+        1000,
+        &mut q_map,
+    );
+    let avg_reward = evaluate_greedy_policy(&mdp, &q_map, eval_episodes, eval_steps, &mut rng);
+    println!(
+        "Fitted Q iteration ({} iterations) average reward: {}",
+        iterations, avg_reward
+    );
+
+    let mut q_map = mdp
+        .get_all_state_actions()
+        .iter()
+        .map(|sa| (*sa, 0.0))
+        .collect();
+    let sweeps = batch_q_learning_with_q_map(
+        &dataset,
+        1.0,
+        mdp.get_discount_factor(),
+        tolerance,
+        1000,
+        &mut rng,
+        &mut q_map,
+    );
+    let avg_reward = evaluate_greedy_policy(&mdp, &q_map, eval_episodes, eval_steps, &mut rng);
+    println!(
+        "Batch Q-learning ({} sweeps) average reward: {}",
+        sweeps, avg_reward
+    );
+
+    // states the logs never left from have no actions in the estimated model, those are filled
+    // with zeros so the greedy evaluation can act everywhere
+    let mut q_map = EstimatedModel::from_dataset(&dataset).plan(
+        mdp.get_discount_factor(),
+        mdp.get_initial_state(&mut rng),
+        tolerance,
+    );
+    mdp.get_all_state_actions().iter().for_each(|sa| {
+        q_map.entry(*sa).or_insert(0.0);
+    });
+    let avg_reward = evaluate_greedy_policy(&mdp, &q_map, eval_episodes, eval_steps, &mut rng);
+    println!("Certainty equivalence average reward: {}", avg_reward);
+
+    let avg_reward = fixed_cycle(&mdp, eval_episodes, eval_steps, 6, 2, &mut rng);
+    println!("Fixed cycle average reward: {}", avg_reward);
+
+    // off-policy evaluation of a near greedy controller from logs of a more exploratory one.
+    // Short episodes keep the importance weights usable.
+    let ope_steps = 20;
+    let mut behaviour = EpsilonGreedyPolicy::new(&q_map, 0.5);
+    let mut target = EpsilonGreedyPolicy::new(&q_map, 0.1);
+    let episodes = collect_episodes(&mdp, &mut behaviour, 500, ope_steps, &mut rng);
+    let estimates = estimate_all(
+        &episodes,
+        &target,
+        mdp.get_discount_factor(),
+        tolerance,
+        1000,
+    );
+    println!("Off-policy estimates: {:?}", estimates);
+    let report = evaluate(&mdp, &mut target, 500, ope_steps, &mut rng);
+    println!("On-policy discounted return: {}", report.discounted_mean);
+}
@@ -0,0 +1,175 @@
+use std::{cell::RefCell, collections::BTreeMap};
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+use crate::{
+    algorithms::{
+        dyna_q::DynaQ,
+        psrl::{NormalGammaPrior, Psrl},
+        q_learning::QLearning,
+        value_iteration::value_iteration,
+        TabularAlgorithm,
+    },
+    generator::generate_random_mdp,
+    mdp::{GenericMdp, IndexAction, IndexMdp, IndexState, Reward},
+};
+
+// records the rewards the learners collect, the algorithms themselves do not report returns
+struct RewardTracker<'a> {
+    mdp: &'a IndexMdp,
+    rewards: RefCell<Vec<Reward>>,
+}
+
+impl<'a> RewardTracker<'a> {
+    fn new(mdp: &'a IndexMdp) -> Self {
+        Self {
+            mdp,
+            rewards: RefCell::new(vec![]),
+        }
+    }
+
+    // discounted return since the last call
+    fn take_return(&self) -> f64 {
+        let discount_factor = self.mdp.get_discount_factor();
+        self.rewards
+            .borrow_mut()
+            .drain(..)
+            .rev()
+            .fold(0.0, |acc, reward| reward + discount_factor * acc)
+    }
+}
+
+impl<'a> GenericMdp<IndexState, IndexAction> for RewardTracker<'a> {
+    fn perform_action<R: Rng>(
+        &self,
+        state_action: (IndexState, IndexAction),
+        rng: &mut R,
+    ) -> (IndexState, Reward) {
+        let (next_state, reward) = self.mdp.perform_action(state_action, rng);
+        self.rewards.borrow_mut().push(reward);
+        (next_state, reward)
+    }
+
+    fn get_possible_actions(&self, current_state: IndexState) -> Vec<IndexAction> {
+        self.mdp.get_possible_actions(current_state)
+    }
+
+    fn get_all_state_actions(&self) -> &[(IndexState, IndexAction)] {
+        self.mdp.get_all_state_actions()
+    }
+
+    fn is_terminal(&self, state: IndexState) -> bool {
+        self.mdp.is_terminal(state)
+    }
+
+    fn get_initial_state<R: Rng>(&self, rng: &mut R) -> IndexState {
+        self.mdp.get_initial_state(rng)
+    }
+
+    fn get_discount_factor(&self) -> f64 {
+        self.mdp.get_discount_factor()
+    }
+}
+
+// random mdp where only reaching one of the terminal states is rewarded, retries until the goal
+// is reachable from the initial state
+fn generate_sparse_mdp(rng: &mut ChaCha20Rng) -> (IndexMdp, f64) {
+    loop {
+        let mut mdp = generate_random_mdp(20, 3, 3, (2, 3), (1, 2), (0.0, 0.0), rng);
+        mdp.discount_factor = 0.95;
+
+        let goal = *mdp
+            .terminal_states
+            .iter()
+            .min()
+            .expect("has terminal states");
+        mdp.transitions.values_mut().for_each(|transitions| {
+            transitions.iter_mut().for_each(|(_, next_state, reward)| {
+                *reward = if *next_state == goal { 1.0 } else { 0.0 };
+            })
+        });
+
+        let optimal_value = *value_iteration(&mdp, 1e-9)
+            .get(&mdp.initial_state)
+            .unwrap_or(&0.0);
+        if optimal_value > 0.0 {
+            return (mdp, optimal_value);
+        }
+    }
+}
+
+// cumulative regret of an agent that is trained one episode at a time
+fn cumulative_regret<F: FnMut(&RewardTracker, &mut ChaCha20Rng)>(
+    mdp: &IndexMdp,
+    optimal_value: f64,
+    episodes: usize,
+    rng: &mut ChaCha20Rng,
+    mut run_episode: F,
+) -> Vec<f64> {
+    let tracker = RewardTracker::new(mdp);
+    let mut regret = 0.0;
+
+    (0..episodes)
+        .map(|_| {
+            run_episode(&tracker, rng);
+            regret += optimal_value - tracker.take_return();
+            regret
+        })
+        .collect()
+}
+
+pub fn run_experiment() {
+    let num_mdps = 10;
+    let episodes = 300;
+    let max_steps = 100;
+    let checkpoints = [10, 50, 100, 200, 300];
+
+    let mut mdp_rng = ChaCha20Rng::seed_from_u64(0);
+    let mut results: BTreeMap<&str, Vec<f64>> = BTreeMap::new();
+
+    for i in 0..num_mdps {
+        let (mdp, optimal_value) = generate_sparse_mdp(&mut mdp_rng);
+        let mut add_result = |name, regret: Vec<f64>| {
+            let total = results.entry(name).or_insert(vec![0.0; episodes]);
+            total
+                .iter_mut()
+                .zip(regret)
+                .for_each(|(total, regret)| *total += regret / num_mdps as f64);
+        };
+
+        let mut rng = ChaCha20Rng::seed_from_u64(i);
+        let mut psrl = Psrl::new(max_steps, 1e-6, 1.0, NormalGammaPrior::default(), &mdp);
+        let mut q_map = BTreeMap::new();
+        let regret = cumulative_regret(&mdp, optimal_value, episodes, &mut rng, |env, rng| {
+            psrl.run_with_q_map(env, 1, rng, &mut q_map)
+        });
+        add_result("PSRL", regret);
+
+        let mut rng = ChaCha20Rng::seed_from_u64(i);
+        let mut dyna_q = DynaQ::new(0.1, 0.1, 10, max_steps, false, true, &mdp);
+        let mut q_map = mdp.states_actions.iter().map(|sa| (*sa, 0.0)).collect();
+        let regret = cumulative_regret(&mdp, optimal_value, episodes, &mut rng, |env, rng| {
+            dyna_q.run_with_q_map(env, 1, rng, &mut q_map)
+        });
+        add_result("DynaQ", regret);
+
+        let mut rng = ChaCha20Rng::seed_from_u64(i);
+        let mut q_learning = QLearning::new(0.1, 0.1, max_steps);
+        let mut q_map = mdp.states_actions.iter().map(|sa| (*sa, 0.0)).collect();
+        let regret = cumulative_regret(&mdp, optimal_value, episodes, &mut rng, |env, rng| {
+            q_learning.run_with_q_map(env, 1, rng, &mut q_map)
+        });
+        add_result("Q-learning", regret);
+    }
+
+    println!("mean cumulative regret over {} sparse mdps", num_mdps);
+    for (name, regret) in results.iter() {
+        let at_checkpoints = checkpoints
+            .iter()
+            .map(|episode| format!("{}: {:.2}", episode, regret[episode - 1]))
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!("{}: {}", name, at_checkpoints);
+    }
+}
@@ -3,10 +3,7 @@ use rand_chacha::ChaCha20Rng;
 
 use crate::{
     algorithms::{
-        dyna_q::{Dyna, DynaQ},
-        q_learning::QLearning,
-        q_learning_beta::QLearningBeta,
-        GenericStateActionAlgorithm, GenericStateActionAlgorithmStateful,
+        dyna_q::DynaQ, q_learning::QLearning, q_learning_beta::QLearningBeta, TabularAlgorithm,
     },
     eval::evaluate_greedy_policy,
     experiments::non_contractive::QLearningClipped,
@@ -87,9 +84,9 @@ where
 
     let eval_episodes = 1;
 
-    let q_algo = QLearning::new(alpha, epsilon, max_steps);
+    let mut q_algo = QLearning::new(alpha, epsilon, max_steps);
     let mut q_beta_algo = QLearningBeta::new(alpha, epsilon, max_steps, beta_rate);
-    let q_clipped_algo = QLearningClipped::new(alpha, epsilon, max_steps, 50.0);
+    let mut q_clipped_algo = QLearningClipped::new(alpha, epsilon, max_steps, 50.0);
     let mut dyna_q_algo = DynaQ::new(alpha, epsilon, k, max_steps, false, true, mdp);
 
     let mut rng = ChaCha20Rng::seed_from_u64(seed);
@@ -1,5 +1,5 @@
 use crate::{
-    algorithms::GenericStateActionAlgorithm,
+    algorithms::TabularAlgorithm,
     eval::{evaluate_epsilon_greedy_policy, evaluate_greedy_policy},
 };
 use rand::SeedableRng;
@@ -25,7 +25,7 @@ pub fn run_experiment() {
 
     let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
 
-    let q_learning_algo = QLearningDynamic::new(alpha, epsilon, learning_max_steps);
+    let mut q_learning_algo = QLearningDynamic::new(alpha, epsilon, learning_max_steps);
     let q_map = q_learning_algo.run(&cliff_walking_mdp, learning_episodes, &mut rng);
 
     let avg_reward = evaluate_epsilon_greedy_policy(
@@ -49,7 +49,7 @@ pub fn run_experiment() {
 
     let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
 
-    let q_learning_algo = QLearning::new(alpha, epsilon, learning_max_steps);
+    let mut q_learning_algo = QLearning::new(alpha, epsilon, learning_max_steps);
     let q_map = q_learning_algo.run(&cliff_walking_mdp, learning_episodes, &mut rng);
 
     let avg_reward = evaluate_epsilon_greedy_policy(
@@ -4,14 +4,17 @@ use std::{
 };
 
 use rand::{
+    distributions::{Distribution, WeightedIndex},
     seq::{IteratorRandom, SliceRandom},
-    Rng,
+    Rng, SeedableRng,
 };
 use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
 
-use crate::mdp::{IndexAction, IndexState, MapMdp, Transition};
+use crate::mdp::{GenericMdp, IndexAction, IndexMdp, IndexState, MapMdp, Reward, Transition};
 
-// TODO: Ensure at least one terminal state is reachable
+// terminal states are not guaranteed to be reachable, GarnetConfig generates mdps that have a
+// path to a terminal state from every state
 pub fn generate_random_mdp(
     n_states: usize,
     n_actions: usize,
@@ -92,3 +95,245 @@ fn random_probs(n: usize, rng: &mut ChaCha20Rng) -> Vec<f64> {
         .map(|pair| pair[1] - pair[0])
         .collect()
 }
+
+// probability mass spread over all states in ergodic mdps
+const ERGODIC_SMOOTHING: f64 = 0.01;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Structure {
+    // episodic, every state has a path to one of the terminal states
+    Absorbing { n_terminal_states: usize },
+    // no terminal states, every state can reach every other state
+    Communicating,
+    // no terminal states, every state is reached from every state under any policy
+    Ergodic,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Rewards {
+    // every transition gets a uniformly drawn reward
+    Dense { min: Reward, max: Reward },
+    // only transitions into the goal get the reward. The goals are the terminal states of
+    // absorbing mdps and the last state otherwise.
+    Sparse { reward: Reward },
+}
+
+// Garnet mdps (Archibald et al. 1995): every action of every state leads to branching randomly
+// chosen next states with random probabilities. The config and its seed fully determine the
+// mdp, so it can be stored next to benchmark results.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GarnetConfig {
+    pub n_states: usize,
+    pub n_actions: usize,
+    pub branching: usize,
+    pub structure: Structure,
+    pub rewards: Rewards,
+    pub discount_factor: f64,
+    // number of states the initial state is drawn from, 1 always starts in state 0
+    pub initial_states: usize,
+    pub seed: u64,
+}
+
+impl GarnetConfig {
+    pub fn new(n_states: usize, n_actions: usize, branching: usize) -> Self {
+        Self {
+            n_states,
+            n_actions,
+            branching,
+            structure: Structure::Absorbing {
+                n_terminal_states: 1,
+            },
+            rewards: Rewards::Dense { min: 0.0, max: 1.0 },
+            discount_factor: 0.95,
+            initial_states: 1,
+            seed: 0,
+        }
+    }
+
+    pub fn with_structure(mut self, structure: Structure) -> Self {
+        self.structure = structure;
+        self
+    }
+
+    pub fn with_rewards(mut self, rewards: Rewards) -> Self {
+        self.rewards = rewards;
+        self
+    }
+
+    pub fn with_discount_factor(mut self, discount_factor: f64) -> Self {
+        self.discount_factor = discount_factor;
+        self
+    }
+
+    pub fn with_initial_states(mut self, initial_states: usize) -> Self {
+        self.initial_states = initial_states;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn generate(&self) -> anyhow::Result<GarnetMdp> {
+        let mut rng = ChaCha20Rng::seed_from_u64(self.seed);
+        self.generate_with(&mut rng)
+    }
+
+    pub fn generate_with(&self, rng: &mut ChaCha20Rng) -> anyhow::Result<GarnetMdp> {
+        let n_terminal_states = match self.structure {
+            Structure::Absorbing { n_terminal_states } => n_terminal_states,
+            Structure::Communicating | Structure::Ergodic => 0,
+        };
+        if self.n_states <= n_terminal_states || self.n_actions == 0 {
+            anyhow::bail!("garnet mdps need at least one non terminal state and one action");
+        }
+        if self.branching == 0 || self.branching > self.n_states {
+            anyhow::bail!("branching has to be between 1 and the number of states");
+        }
+        if self.initial_states == 0 || self.initial_states > self.n_states - n_terminal_states {
+            anyhow::bail!(
+                "initial states have to be between 1 and the number of non terminal states"
+            );
+        }
+
+        // state 0 starts, the terminal states are drawn from the rest
+        let mut others: Vec<IndexState> = (1..self.n_states).map(IndexState).collect();
+        others.shuffle(rng);
+        let terminal_states: HashSet<IndexState> = others.drain(..n_terminal_states).collect();
+        let mut non_terminal = vec![IndexState(0)];
+        non_terminal.extend(others);
+
+        let states: Vec<IndexState> = (0..self.n_states).map(IndexState).collect();
+        let actions: Vec<IndexAction> = (0..self.n_actions).map(IndexAction).collect();
+
+        let mut next_states: BTreeMap<(IndexState, IndexAction), Vec<IndexState>> = non_terminal
+            .iter()
+            .flat_map(|state| actions.iter().map(move |action| (*state, *action)))
+            .map(|state_action| {
+                let chosen = states
+                    .choose_multiple(rng, self.branching)
+                    .copied()
+                    .collect();
+                (state_action, chosen)
+            })
+            .collect();
+
+        // a random action of every state leads to the next state of a random order, the last
+        // state of the order leads to a terminal state or back to the first one
+        let mut order = non_terminal.clone();
+        order.shuffle(rng);
+        // sorted, hash set order differs between runs
+        let mut terminal_vec: Vec<IndexState> = terminal_states.iter().copied().collect();
+        terminal_vec.sort();
+        for (i, state) in order.iter().enumerate() {
+            let successor = match order.get(i + 1) {
+                Some(next) => *next,
+                None if terminal_vec.is_empty() => order[0],
+                None => *terminal_vec.choose(rng).expect("terminal states exist"),
+            };
+            let action = *actions.choose(rng).expect("actions exist");
+            let chosen = next_states
+                .get_mut(&(*state, action))
+                .expect("every pair has next states");
+            if !chosen.contains(&successor) {
+                let index = rng.gen_range(0..chosen.len());
+                chosen[index] = successor;
+            }
+        }
+
+        let goals: HashSet<IndexState> = if terminal_states.is_empty() {
+            HashSet::from([IndexState(self.n_states - 1)])
+        } else {
+            terminal_states.clone()
+        };
+
+        let mut mdp = IndexMdp::new(self.discount_factor, IndexState(0));
+        for (state_action, chosen) in next_states {
+            let mut outcomes: BTreeMap<IndexState, f64> = BTreeMap::new();
+            chosen
+                .iter()
+                .zip(random_probs(self.branching, rng))
+                .for_each(|(next_state, prob)| *outcomes.entry(*next_state).or_insert(0.0) += prob);
+            if self.structure == Structure::Ergodic {
+                outcomes
+                    .values_mut()
+                    .for_each(|prob| *prob *= 1.0 - ERGODIC_SMOOTHING);
+                states.iter().for_each(|state| {
+                    *outcomes.entry(*state).or_insert(0.0) +=
+                        ERGODIC_SMOOTHING / self.n_states as f64;
+                });
+            }
+
+            let transitions = outcomes
+                .into_iter()
+                .filter(|(_, prob)| *prob > 0.0)
+                .map(|(next_state, prob)| {
+                    let reward = match self.rewards {
+                        Rewards::Dense { min, max } => rng.gen_range(min..=max),
+                        Rewards::Sparse { reward } if goals.contains(&next_state) => reward,
+                        Rewards::Sparse { .. } => 0.0,
+                    };
+                    (prob, next_state, reward)
+                })
+                .collect();
+            mdp.add_transition_vector(state_action, transitions)?;
+        }
+        terminal_states
+            .iter()
+            .for_each(|state| mdp.add_terminal_state(*state));
+
+        let mut initial_states = non_terminal[..self.initial_states].to_vec();
+        initial_states.sort();
+        let initial_distribution = initial_states
+            .into_iter()
+            .zip(random_probs(self.initial_states, rng))
+            .map(|(state, prob)| (prob, state))
+            .collect();
+
+        Ok(GarnetMdp {
+            mdp,
+            initial_distribution,
+        })
+    }
+}
+
+// an IndexMdp with a random initial state. Model based tools that need a MapMdp use the mdp
+// field, its initial state is state 0.
+#[derive(Clone, Debug)]
+pub struct GarnetMdp {
+    pub mdp: IndexMdp,
+    pub initial_distribution: Vec<(f64, IndexState)>,
+}
+
+impl GenericMdp<IndexState, IndexAction> for GarnetMdp {
+    fn perform_action<R: Rng>(
+        &self,
+        state_action: (IndexState, IndexAction),
+        rng: &mut R,
+    ) -> (IndexState, Reward) {
+        self.mdp.perform_action(state_action, rng)
+    }
+
+    fn get_possible_actions(&self, current_state: IndexState) -> Vec<IndexAction> {
+        self.mdp.get_possible_actions(current_state)
+    }
+
+    fn get_all_state_actions(&self) -> &[(IndexState, IndexAction)] {
+        self.mdp.get_all_state_actions()
+    }
+
+    fn is_terminal(&self, state: IndexState) -> bool {
+        self.mdp.is_terminal(state)
+    }
+
+    fn get_initial_state<R: Rng>(&self, rng: &mut R) -> IndexState {
+        let probs = self.initial_distribution.iter().map(|(prob, _)| prob);
+        let dist = WeightedIndex::new(probs).expect("valid initial distribution");
+        self.initial_distribution[dist.sample(rng)].1
+    }
+
+    fn get_discount_factor(&self) -> f64 {
+        self.mdp.get_discount_factor()
+    }
+}
@@ -3,12 +3,17 @@
 #[macro_use]
 extern crate assert_float_eq;
 
+pub mod agent;
 pub mod algorithms;
+pub mod analysis;
+pub mod approximation;
 pub mod envs;
 pub mod eval;
 pub mod generator;
 pub mod mdp;
+pub mod persistence;
 pub mod policies;
+pub mod recorder;
 pub mod utils;
 
 #[cfg(test)]
@@ -21,3 +26,5 @@ pub mod experiments;
 pub mod visualisation;
 
 pub mod multiagent;
+
+pub mod offline;
@@ -1,4 +1,6 @@
-use clap::Command;
+use std::path::PathBuf;
+
+use clap::{Arg, Command};
 use mdp::{
     benchmarks,
     experiments::{self},
@@ -15,6 +17,14 @@ fn cli() -> Command {
             Command::new("experiment")
                 .about("Run experiments")
                 .arg_required_else_help(true)
+                .subcommand(
+                    Command::new("blackjack")
+                        .about("Compare Monte Carlo control on Blackjack with the exact optimum"),
+                )
+                .subcommand(
+                    Command::new("car_rental")
+                        .about("Solve Jack's car rental with policy and value iteration"),
+                )
                 .subcommand(
                     Command::new("noncontractive")
                         .about("Tests various algorithms on non-contractive mdp"),
@@ -26,6 +36,22 @@ fn cli() -> Command {
                 .subcommand(
                     Command::new("multiagent_agent_aware")
                         .about("Run agent-aware RL on multi-agent intersection environment"),
+                )
+                .subcommand(
+                    Command::new("average_reward")
+                        .about("Run average reward learners on intersection environment"),
+                )
+                .subcommand(
+                    Command::new("function_approximation")
+                        .about("Run linear function approximation on intersection environments"),
+                )
+                .subcommand(
+                    Command::new("offline")
+                        .about("Learn intersection policies from logged transitions"),
+                )
+                .subcommand(
+                    Command::new("psrl")
+                        .about("Compare the cumulative regret of PSRL on sparse random mdps"),
                 ),
         )
         .subcommand(
@@ -37,6 +63,13 @@ fn cli() -> Command {
                     Command::new("optimal_episodes")
                         .about("Run episodes required for optimal policy benchmarks"),
                 )
+                .subcommand(
+                    Command::new("windy_grid_world")
+                        .about("Run episodes required for optimal policy on the windy gridworld"),
+                )
+                .subcommand(Command::new("chain_length").about(
+                    "Run episodes required for optimal policy over combination lock lengths",
+                ))
                 .subcommand(
                     Command::new("intersection")
                         .about("Run strategy comparison benchmark on intersection environment"),
@@ -48,7 +81,18 @@ fn cli() -> Command {
                 .arg_required_else_help(true)
                 .subcommand(
                     Command::new("multiagent_intersection")
-                        .about("Run visualisation of multi-agent intersection"),
+                        .about("Run visualisation of multi-agent intersection")
+                        .arg(
+                            Arg::new("record")
+                                .long("record")
+                                .help("Write the simulated steps to a JSONL log"),
+                        )
+                        .arg(
+                            Arg::new("replay")
+                                .long("replay")
+                                .conflicts_with("record")
+                                .help("Play back a JSONL log instead of simulating"),
+                        ),
                 )
                 .subcommand(
                     Command::new("cliff_walking")
@@ -62,19 +106,35 @@ fn main() {
 
     match matches.subcommand() {
         Some(("experiment", experiment)) => match experiment.subcommand() {
+            Some(("blackjack", _)) => experiments::blackjack::run_experiment(),
+            Some(("car_rental", _)) => experiments::car_rental::run_experiment(),
             Some(("noncontractive", _)) => experiments::non_contractive::run_experiment(),
             Some(("multiagent_single", _)) => experiments::multiagent::regular_rl(),
             Some(("multiagent_agent_aware", _)) => experiments::multiagent::single_agent_rl(),
+            Some(("average_reward", _)) => experiments::average_reward::run_experiment(),
+            Some(("function_approximation", _)) => {
+                experiments::function_approximation::run_experiment()
+            }
+            Some(("offline", _)) => experiments::offline::run_experiment(),
+            Some(("psrl", _)) => experiments::psrl::run_experiment(),
             _ => println!("Invalid command."),
         },
         Some(("bench", benchmark)) => match benchmark.subcommand() {
             Some(("runtime", _)) => benchmarks::runtime::bench_runtime_all_env(),
             Some(("optimal_episodes", _)) => benchmarks::optimal_episodes::run_benchmark(),
+            Some(("windy_grid_world", _)) => benchmarks::optimal_episodes::windy_grid_world(),
+            Some(("chain_length", _)) => benchmarks::optimal_episodes::sweep_chain_length(),
             Some(("intersection", _)) => benchmarks::strategies::compare_intersection(),
             _ => println!("Invalid command."),
         },
         Some(("visual", vis)) => match vis.subcommand() {
-            Some(("multiagent_intersection", _)) => visualisation::ma_intersection::main().unwrap(),
+            Some(("multiagent_intersection", args)) => {
+                let flags = visualisation::ma_intersection::Flags {
+                    record: args.get_one::<String>("record").map(PathBuf::from),
+                    replay: args.get_one::<String>("replay").map(PathBuf::from),
+                };
+                visualisation::ma_intersection::main(flags).unwrap()
+            }
             Some(("cliff_walking", _)) => visualisation::vis_test(),
             _ => println!("Invalid command."),
         },
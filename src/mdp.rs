@@ -2,16 +2,17 @@ use rand::{
     distributions::{Distribution, WeightedIndex},
     Rng,
 };
+use serde::{Deserialize, Serialize};
 
 use std::{
     collections::{BTreeMap, HashSet},
     hash::Hash,
 };
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct IndexState(pub usize);
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct IndexAction(pub usize);
 
 pub type IndexMdp = MapMdp<IndexState, IndexAction>;
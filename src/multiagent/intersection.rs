@@ -4,15 +4,19 @@ use std::collections::BTreeMap;
 use itertools::{iproduct, Itertools};
 
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    algorithms::GenericStateActionAlgorithm,
+    agent::Observation,
+    algorithms::{
+        replay::{Experience, ReplayBuffer},
+        TabularAlgorithm,
+    },
     envs::my_intersection::{IntersectionState, LightAction, LightState},
     mdp::GenericMdp,
-    policies::epsilon_greedy_policy_ma,
 };
 
-#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Copy)]
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Copy, Serialize, Deserialize)]
 pub struct MAState {
     pub light_state_1: LightState,
     pub light_state_2: LightState,
@@ -35,8 +39,8 @@ impl From<(LightState, LightState, u8, u8, u8, u8)> for MAState {
     }
 }
 
-#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Copy)]
-pub struct Action(LightAction, LightAction);
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Copy, Serialize, Deserialize)]
+pub struct Action(pub LightAction, pub LightAction);
 
 pub struct MAIntersectionMdp {
     new_car_prob_ns_1: f64,
@@ -118,6 +122,29 @@ impl MAIntersectionMdp {
         }
     }
 
+    // skips enumerating all 16 * (max_cars + 1)^4 states, get_all_state_actions returns an empty
+    // slice so this is only usable with function approximation
+    pub fn new_lazy(
+        new_car_prob_ns_1: f64,
+        new_car_prob_ew_1: f64,
+        new_car_prob_ns_2: f64,
+        new_car_prob_ew_2: f64,
+        max_cars: u8,
+    ) -> Self {
+        Self {
+            new_car_prob_ns_1,
+            new_car_prob_ew_1,
+            new_car_prob_ns_2,
+            new_car_prob_ew_2,
+            max_cars,
+            states_actions: vec![],
+        }
+    }
+
+    pub fn get_max_cars(&self) -> u8 {
+        self.max_cars
+    }
+
     fn open_road_transition<R: Rng>(&self, old_cars: u8, new_prob: f64, rng: &mut R) -> u8 {
         if old_cars == 0 {
             0
@@ -324,14 +351,15 @@ impl GenericMdp<MAState, Action> for MAIntersectionMdp {
     }
 }
 
-pub struct MAIntersectionRunnerSingleAgentRL<G: GenericStateActionAlgorithm> {
+pub struct MAIntersectionRunnerSingleAgentRL<G: TabularAlgorithm<MAState, LightAction, Memory = ()>>
+{
     pub mdp: MAIntersectionMdp,
     agent_1: G,
     agent_2: G,
     max_steps: usize,
 }
 
-impl<G: GenericStateActionAlgorithm> MAIntersectionRunnerSingleAgentRL<G> {
+impl<G: TabularAlgorithm<MAState, LightAction, Memory = ()>> MAIntersectionRunnerSingleAgentRL<G> {
     pub fn new(
         new_car_prob_ns_1: f64,
         new_car_prob_ew_1: f64,
@@ -359,11 +387,24 @@ impl<G: GenericStateActionAlgorithm> MAIntersectionRunnerSingleAgentRL<G> {
     }
 
     pub fn run<R: Rng>(
-        &self,
+        &mut self,
         episodes: usize,
         q_map_1: &mut BTreeMap<(MAState, LightAction), f64>,
         q_map_2: &mut BTreeMap<(MAState, LightAction), f64>,
         rng: &mut R,
+    ) {
+        self.run_with_replay(episodes, q_map_1, q_map_2, None, None, rng);
+    }
+
+    // same as run, every agent stores its own experience in its buffer and replays it
+    pub fn run_with_replay<R: Rng>(
+        &mut self,
+        episodes: usize,
+        q_map_1: &mut BTreeMap<(MAState, LightAction), f64>,
+        q_map_2: &mut BTreeMap<(MAState, LightAction), f64>,
+        mut replay_1: Option<&mut ReplayBuffer<MAState, LightAction>>,
+        mut replay_2: Option<&mut ReplayBuffer<MAState, LightAction>>,
+        rng: &mut R,
     ) {
         for _ in 0..episodes {
             let mut current_state: MAState = self.mdp.get_initial_state(rng);
@@ -382,24 +423,18 @@ impl<G: GenericStateActionAlgorithm> MAIntersectionRunnerSingleAgentRL<G> {
                     MAIntersectionMdp::possible_light_actions(current_state.light_state_2);
 
                 // select action for intersection 1
-                let Some(selected_action_1) = epsilon_greedy_policy_ma(
-                    &possible_actions_1,
-                    q_map_1,
-                    current_state,
-                    self.agent_1.get_epsilon(),
-                    rng,
-                ) else {
+                let Some(selected_action_1) =
+                    self.agent_1
+                        .act(&mut (), q_map_1, current_state, &possible_actions_1, rng)
+                else {
                     panic!("no action possible")
                 };
 
                 // select action for intersection 2
-                let Some(selected_action_2) = epsilon_greedy_policy_ma(
-                    &possible_actions_2,
-                    q_map_2,
-                    current_state,
-                    self.agent_2.get_epsilon(),
-                    rng,
-                ) else {
+                let Some(selected_action_2) =
+                    self.agent_2
+                        .act(&mut (), q_map_2, current_state, &possible_actions_2, rng)
+                else {
                     panic!("no action possible")
                 };
 
@@ -417,29 +452,71 @@ impl<G: GenericStateActionAlgorithm> MAIntersectionRunnerSingleAgentRL<G> {
                     MAIntersectionMdp::possible_light_actions(next_state.light_state_2);
 
                 // println!("agent 1");
-                self.agent_1.step(
+                self.agent_1.observe(
+                    &mut (),
                     q_map_1,
-                    &next_possible_actions_1,
-                    current_state,
-                    selected_action_1,
-                    next_state,
-                    reward,
-                    self.mdp.get_discount_factor(),
+                    &Observation {
+                        state: current_state,
+                        action: selected_action_1,
+                        reward,
+                        next_state,
+                        next_possible_actions: next_possible_actions_1.clone(),
+                        terminal: self.mdp.is_terminal(next_state),
+                        discount_factor: self.mdp.get_discount_factor(),
+                    },
                     rng,
                 );
 
+                if let Some(buffer) = replay_1.as_deref_mut() {
+                    buffer.push(Experience {
+                        state: current_state,
+                        action: selected_action_1,
+                        reward,
+                        next_state,
+                        next_possible_actions: next_possible_actions_1.clone(),
+                        terminal: self.mdp.is_terminal(next_state),
+                    });
+                    buffer.replay(
+                        &mut self.agent_1,
+                        q_map_1,
+                        self.mdp.get_discount_factor(),
+                        rng,
+                    );
+                }
+
                 // println!("agent 2");
-                self.agent_2.step(
+                self.agent_2.observe(
+                    &mut (),
                     q_map_2,
-                    &next_possible_actions_2,
-                    current_state,
-                    selected_action_2,
-                    next_state,
-                    reward,
-                    self.mdp.get_discount_factor(),
+                    &Observation {
+                        state: current_state,
+                        action: selected_action_2,
+                        reward,
+                        next_state,
+                        next_possible_actions: next_possible_actions_2.clone(),
+                        terminal: self.mdp.is_terminal(next_state),
+                        discount_factor: self.mdp.get_discount_factor(),
+                    },
                     rng,
                 );
 
+                if let Some(buffer) = replay_2.as_deref_mut() {
+                    buffer.push(Experience {
+                        state: current_state,
+                        action: selected_action_2,
+                        reward,
+                        next_state,
+                        next_possible_actions: next_possible_actions_2.clone(),
+                        terminal: self.mdp.is_terminal(next_state),
+                    });
+                    buffer.replay(
+                        &mut self.agent_2,
+                        q_map_2,
+                        self.mdp.get_discount_factor(),
+                        rng,
+                    );
+                }
+
                 // the usual
                 current_state = next_state;
                 steps += 1;
@@ -492,7 +569,7 @@ impl<G: GenericStateActionAlgorithm> MAIntersectionRunnerSingleAgentRL<G> {
     }
 
     pub fn eval_greedy<R: Rng>(
-        &self,
+        &mut self,
         episodes: usize,
         q_map_1: &BTreeMap<(MAState, LightAction), f64>,
         q_map_2: &BTreeMap<(MAState, LightAction), f64>,
@@ -516,24 +593,18 @@ impl<G: GenericStateActionAlgorithm> MAIntersectionRunnerSingleAgentRL<G> {
                     MAIntersectionMdp::possible_light_actions(current_state.light_state_2);
 
                 // select action for intersection 1
-                let Some(selected_action_1) = epsilon_greedy_policy_ma(
-                    &possible_actions_1,
-                    q_map_1,
-                    current_state,
-                    self.agent_1.get_epsilon(),
-                    rng,
-                ) else {
+                let Some(selected_action_1) =
+                    self.agent_1
+                        .act(&mut (), q_map_1, current_state, &possible_actions_1, rng)
+                else {
                     panic!("no action possible")
                 };
 
                 // select action for intersection 2
-                let Some(selected_action_2) = epsilon_greedy_policy_ma(
-                    &possible_actions_2,
-                    q_map_2,
-                    current_state,
-                    self.agent_2.get_epsilon(),
-                    rng,
-                ) else {
+                let Some(selected_action_2) =
+                    self.agent_2
+                        .act(&mut (), q_map_2, current_state, &possible_actions_2, rng)
+                else {
                     panic!("no action possible")
                 };
 
@@ -553,12 +624,12 @@ impl<G: GenericStateActionAlgorithm> MAIntersectionRunnerSingleAgentRL<G> {
     }
 
     pub fn single_step<R: Rng>(
-        &self,
+        &mut self,
         state: MAState,
         q_map_1: &BTreeMap<(MAState, LightAction), f64>,
         q_map_2: &BTreeMap<(MAState, LightAction), f64>,
         rng: &mut R,
-    ) -> (MAState, f64) {
+    ) -> (Action, MAState, f64) {
         // retrieve possible actions for light 1
         let possible_actions_1 = MAIntersectionMdp::possible_light_actions(state.light_state_1);
 
@@ -566,24 +637,18 @@ impl<G: GenericStateActionAlgorithm> MAIntersectionRunnerSingleAgentRL<G> {
         let possible_actions_2 = MAIntersectionMdp::possible_light_actions(state.light_state_2);
 
         // select action for intersection 1
-        let Some(selected_action_1) = epsilon_greedy_policy_ma(
-            &possible_actions_1,
-            q_map_1,
-            state,
-            self.agent_1.get_epsilon(),
-            rng,
-        ) else {
+        let Some(selected_action_1) =
+            self.agent_1
+                .act(&mut (), q_map_1, state, &possible_actions_1, rng)
+        else {
             panic!("no action possible")
         };
 
         // select action for intersection 2
-        let Some(selected_action_2) = epsilon_greedy_policy_ma(
-            &possible_actions_2,
-            q_map_2,
-            state,
-            self.agent_2.get_epsilon(),
-            rng,
-        ) else {
+        let Some(selected_action_2) =
+            self.agent_2
+                .act(&mut (), q_map_2, state, &possible_actions_2, rng)
+        else {
             panic!("no action possible")
         };
 
@@ -592,18 +657,22 @@ impl<G: GenericStateActionAlgorithm> MAIntersectionRunnerSingleAgentRL<G> {
 
         let (next_state, reward) = self.mdp.perform_action((state, combined_action), rng);
 
-        (next_state, reward)
+        (combined_action, next_state, reward)
     }
 }
 
-pub struct MAIntersectionRunnerRegularRL<G: GenericStateActionAlgorithm> {
+pub struct MAIntersectionRunnerRegularRL<
+    G: TabularAlgorithm<IntersectionState, LightAction, Memory = ()>,
+> {
     pub mdp: MAIntersectionMdp,
     agent_1: G,
     agent_2: G,
     max_steps: usize,
 }
 
-impl<G: GenericStateActionAlgorithm> MAIntersectionRunnerRegularRL<G> {
+impl<G: TabularAlgorithm<IntersectionState, LightAction, Memory = ()>>
+    MAIntersectionRunnerRegularRL<G>
+{
     pub fn new(
         new_car_prob_ns_1: f64,
         new_car_prob_ew_1: f64,
@@ -631,11 +700,24 @@ impl<G: GenericStateActionAlgorithm> MAIntersectionRunnerRegularRL<G> {
     }
 
     pub fn run<R: Rng>(
-        &self,
+        &mut self,
         episodes: usize,
         q_map_1: &mut BTreeMap<(IntersectionState, LightAction), f64>,
         q_map_2: &mut BTreeMap<(IntersectionState, LightAction), f64>,
         rng: &mut R,
+    ) {
+        self.run_with_replay(episodes, q_map_1, q_map_2, None, None, rng);
+    }
+
+    // same as run, every agent stores its own experience in its buffer and replays it
+    pub fn run_with_replay<R: Rng>(
+        &mut self,
+        episodes: usize,
+        q_map_1: &mut BTreeMap<(IntersectionState, LightAction), f64>,
+        q_map_2: &mut BTreeMap<(IntersectionState, LightAction), f64>,
+        mut replay_1: Option<&mut ReplayBuffer<IntersectionState, LightAction>>,
+        mut replay_2: Option<&mut ReplayBuffer<IntersectionState, LightAction>>,
+        rng: &mut R,
     ) {
         for _ in 0..episodes {
             let mut current_state: MAState = self.mdp.get_initial_state(rng);
@@ -666,22 +748,22 @@ impl<G: GenericStateActionAlgorithm> MAIntersectionRunnerRegularRL<G> {
                     MAIntersectionMdp::possible_light_actions(current_state.light_state_2);
 
                 // select action for intersection 1
-                let Some(selected_action_1) = epsilon_greedy_policy_ma(
-                    &possible_actions_1,
+                let Some(selected_action_1) = self.agent_1.act(
+                    &mut (),
                     q_map_1,
                     intersection_state_1,
-                    self.agent_1.get_epsilon(),
+                    &possible_actions_1,
                     rng,
                 ) else {
                     panic!("no action possible")
                 };
 
                 // select action for intersection 2
-                let Some(selected_action_2) = epsilon_greedy_policy_ma(
-                    &possible_actions_2,
+                let Some(selected_action_2) = self.agent_2.act(
+                    &mut (),
                     q_map_2,
                     intersection_state_2,
-                    self.agent_2.get_epsilon(),
+                    &possible_actions_2,
                     rng,
                 ) else {
                     panic!("no action possible")
@@ -713,29 +795,71 @@ impl<G: GenericStateActionAlgorithm> MAIntersectionRunnerRegularRL<G> {
                     MAIntersectionMdp::possible_light_actions(next_state.light_state_2);
 
                 // println!("agent 1");
-                self.agent_1.step(
+                self.agent_1.observe(
+                    &mut (),
                     q_map_1,
-                    &next_possible_actions_1,
-                    intersection_state_1,
-                    selected_action_1,
-                    next_intersection_state_1,
-                    reward,
-                    self.mdp.get_discount_factor(),
+                    &Observation {
+                        state: intersection_state_1,
+                        action: selected_action_1,
+                        reward,
+                        next_state: next_intersection_state_1,
+                        next_possible_actions: next_possible_actions_1.clone(),
+                        terminal: self.mdp.is_terminal(next_state),
+                        discount_factor: self.mdp.get_discount_factor(),
+                    },
                     rng,
                 );
 
+                if let Some(buffer) = replay_1.as_deref_mut() {
+                    buffer.push(Experience {
+                        state: intersection_state_1,
+                        action: selected_action_1,
+                        reward,
+                        next_state: next_intersection_state_1,
+                        next_possible_actions: next_possible_actions_1.clone(),
+                        terminal: self.mdp.is_terminal(next_state),
+                    });
+                    buffer.replay(
+                        &mut self.agent_1,
+                        q_map_1,
+                        self.mdp.get_discount_factor(),
+                        rng,
+                    );
+                }
+
                 // println!("agent 2");
-                self.agent_2.step(
+                self.agent_2.observe(
+                    &mut (),
                     q_map_2,
-                    &next_possible_actions_2,
-                    intersection_state_2,
-                    selected_action_2,
-                    next_intersection_state_2,
-                    reward,
-                    self.mdp.get_discount_factor(),
+                    &Observation {
+                        state: intersection_state_2,
+                        action: selected_action_2,
+                        reward,
+                        next_state: next_intersection_state_2,
+                        next_possible_actions: next_possible_actions_2.clone(),
+                        terminal: self.mdp.is_terminal(next_state),
+                        discount_factor: self.mdp.get_discount_factor(),
+                    },
                     rng,
                 );
 
+                if let Some(buffer) = replay_2.as_deref_mut() {
+                    buffer.push(Experience {
+                        state: intersection_state_2,
+                        action: selected_action_2,
+                        reward,
+                        next_state: next_intersection_state_2,
+                        next_possible_actions: next_possible_actions_2.clone(),
+                        terminal: self.mdp.is_terminal(next_state),
+                    });
+                    buffer.replay(
+                        &mut self.agent_2,
+                        q_map_2,
+                        self.mdp.get_discount_factor(),
+                        rng,
+                    );
+                }
+
                 // the usual
                 current_state = next_state;
                 steps += 1;
@@ -799,7 +923,7 @@ impl<G: GenericStateActionAlgorithm> MAIntersectionRunnerRegularRL<G> {
     }
 
     pub fn eval_greedy<R: Rng>(
-        &self,
+        &mut self,
         episodes: usize,
         q_map_1: &BTreeMap<(IntersectionState, LightAction), f64>,
         q_map_2: &BTreeMap<(IntersectionState, LightAction), f64>,
@@ -834,22 +958,22 @@ impl<G: GenericStateActionAlgorithm> MAIntersectionRunnerRegularRL<G> {
                     MAIntersectionMdp::possible_light_actions(current_state.light_state_2);
 
                 // select action for intersection 1
-                let Some(selected_action_1) = epsilon_greedy_policy_ma(
-                    &possible_actions_1,
+                let Some(selected_action_1) = self.agent_1.act(
+                    &mut (),
                     q_map_1,
                     intersection_state_1,
-                    self.agent_1.get_epsilon(),
+                    &possible_actions_1,
                     rng,
                 ) else {
                     panic!("no action possible")
                 };
 
                 // select action for intersection 2
-                let Some(selected_action_2) = epsilon_greedy_policy_ma(
-                    &possible_actions_2,
+                let Some(selected_action_2) = self.agent_2.act(
+                    &mut (),
                     q_map_2,
                     intersection_state_2,
-                    self.agent_2.get_epsilon(),
+                    &possible_actions_2,
                     rng,
                 ) else {
                     panic!("no action possible")
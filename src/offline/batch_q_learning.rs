@@ -0,0 +1,93 @@
+use std::collections::BTreeMap;
+
+use rand::{seq::SliceRandom, Rng};
+
+use crate::mdp::{GenericAction, GenericState};
+
+use super::{actions_by_state, dataset_q_map, max_q, mean_targets, Dataset};
+
+// q-learning on the logged transitions, every sweep visits the state action pairs in random order and
+// moves the q-value of a pair by alpha towards the mean q-learning target of its transitions. The
+// targets use the latest q-values, so unlike fitted q iteration a sweep already builds on the pairs
+// updated before. For alpha in (0, 1] this converges to the fixed point of the empirical model, it
+// stops once the largest bellman residual after a sweep is below the tolerance, which does not
+// depend on alpha. Returns the q_map and the number of sweeps, max_sweeps if it did not converge.
+pub fn batch_q_learning<S: GenericState, A: GenericAction, R: Rng>(
+    dataset: &Dataset<S, A>,
+    alpha: f64,
+    discount_factor: f64,
+    tolerance: f64,
+    max_sweeps: usize,
+    rng: &mut R,
+) -> (BTreeMap<(S, A), f64>, usize) {
+    let mut q_map = dataset_q_map(dataset);
+    let sweeps = batch_q_learning_with_q_map(
+        dataset,
+        alpha,
+        discount_factor,
+        tolerance,
+        max_sweeps,
+        rng,
+        &mut q_map,
+    );
+    (q_map, sweeps)
+}
+
+pub fn batch_q_learning_with_q_map<S: GenericState, A: GenericAction, R: Rng>(
+    dataset: &Dataset<S, A>,
+    alpha: f64,
+    discount_factor: f64,
+    tolerance: f64,
+    max_sweeps: usize,
+    rng: &mut R,
+    q_map: &mut BTreeMap<(S, A), f64>,
+) -> usize {
+    dataset
+        .transitions
+        .iter()
+        .for_each(|(state, action, _, _, _)| {
+            q_map.entry((*state, *action)).or_insert(0.0);
+        });
+    let actions = actions_by_state(q_map);
+    let mut pairs: BTreeMap<(S, A), Vec<usize>> = BTreeMap::new();
+    dataset
+        .transitions
+        .iter()
+        .enumerate()
+        .for_each(|(index, (state, action, _, _, _))| {
+            pairs.entry((*state, *action)).or_default().push(index)
+        });
+    let mut order: Vec<((S, A), Vec<usize>)> = pairs.into_iter().collect();
+
+    for sweep in 1..=max_sweeps {
+        order.shuffle(rng);
+
+        for (state_action, indices) in order.iter() {
+            let mean_target = indices
+                .iter()
+                .map(|index| {
+                    let (_, _, reward, next_state, done) = dataset.transitions[*index];
+                    if done {
+                        reward
+                    } else {
+                        reward + discount_factor * max_q(q_map, &actions, next_state)
+                    }
+                })
+                .sum::<f64>()
+                / indices.len() as f64;
+
+            let current_q = q_map.get_mut(state_action).expect("No qmap entry found");
+            *current_q += alpha * (mean_target - *current_q);
+        }
+
+        let residual = mean_targets(dataset, q_map, &actions, discount_factor)
+            .into_iter()
+            .map(|(sa, target)| (target - q_map[&sa]).abs())
+            .fold(0.0, f64::max);
+        if residual < tolerance {
+            return sweep;
+        }
+    }
+
+    max_sweeps
+}
@@ -0,0 +1,57 @@
+use std::collections::BTreeMap;
+
+use crate::mdp::{GenericAction, GenericState};
+
+use super::{actions_by_state, dataset_q_map, mean_targets, Dataset};
+
+// tabular fitted Q iteration, the regression step is the mean target of every state action pair
+// in the data. Returns the q_map and the number of iterations.
+pub fn fitted_q_iteration<S: GenericState, A: GenericAction>(
+    dataset: &Dataset<S, A>,
+    discount_factor: f64,
+    tolerance: f64,
+    max_iterations: usize,
+) -> (BTreeMap<(S, A), f64>, usize) {
+    let mut q_map = dataset_q_map(dataset);
+    let iterations = fitted_q_iteration_with_q_map(
+        dataset,
+        discount_factor,
+        tolerance,
+        max_iterations,
+        &mut q_map,
+    );
+    (q_map, iterations)
+}
+
+// pairs of the q_map without data keep their value but are still used for the max over next
+// states, so the q_map can be initialised from an mdp
+pub fn fitted_q_iteration_with_q_map<S: GenericState, A: GenericAction>(
+    dataset: &Dataset<S, A>,
+    discount_factor: f64,
+    tolerance: f64,
+    max_iterations: usize,
+    q_map: &mut BTreeMap<(S, A), f64>,
+) -> usize {
+    dataset
+        .transitions
+        .iter()
+        .for_each(|(state, action, _, _, _)| {
+            q_map.entry((*state, *action)).or_insert(0.0);
+        });
+    let actions = actions_by_state(q_map);
+
+    for iteration in 1..=max_iterations {
+        let mut max_change: f64 = 0.0;
+        for (sa, new_q) in mean_targets(dataset, q_map, &actions, discount_factor) {
+            let q = q_map.get_mut(&sa).expect("No qmap entry found");
+            max_change = max_change.max((new_q - *q).abs());
+            *q = new_q;
+        }
+
+        if max_change < tolerance {
+            return iteration;
+        }
+    }
+
+    max_iterations
+}
@@ -0,0 +1,273 @@
+pub mod batch_q_learning;
+pub mod fitted_q_iteration;
+pub mod model;
+pub mod ope;
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+use anyhow::Context;
+use rand::Rng;
+
+use crate::{
+    mdp::{GenericAction, GenericMdp, GenericState, IndexAction, IndexState, Reward},
+    policies::random_policy,
+};
+
+// (state, action, reward, next state, done)
+pub type LoggedTransition<S, A> = (S, A, Reward, S, bool);
+
+#[derive(Clone, Debug, Default)]
+pub struct Dataset<S: GenericState, A: GenericAction> {
+    pub transitions: Vec<LoggedTransition<S, A>>,
+}
+
+impl<S: GenericState, A: GenericAction> Dataset<S, A> {
+    pub fn new() -> Self {
+        Self {
+            transitions: vec![],
+        }
+    }
+
+    pub fn push(&mut self, transition: LoggedTransition<S, A>) {
+        self.transitions.push(transition);
+    }
+
+    pub fn len(&self) -> usize {
+        self.transitions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transitions.is_empty()
+    }
+
+    // logs transitions of a uniformly random behaviour policy
+    pub fn collect_random<M: GenericMdp<S, A>, R: Rng>(
+        mdp: &M,
+        episodes: usize,
+        max_steps: usize,
+        rng: &mut R,
+    ) -> Self {
+        let mut dataset = Self::new();
+
+        for _ in 0..episodes {
+            let mut current_state = mdp.get_initial_state(rng);
+            let mut steps = 0;
+
+            while !mdp.is_terminal(current_state) && steps < max_steps {
+                let Some(action) = random_policy(mdp, current_state, rng) else {
+                    break;
+                };
+                let (next_state, reward) = mdp.perform_action((current_state, action), rng);
+                dataset.push((
+                    current_state,
+                    action,
+                    reward,
+                    next_state,
+                    mdp.is_terminal(next_state),
+                ));
+
+                current_state = next_state;
+                steps += 1;
+            }
+        }
+
+        dataset
+    }
+
+    // reads a csv file with a header, parse converts a single record to a transition
+    pub fn from_csv_with<
+        P: AsRef<Path>,
+        F: Fn(&csv::StringRecord) -> anyhow::Result<LoggedTransition<S, A>>,
+    >(
+        path: P,
+        parse: F,
+    ) -> anyhow::Result<Self> {
+        let mut reader = csv::Reader::from_path(path)?;
+        let mut dataset = Self::new();
+
+        for (line, record) in reader.records().enumerate() {
+            let record = record?;
+            let transition =
+                parse(&record).with_context(|| format!("invalid record {}", line + 1))?;
+            dataset.push(transition);
+        }
+
+        Ok(dataset)
+    }
+
+    pub fn to_csv_with<P: AsRef<Path>, F: Fn(&LoggedTransition<S, A>) -> Vec<String>>(
+        &self,
+        path: P,
+        header: &[&str],
+        format: F,
+    ) -> anyhow::Result<()> {
+        let mut writer = csv::Writer::from_path(path)?;
+        writer.write_record(header)?;
+        for transition in self.transitions.iter() {
+            writer.write_record(format(transition))?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn state_actions(&self) -> BTreeSet<(S, A)> {
+        self.transitions
+            .iter()
+            .map(|(state, action, _, _, _)| (*state, *action))
+            .collect()
+    }
+
+    // counts the samples of every given state action pair, pairs without data are reported as
+    // missing. Pairs that only appear in the data are counted as well.
+    pub fn coverage(&self, state_actions: &[(S, A)]) -> Coverage<S, A> {
+        let mut counts: BTreeMap<(S, A), usize> = state_actions.iter().map(|sa| (*sa, 0)).collect();
+
+        self.transitions
+            .iter()
+            .for_each(|(state, action, _, _, _)| {
+                *counts.entry((*state, *action)).or_insert(0) += 1
+            });
+
+        let missing = counts
+            .iter()
+            .filter(|(_, count)| **count == 0)
+            .map(|(sa, _)| *sa)
+            .collect();
+
+        Coverage { counts, missing }
+    }
+}
+
+const CSV_HEADER: [&str; 5] = ["state", "action", "reward", "next_state", "done"];
+
+impl Dataset<IndexState, IndexAction> {
+    // expects the columns state, action, reward, next_state, done
+    pub fn from_csv<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        Self::from_csv_with(path, |record| {
+            let field = |index: usize| {
+                record
+                    .get(index)
+                    .map(str::trim)
+                    .ok_or_else(|| anyhow::anyhow!("missing column {}", CSV_HEADER[index]))
+            };
+
+            Ok((
+                IndexState(field(0)?.parse()?),
+                IndexAction(field(1)?.parse()?),
+                field(2)?.parse()?,
+                IndexState(field(3)?.parse()?),
+                field(4)?.parse()?,
+            ))
+        })
+    }
+
+    pub fn to_csv<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        self.to_csv_with(
+            path,
+            &CSV_HEADER,
+            |(state, action, reward, next_state, done)| {
+                vec![
+                    state.0.to_string(),
+                    action.0.to_string(),
+                    reward.to_string(),
+                    next_state.0.to_string(),
+                    done.to_string(),
+                ]
+            },
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Coverage<S: GenericState, A: GenericAction> {
+    pub counts: BTreeMap<(S, A), usize>,
+    pub missing: Vec<(S, A)>,
+}
+
+impl<S: GenericState, A: GenericAction> Coverage<S, A> {
+    // share of state action pairs with at least one sample
+    pub fn fraction(&self) -> f64 {
+        if self.counts.is_empty() {
+            return 0.0;
+        }
+        1.0 - self.missing.len() as f64 / self.counts.len() as f64
+    }
+
+    pub fn print_report(&self) {
+        println!(
+            "coverage: {}/{} state action pairs ({:.1}%)",
+            self.counts.len() - self.missing.len(),
+            self.counts.len(),
+            self.fraction() * 100.0
+        );
+        self.missing
+            .iter()
+            .for_each(|sa| println!("missing: {:?}", sa));
+    }
+}
+
+// initial q_map for the offline learners, every pair in the data starts at 0
+pub fn dataset_q_map<S: GenericState, A: GenericAction>(
+    dataset: &Dataset<S, A>,
+) -> BTreeMap<(S, A), f64> {
+    dataset
+        .state_actions()
+        .into_iter()
+        .map(|sa| (sa, 0.0))
+        .collect()
+}
+
+// actions available in every state of the q_map, used for the max over next states
+fn actions_by_state<S: GenericState, A: GenericAction>(
+    q_map: &BTreeMap<(S, A), f64>,
+) -> BTreeMap<S, Vec<A>> {
+    let mut actions: BTreeMap<S, Vec<A>> = BTreeMap::new();
+    q_map
+        .keys()
+        .for_each(|(state, action)| actions.entry(*state).or_default().push(*action));
+    actions
+}
+
+// next states without any known action have a value of 0
+fn max_q<S: GenericState, A: GenericAction>(
+    q_map: &BTreeMap<(S, A), f64>,
+    actions: &BTreeMap<S, Vec<A>>,
+    state: S,
+) -> f64 {
+    actions
+        .get(&state)
+        .and_then(|actions| {
+            actions
+                .iter()
+                .map(|action| q_map[&(state, *action)])
+                .reduce(f64::max)
+        })
+        .unwrap_or(0.0)
+}
+
+// empirical bellman backup of the q_map, the mean target of every state action pair in the data
+fn mean_targets<S: GenericState, A: GenericAction>(
+    dataset: &Dataset<S, A>,
+    q_map: &BTreeMap<(S, A), f64>,
+    actions: &BTreeMap<S, Vec<A>>,
+    discount_factor: f64,
+) -> BTreeMap<(S, A), f64> {
+    let mut targets: BTreeMap<(S, A), (f64, usize)> = BTreeMap::new();
+    for (state, action, reward, next_state, done) in dataset.transitions.iter() {
+        let target = if *done {
+            *reward
+        } else {
+            reward + discount_factor * max_q(q_map, actions, *next_state)
+        };
+        let entry = targets.entry((*state, *action)).or_insert((0.0, 0));
+        entry.0 += target;
+        entry.1 += 1;
+    }
+    targets
+        .into_iter()
+        .map(|(sa, (sum, count))| (sa, sum / count as f64))
+        .collect()
+}
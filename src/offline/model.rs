@@ -0,0 +1,220 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    algorithms::value_iteration::{q_map_from_values, value_iteration},
+    mdp::{GenericAction, GenericState, MapMdp, Reward},
+    persistence::nested_entries,
+};
+
+use super::{Dataset, LoggedTransition};
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct OutcomeStats {
+    pub count: usize,
+    pub reward_sum: f64,
+    pub reward_sq_sum: f64,
+}
+
+impl OutcomeStats {
+    fn add(&mut self, reward: Reward) {
+        self.count += 1;
+        self.reward_sum += reward;
+        self.reward_sq_sum += reward * reward;
+    }
+
+    pub fn mean_reward(&self) -> f64 {
+        self.reward_sum / self.count as f64
+    }
+}
+
+// maximum likelihood model of the observed transitions, rewards are averaged per
+// (state, action, next state) since MapMdp attaches rewards to outcomes
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "S: Serialize, A: Serialize",
+    deserialize = "S: Deserialize<'de>, A: Deserialize<'de>"
+))]
+pub struct EstimatedModel<S: GenericState, A: GenericAction> {
+    #[serde(with = "nested_entries")]
+    pub outcomes: BTreeMap<(S, A), BTreeMap<S, OutcomeStats>>,
+    pub terminal_states: BTreeSet<S>,
+}
+
+impl<S: GenericState, A: GenericAction> Default for EstimatedModel<S, A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: GenericState, A: GenericAction> EstimatedModel<S, A> {
+    pub fn new() -> Self {
+        Self {
+            outcomes: BTreeMap::new(),
+            terminal_states: BTreeSet::new(),
+        }
+    }
+
+    pub fn from_dataset(dataset: &Dataset<S, A>) -> Self {
+        let mut model = Self::new();
+        dataset.transitions.iter().for_each(|t| model.add(*t));
+        model
+    }
+
+    pub fn from_trajectories(trajectories: &[Vec<LoggedTransition<S, A>>]) -> Self {
+        let mut model = Self::new();
+        trajectories
+            .iter()
+            .flatten()
+            .for_each(|transition| model.add(*transition));
+        model
+    }
+
+    pub fn add(&mut self, (state, action, reward, next_state, done): LoggedTransition<S, A>) {
+        self.outcomes
+            .entry((state, action))
+            .or_default()
+            .entry(next_state)
+            .or_default()
+            .add(reward);
+
+        if done {
+            self.terminal_states.insert(next_state);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.outcomes.clear();
+        self.terminal_states.clear();
+    }
+
+    pub fn count(&self, state_action: (S, A)) -> usize {
+        self.outcomes
+            .get(&state_action)
+            .map(|outcomes| outcomes.values().map(|stats| stats.count).sum())
+            .unwrap_or(0)
+    }
+
+    pub fn counts(&self) -> BTreeMap<(S, A), usize> {
+        self.outcomes
+            .keys()
+            .map(|sa| (*sa, self.count(*sa)))
+            .collect()
+    }
+
+    pub fn transition_probability(&self, state_action: (S, A), next_state: S) -> Option<f64> {
+        let total = self.count(state_action);
+        let stats = self.outcomes.get(&state_action)?;
+        Some(stats.get(&next_state).map_or(0.0, |s| s.count as f64) / total as f64)
+    }
+
+    // expected immediate reward of a state action pair
+    pub fn mean_reward(&self, state_action: (S, A)) -> Option<f64> {
+        let outcomes = self.outcomes.get(&state_action)?;
+        let (sum, count) = outcomes.values().fold((0.0, 0), |(sum, count), s| {
+            (sum + s.reward_sum, count + s.count)
+        });
+        Some(sum / count as f64)
+    }
+
+    // wilson score interval of a transition probability, z is the normal quantile (1.96 for 95%)
+    pub fn probability_interval(
+        &self,
+        state_action: (S, A),
+        next_state: S,
+        z: f64,
+    ) -> Option<(f64, f64)> {
+        let p = self.transition_probability(state_action, next_state)?;
+        let n = self.count(state_action) as f64;
+
+        let denom = 1.0 + z * z / n;
+        let center = (p + z * z / (2.0 * n)) / denom;
+        let half_width = z * (p * (1.0 - p) / n + z * z / (4.0 * n * n)).sqrt() / denom;
+        Some((
+            (center - half_width).max(0.0),
+            (center + half_width).min(1.0),
+        ))
+    }
+
+    // normal approximation interval of the mean reward, collapses to the mean for a single sample
+    pub fn reward_interval(&self, state_action: (S, A), z: f64) -> Option<(f64, f64)> {
+        let outcomes = self.outcomes.get(&state_action)?;
+        let (sum, sq_sum, count) = outcomes.values().fold((0.0, 0.0, 0), |acc, s| {
+            (
+                acc.0 + s.reward_sum,
+                acc.1 + s.reward_sq_sum,
+                acc.2 + s.count,
+            )
+        });
+        let n = count as f64;
+        let mean = sum / n;
+
+        if count < 2 {
+            return Some((mean, mean));
+        }
+
+        let variance = ((sq_sum - n * mean * mean) / (n - 1.0)).max(0.0);
+        let half_width = z * (variance / n).sqrt();
+        Some((mean - half_width, mean + half_width))
+    }
+
+    pub fn to_mdp(&self, discount_factor: f64, initial_state: S) -> MapMdp<S, A> {
+        let mut mdp = MapMdp::new(discount_factor, initial_state);
+
+        for (state_action, outcomes) in self.outcomes.iter() {
+            let total: usize = outcomes.values().map(|stats| stats.count).sum();
+            let transitions = outcomes
+                .iter()
+                .map(|(next_state, stats)| {
+                    (
+                        stats.count as f64 / total as f64,
+                        *next_state,
+                        stats.mean_reward(),
+                    )
+                })
+                .collect();
+            mdp.add_transition_vector(*state_action, transitions)
+                .expect("outcomes contain every state action pair once");
+        }
+
+        self.terminal_states
+            .iter()
+            .for_each(|state| mdp.add_terminal_state(*state));
+
+        mdp
+    }
+
+    // certainty equivalence planning, the estimated model is solved as if it was the true one
+    pub fn plan(
+        &self,
+        discount_factor: f64,
+        initial_state: S,
+        tolerance: f64,
+    ) -> BTreeMap<(S, A), f64> {
+        let mdp = self.to_mdp(discount_factor, initial_state);
+        let values = value_iteration(&mdp, tolerance);
+        q_map_from_values(&mdp, &values)
+    }
+}
+
+// builds the maximum likelihood mdp of the observed episodes, the initial state is the most
+// frequent first state
+pub fn estimate_mdp<S: GenericState, A: GenericAction>(
+    trajectories: &[Vec<LoggedTransition<S, A>>],
+    discount_factor: f64,
+) -> anyhow::Result<MapMdp<S, A>> {
+    let mut first_states: BTreeMap<S, usize> = BTreeMap::new();
+    trajectories
+        .iter()
+        .filter_map(|trajectory| trajectory.first())
+        .for_each(|(state, _, _, _, _)| *first_states.entry(*state).or_insert(0) += 1);
+
+    let initial_state = first_states
+        .iter()
+        .max_by_key(|(_, count)| **count)
+        .map(|(state, _)| *state)
+        .ok_or_else(|| anyhow::anyhow!("no transitions to estimate an mdp from"))?;
+
+    Ok(EstimatedModel::from_trajectories(trajectories).to_mdp(discount_factor, initial_state))
+}
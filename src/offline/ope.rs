@@ -0,0 +1,372 @@
+use std::collections::BTreeMap;
+
+use rand::Rng;
+
+use crate::{
+    mdp::{GenericAction, GenericMdp, GenericState, Reward},
+    policies::Policy,
+};
+
+// off-policy evaluation, estimates the value of a target policy from episodes logged by a
+// behaviour policy. The target policy is queried through action_probabilities only, so it has to
+// be memoryless, the behaviour policy may keep state while logging.
+
+#[derive(Clone, Debug)]
+pub struct LoggedStep<S: GenericState, A: GenericAction> {
+    pub state: S,
+    pub action: A,
+    pub reward: Reward,
+    pub possible_actions: Vec<A>,
+    // probability of the logging policy to select the action
+    pub behaviour_probability: f64,
+}
+
+pub type LoggedEpisode<S, A> = Vec<LoggedStep<S, A>>;
+
+// runs the behaviour policy on the mdp and records the probability of every selected action
+pub fn collect_episodes<M, S, A, P, R>(
+    mdp: &M,
+    behaviour: &mut P,
+    episodes: usize,
+    max_steps: usize,
+    rng: &mut R,
+) -> Vec<LoggedEpisode<S, A>>
+where
+    M: GenericMdp<S, A>,
+    S: GenericState,
+    A: GenericAction,
+    P: Policy<S, A>,
+    R: Rng,
+{
+    (0..episodes)
+        .map(|_| {
+            behaviour.begin_episode();
+            let mut episode = vec![];
+            let mut current_state = mdp.get_initial_state(rng);
+
+            while !mdp.is_terminal(current_state) && episode.len() < max_steps {
+                let possible_actions = mdp.get_possible_actions(current_state);
+                // the probabilities describe the next select_action call
+                let probabilities =
+                    behaviour.action_probabilities(current_state, &possible_actions);
+                let Some(action) = behaviour.select_action(current_state, &possible_actions, rng)
+                else {
+                    break;
+                };
+                let behaviour_probability = probabilities
+                    .iter()
+                    .filter(|(a, _)| *a == action)
+                    .map(|(_, prob)| *prob)
+                    .sum();
+                let (next_state, reward) = mdp.perform_action((current_state, action), rng);
+
+                episode.push(LoggedStep {
+                    state: current_state,
+                    action,
+                    reward,
+                    possible_actions,
+                    behaviour_probability,
+                });
+                current_state = next_state;
+            }
+            episode
+        })
+        .collect()
+}
+
+// importance ratio of every step, pi(a|s) / mu(a|s)
+fn ratios<S: GenericState, A: GenericAction, P: Policy<S, A>>(
+    episode: &[LoggedStep<S, A>],
+    target: &P,
+) -> Vec<f64> {
+    episode
+        .iter()
+        .map(|step| {
+            assert!(
+                step.behaviour_probability > 0.0,
+                "logged action {:?} in state {:?} has behaviour probability 0",
+                step.action,
+                step.state
+            );
+            target.action_probability(step.state, step.action, &step.possible_actions)
+                / step.behaviour_probability
+        })
+        .collect()
+}
+
+fn discounted_return<S: GenericState, A: GenericAction>(
+    episode: &[LoggedStep<S, A>],
+    discount_factor: f64,
+) -> f64 {
+    episode
+        .iter()
+        .rev()
+        .fold(0.0, |ret, step| step.reward + discount_factor * ret)
+}
+
+// ordinary importance sampling, unbiased but with high variance
+pub fn importance_sampling<S: GenericState, A: GenericAction, P: Policy<S, A>>(
+    episodes: &[LoggedEpisode<S, A>],
+    target: &P,
+    discount_factor: f64,
+) -> f64 {
+    if episodes.is_empty() {
+        return 0.0;
+    }
+    episodes
+        .iter()
+        .map(|episode| {
+            ratios(episode, target).iter().product::<f64>()
+                * discounted_return(episode, discount_factor)
+        })
+        .sum::<f64>()
+        / episodes.len() as f64
+}
+
+// weighted importance sampling, normalises by the sum of the episode weights
+pub fn weighted_importance_sampling<S: GenericState, A: GenericAction, P: Policy<S, A>>(
+    episodes: &[LoggedEpisode<S, A>],
+    target: &P,
+    discount_factor: f64,
+) -> f64 {
+    let (weighted, total_weight) = episodes.iter().fold((0.0, 0.0), |(sum, total), episode| {
+        let weight: f64 = ratios(episode, target).iter().product();
+        (
+            sum + weight * discounted_return(episode, discount_factor),
+            total + weight,
+        )
+    });
+    if total_weight > 0.0 {
+        weighted / total_weight
+    } else {
+        0.0
+    }
+}
+
+// per-decision importance sampling, every reward is weighted by the ratios up to its step only
+pub fn per_decision_importance_sampling<S: GenericState, A: GenericAction, P: Policy<S, A>>(
+    episodes: &[LoggedEpisode<S, A>],
+    target: &P,
+    discount_factor: f64,
+) -> f64 {
+    if episodes.is_empty() {
+        return 0.0;
+    }
+    episodes
+        .iter()
+        .map(|episode| {
+            let mut weight = 1.0;
+            let mut discount = 1.0;
+            episode
+                .iter()
+                .zip(ratios(episode, target))
+                .map(|(step, ratio)| {
+                    weight *= ratio;
+                    let value = discount * weight * step.reward;
+                    discount *= discount_factor;
+                    value
+                })
+                .sum::<f64>()
+        })
+        .sum::<f64>()
+        / episodes.len() as f64
+}
+
+// tabular fitted Q evaluation. Like fitted Q iteration, but the targets use the expected value of
+// the target policy in the next state instead of the max. The last step of an episode counts as
+// terminal.
+pub fn fitted_q_evaluation<S: GenericState, A: GenericAction, P: Policy<S, A>>(
+    episodes: &[LoggedEpisode<S, A>],
+    target: &P,
+    discount_factor: f64,
+    tolerance: f64,
+    max_iterations: usize,
+) -> BTreeMap<(S, A), f64> {
+    let mut q_map: BTreeMap<(S, A), f64> = episodes
+        .iter()
+        .flatten()
+        .map(|step| ((step.state, step.action), 0.0))
+        .collect();
+
+    for _ in 0..max_iterations {
+        let mut targets: BTreeMap<(S, A), (f64, usize)> = BTreeMap::new();
+        for episode in episodes {
+            for (t, step) in episode.iter().enumerate() {
+                let next_value = episode
+                    .get(t + 1)
+                    .map(|next| state_value(&q_map, target, next))
+                    .unwrap_or(0.0);
+                let entry = targets.entry((step.state, step.action)).or_insert((0.0, 0));
+                entry.0 += step.reward + discount_factor * next_value;
+                entry.1 += 1;
+            }
+        }
+
+        let mut max_change: f64 = 0.0;
+        for (sa, (sum, count)) in targets {
+            let new_q = sum / count as f64;
+            let q = q_map.get_mut(&sa).expect("No qmap entry found");
+            max_change = max_change.max((new_q - *q).abs());
+            *q = new_q;
+        }
+        if max_change < tolerance {
+            break;
+        }
+    }
+
+    q_map
+}
+
+// expected q value of the target policy in the state of the step, pairs without data count as 0
+fn state_value<S: GenericState, A: GenericAction, P: Policy<S, A>>(
+    q_map: &BTreeMap<(S, A), f64>,
+    target: &P,
+    step: &LoggedStep<S, A>,
+) -> f64 {
+    target
+        .action_probabilities(step.state, &step.possible_actions)
+        .iter()
+        .map(|(action, prob)| prob * q_map.get(&(step.state, *action)).unwrap_or(&0.0))
+        .sum()
+}
+
+// direct method, the value of the initial states under the fitted q_map
+pub fn direct_method<S: GenericState, A: GenericAction, P: Policy<S, A>>(
+    episodes: &[LoggedEpisode<S, A>],
+    target: &P,
+    q_map: &BTreeMap<(S, A), f64>,
+) -> f64 {
+    if episodes.is_empty() {
+        return 0.0;
+    }
+    episodes
+        .iter()
+        .filter_map(|episode| episode.first())
+        .map(|step| state_value(q_map, target, step))
+        .sum::<f64>()
+        / episodes.len() as f64
+}
+
+// doubly robust estimator (Jiang and Li 2016), corrects the model value with per-decision
+// importance weighted residuals. Unbiased if either the ratios or the q_map are correct.
+pub fn doubly_robust<S: GenericState, A: GenericAction, P: Policy<S, A>>(
+    episodes: &[LoggedEpisode<S, A>],
+    target: &P,
+    q_map: &BTreeMap<(S, A), f64>,
+    discount_factor: f64,
+) -> f64 {
+    if episodes.is_empty() {
+        return 0.0;
+    }
+    episodes
+        .iter()
+        .map(|episode| {
+            episode
+                .iter()
+                .zip(ratios(episode, target))
+                .rev()
+                .fold(0.0, |value, (step, ratio)| {
+                    let q = *q_map.get(&(step.state, step.action)).unwrap_or(&0.0);
+                    state_value(q_map, target, step)
+                        + ratio * (step.reward + discount_factor * value - q)
+                })
+        })
+        .sum::<f64>()
+        / episodes.len() as f64
+}
+
+// weighted doubly robust estimator (Thomas and Brunskill 2016), the per-decision weights are
+// normalised over all episodes at every step. Finished episodes keep their last weight.
+pub fn weighted_doubly_robust<S: GenericState, A: GenericAction, P: Policy<S, A>>(
+    episodes: &[LoggedEpisode<S, A>],
+    target: &P,
+    q_map: &BTreeMap<(S, A), f64>,
+    discount_factor: f64,
+) -> f64 {
+    let horizon = episodes
+        .iter()
+        .map(|episode| episode.len())
+        .max()
+        .unwrap_or(0);
+    // cumulative ratios of every episode, padded to the horizon
+    let weights: Vec<Vec<f64>> = episodes
+        .iter()
+        .map(|episode| {
+            let mut weight = 1.0;
+            let mut cumulative: Vec<f64> = ratios(episode, target)
+                .iter()
+                .map(|ratio| {
+                    weight *= ratio;
+                    weight
+                })
+                .collect();
+            cumulative.resize(horizon, weight);
+            cumulative
+        })
+        .collect();
+    let totals: Vec<f64> = (0..horizon)
+        .map(|t| weights.iter().map(|w| w[t]).sum())
+        .collect();
+    let normalised = |i: usize, t: usize| -> f64 {
+        if totals[t] > 0.0 {
+            weights[i][t] / totals[t]
+        } else {
+            0.0
+        }
+    };
+
+    let mut estimate = 0.0;
+    for (i, episode) in episodes.iter().enumerate() {
+        let mut discount = 1.0;
+        for (t, step) in episode.iter().enumerate() {
+            let prev_weight = if t == 0 {
+                1.0 / episodes.len() as f64
+            } else {
+                normalised(i, t - 1)
+            };
+            let q = *q_map.get(&(step.state, step.action)).unwrap_or(&0.0);
+            estimate += discount
+                * (normalised(i, t) * (step.reward - q)
+                    + prev_weight * state_value(q_map, target, step));
+            discount *= discount_factor;
+        }
+    }
+    estimate
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct OpeEstimates {
+    pub importance_sampling: f64,
+    pub weighted_importance_sampling: f64,
+    pub per_decision_importance_sampling: f64,
+    pub direct_method: f64,
+    pub doubly_robust: f64,
+    pub weighted_doubly_robust: f64,
+}
+
+// runs every estimator, the model based ones share one fitted Q evaluation
+pub fn estimate_all<S: GenericState, A: GenericAction, P: Policy<S, A>>(
+    episodes: &[LoggedEpisode<S, A>],
+    target: &P,
+    discount_factor: f64,
+    tolerance: f64,
+    max_iterations: usize,
+) -> OpeEstimates {
+    let q_map = fitted_q_evaluation(episodes, target, discount_factor, tolerance, max_iterations);
+    OpeEstimates {
+        importance_sampling: importance_sampling(episodes, target, discount_factor),
+        weighted_importance_sampling: weighted_importance_sampling(
+            episodes,
+            target,
+            discount_factor,
+        ),
+        per_decision_importance_sampling: per_decision_importance_sampling(
+            episodes,
+            target,
+            discount_factor,
+        ),
+        direct_method: direct_method(episodes, target, &q_map),
+        doubly_robust: doubly_robust(episodes, target, &q_map, discount_factor),
+        weighted_doubly_robust: weighted_doubly_robust(episodes, target, &q_map, discount_factor),
+    }
+}
@@ -0,0 +1,243 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+use anyhow::{bail, Context};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    mdp::{GenericAction, GenericState},
+    policies::TabularPolicy,
+};
+
+// bump whenever the layout of a saved file changes, files of other versions are rejected on load
+pub const FORMAT_VERSION: u32 = 1;
+
+const Q_MAP_KIND: &str = "q_map";
+const POLICY_KIND: &str = "policy";
+const LEARNER_KIND: &str = "learner";
+
+// describes how the saved data was produced
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    pub environment: String,
+    pub parameters: BTreeMap<String, f64>,
+    pub seed: Option<u64>,
+    pub episodes: usize,
+}
+
+impl Metadata {
+    pub fn new(environment: &str, episodes: usize) -> Self {
+        Self {
+            environment: environment.to_owned(),
+            episodes,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_parameter(mut self, name: &str, value: f64) -> Self {
+        self.parameters.insert(name.to_owned(), value);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+}
+
+type QMap<S, A> = BTreeMap<(S, A), f64>;
+// q_maps are stored as a list of entries, see entries below
+type QMapEntries<S, A> = Vec<((S, A), f64)>;
+
+#[derive(Serialize, Deserialize)]
+struct Envelope<T> {
+    version: u32,
+    kind: String,
+    metadata: Metadata,
+    data: T,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LearnerData<T, Q> {
+    learner: T,
+    q_map: Q,
+}
+
+fn save<T: Serialize>(
+    path: impl AsRef<Path>,
+    kind: &str,
+    metadata: &Metadata,
+    data: T,
+) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let writer = BufWriter::new(
+        File::create(path).with_context(|| format!("could not create {}", path.display()))?,
+    );
+    let envelope = Envelope {
+        version: FORMAT_VERSION,
+        kind: kind.to_owned(),
+        metadata: metadata.clone(),
+        data,
+    };
+    serde_json::to_writer(writer, &envelope)?;
+    Ok(())
+}
+
+fn load<T: DeserializeOwned>(path: impl AsRef<Path>, kind: &str) -> anyhow::Result<(Metadata, T)> {
+    let path = path.as_ref();
+    let reader = BufReader::new(
+        File::open(path).with_context(|| format!("could not open {}", path.display()))?,
+    );
+
+    // check the header before interpreting the data
+    let value: serde_json::Value = serde_json::from_reader(reader)?;
+    let version = value.get("version").and_then(|v| v.as_u64());
+    if version != Some(FORMAT_VERSION as u64) {
+        bail!(
+            "{} has format version {:?}, expected {}",
+            path.display(),
+            version,
+            FORMAT_VERSION
+        );
+    }
+    let found_kind = value.get("kind").and_then(|k| k.as_str());
+    if found_kind != Some(kind) {
+        bail!(
+            "{} contains {:?}, expected {}",
+            path.display(),
+            found_kind,
+            kind
+        );
+    }
+
+    let envelope: Envelope<T> = serde_json::from_value(value)
+        .with_context(|| format!("invalid {} in {}", kind, path.display()))?;
+    Ok((envelope.metadata, envelope.data))
+}
+
+pub fn save_q_map<S: GenericState + Serialize, A: GenericAction + Serialize>(
+    path: impl AsRef<Path>,
+    metadata: &Metadata,
+    q_map: &BTreeMap<(S, A), f64>,
+) -> anyhow::Result<()> {
+    save(path, Q_MAP_KIND, metadata, q_map.iter().collect::<Vec<_>>())
+}
+
+pub fn load_q_map<S: GenericState + DeserializeOwned, A: GenericAction + DeserializeOwned>(
+    path: impl AsRef<Path>,
+) -> anyhow::Result<(Metadata, QMap<S, A>)> {
+    let (metadata, entries): (Metadata, QMapEntries<S, A>) = load(path, Q_MAP_KIND)?;
+    Ok((metadata, entries.into_iter().collect()))
+}
+
+pub fn save_policy<S: GenericState + Serialize, A: GenericAction + Serialize>(
+    path: impl AsRef<Path>,
+    metadata: &Metadata,
+    policy: &TabularPolicy<S, A>,
+) -> anyhow::Result<()> {
+    save(
+        path,
+        POLICY_KIND,
+        metadata,
+        policy.actions.iter().collect::<Vec<_>>(),
+    )
+}
+
+pub fn load_policy<S: GenericState + DeserializeOwned, A: GenericAction + DeserializeOwned>(
+    path: impl AsRef<Path>,
+) -> anyhow::Result<(Metadata, TabularPolicy<S, A>)> {
+    let (metadata, entries): (Metadata, Vec<(S, A)>) = load(path, POLICY_KIND)?;
+    Ok((metadata, TabularPolicy::new(entries.into_iter().collect())))
+}
+
+// saves the learner together with its q_map, loading both and calling run_with_q_map continues
+// the training
+pub fn save_learner<T: Serialize, S: GenericState + Serialize, A: GenericAction + Serialize>(
+    path: impl AsRef<Path>,
+    metadata: &Metadata,
+    learner: &T,
+    q_map: &BTreeMap<(S, A), f64>,
+) -> anyhow::Result<()> {
+    let data = LearnerData {
+        learner,
+        q_map: q_map.iter().collect::<Vec<_>>(),
+    };
+    save(path, LEARNER_KIND, metadata, data)
+}
+
+pub fn load_learner<
+    T: DeserializeOwned,
+    S: GenericState + DeserializeOwned,
+    A: GenericAction + DeserializeOwned,
+>(
+    path: impl AsRef<Path>,
+) -> anyhow::Result<(Metadata, T, QMap<S, A>)> {
+    let (metadata, data): (Metadata, LearnerData<T, QMapEntries<S, A>>) = load(path, LEARNER_KIND)?;
+    Ok((metadata, data.learner, data.q_map.into_iter().collect()))
+}
+
+// json only allows string keys, maps keyed by states or state action pairs are stored as lists of
+// (key, value) pairs instead. Use with #[serde(with = "crate::persistence::entries")].
+pub mod entries {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<K: Serialize, V: Serialize, Z: Serializer>(
+        map: &BTreeMap<K, V>,
+        serializer: Z,
+    ) -> Result<Z::Ok, Z::Error> {
+        serializer.collect_seq(map.iter())
+    }
+
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<BTreeMap<K, V>, D::Error>
+    where
+        K: Deserialize<'de> + Ord,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        let entries: Vec<(K, V)> = Vec::deserialize(deserializer)?;
+        Ok(entries.into_iter().collect())
+    }
+}
+
+// same as entries for maps of maps like transition counts
+pub mod nested_entries {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<K: Serialize, L: Serialize, V: Serialize, Z: Serializer>(
+        map: &BTreeMap<K, BTreeMap<L, V>>,
+        serializer: Z,
+    ) -> Result<Z::Ok, Z::Error> {
+        serializer.collect_seq(
+            map.iter()
+                .map(|(key, inner)| (key, inner.iter().collect::<Vec<_>>())),
+        )
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn deserialize<'de, K, L, V, D>(
+        deserializer: D,
+    ) -> Result<BTreeMap<K, BTreeMap<L, V>>, D::Error>
+    where
+        K: Deserialize<'de> + Ord,
+        L: Deserialize<'de> + Ord,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        let entries: Vec<(K, Vec<(L, V)>)> = Vec::deserialize(deserializer)?;
+        Ok(entries
+            .into_iter()
+            .map(|(key, inner)| (key, inner.into_iter().collect()))
+            .collect())
+    }
+}
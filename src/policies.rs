@@ -2,17 +2,322 @@ use crate::mdp::GenericMdp;
 use std::collections::BTreeMap;
 
 use rand::Rng;
-use rand_chacha::ChaCha20Rng;
 
-use crate::mdp::{GenericAction, GenericState, IndexAction, IndexMdp, IndexState, Reward};
+use crate::mdp::{GenericAction, GenericState, Reward};
 
-trait Policy {
-    fn select_action(
-        &self,
-        mdp: &IndexMdp,
-        q_map: &BTreeMap<(IndexState, IndexAction), Reward>,
-        rng: &mut ChaCha20Rng,
-    );
+// a policy picks an action among the possible actions of a state and can report the probability
+// of every action, so learned and hand-written policies can be evaluated the same way
+pub trait Policy<S: GenericState, A: GenericAction> {
+    // called at the start of every episode, stateful controllers reset their counters here
+    fn begin_episode(&mut self) {}
+
+    fn select_action<R: Rng>(&mut self, state: S, possible_actions: &[A], rng: &mut R)
+        -> Option<A>;
+
+    // distribution of the next select_action call, empty if no action is possible
+    fn action_probabilities(&self, state: S, possible_actions: &[A]) -> Vec<(A, f64)>;
+
+    fn action_probability(&self, state: S, action: A, possible_actions: &[A]) -> f64 {
+        self.action_probabilities(state, possible_actions)
+            .iter()
+            .filter(|(a, _)| *a == action)
+            .map(|(_, prob)| *prob)
+            .sum()
+    }
+}
+
+// fixed action per state, states without an entry or with an impossible action fall back to a
+// uniformly random action
+#[derive(Clone, Debug, Default)]
+pub struct TabularPolicy<S: GenericState, A: GenericAction> {
+    pub actions: BTreeMap<S, A>,
+}
+
+impl<S: GenericState, A: GenericAction> TabularPolicy<S, A> {
+    pub fn new(actions: BTreeMap<S, A>) -> Self {
+        Self { actions }
+    }
+
+    // greedy actions of a q_map, ties go to the first action
+    pub fn from_q_map(q_map: &BTreeMap<(S, A), Reward>) -> Self {
+        let mut best: BTreeMap<S, (A, Reward)> = BTreeMap::new();
+        q_map.iter().for_each(|((state, action), q)| {
+            best.entry(*state)
+                .and_modify(|(best_action, best_q)| {
+                    if q > best_q {
+                        *best_action = *action;
+                        *best_q = *q;
+                    }
+                })
+                .or_insert((*action, *q));
+        });
+        Self::new(
+            best.into_iter()
+                .map(|(state, (action, _))| (state, action))
+                .collect(),
+        )
+    }
+
+    fn get_action(&self, state: S, possible_actions: &[A]) -> Option<A> {
+        self.actions
+            .get(&state)
+            .copied()
+            .filter(|action| possible_actions.contains(action))
+    }
+}
+
+impl<S: GenericState, A: GenericAction> Policy<S, A> for TabularPolicy<S, A> {
+    fn select_action<R: Rng>(
+        &mut self,
+        state: S,
+        possible_actions: &[A],
+        rng: &mut R,
+    ) -> Option<A> {
+        self.get_action(state, possible_actions)
+            .or_else(|| random_policy_ma(possible_actions, rng))
+    }
+
+    fn action_probabilities(&self, state: S, possible_actions: &[A]) -> Vec<(A, f64)> {
+        match self.get_action(state, possible_actions) {
+            Some(action) => deterministic(action, possible_actions),
+            None => uniform(possible_actions),
+        }
+    }
+}
+
+// greedy with respect to a q_map, ties are broken uniformly. Missing q_map entries count as 0.
+#[derive(Clone, Copy, Debug)]
+pub struct GreedyPolicy<'a, S: GenericState, A: GenericAction> {
+    q_map: &'a BTreeMap<(S, A), Reward>,
+}
+
+impl<'a, S: GenericState, A: GenericAction> GreedyPolicy<'a, S, A> {
+    pub fn new(q_map: &'a BTreeMap<(S, A), Reward>) -> Self {
+        Self { q_map }
+    }
+}
+
+impl<S: GenericState, A: GenericAction> Policy<S, A> for GreedyPolicy<'_, S, A> {
+    fn select_action<R: Rng>(
+        &mut self,
+        state: S,
+        possible_actions: &[A],
+        rng: &mut R,
+    ) -> Option<A> {
+        random_policy_ma(&best_actions(self.q_map, state, possible_actions), rng)
+    }
+
+    fn action_probabilities(&self, state: S, possible_actions: &[A]) -> Vec<(A, f64)> {
+        let best = best_actions(self.q_map, state, possible_actions);
+        possible_actions
+            .iter()
+            .map(|action| {
+                let prob = if best.contains(action) {
+                    1.0 / best.len() as f64
+                } else {
+                    0.0
+                };
+                (*action, prob)
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct EpsilonGreedyPolicy<'a, S: GenericState, A: GenericAction> {
+    q_map: &'a BTreeMap<(S, A), Reward>,
+    epsilon: f64,
+}
+
+impl<'a, S: GenericState, A: GenericAction> EpsilonGreedyPolicy<'a, S, A> {
+    pub fn new(q_map: &'a BTreeMap<(S, A), Reward>, epsilon: f64) -> Self {
+        Self { q_map, epsilon }
+    }
+}
+
+impl<S: GenericState, A: GenericAction> Policy<S, A> for EpsilonGreedyPolicy<'_, S, A> {
+    fn select_action<R: Rng>(
+        &mut self,
+        state: S,
+        possible_actions: &[A],
+        rng: &mut R,
+    ) -> Option<A> {
+        if rng.gen_range(0.0..1.0) < self.epsilon {
+            random_policy_ma(possible_actions, rng)
+        } else {
+            random_policy_ma(&best_actions(self.q_map, state, possible_actions), rng)
+        }
+    }
+
+    fn action_probabilities(&self, state: S, possible_actions: &[A]) -> Vec<(A, f64)> {
+        let best = best_actions(self.q_map, state, possible_actions);
+        let explore = self.epsilon / possible_actions.len() as f64;
+        possible_actions
+            .iter()
+            .map(|action| {
+                let prob = if best.contains(action) {
+                    explore + (1.0 - self.epsilon) / best.len() as f64
+                } else {
+                    explore
+                };
+                (*action, prob)
+            })
+            .collect()
+    }
+}
+
+// boltzmann exploration, the temperature has to be positive
+#[derive(Clone, Copy, Debug)]
+pub struct SoftmaxPolicy<'a, S: GenericState, A: GenericAction> {
+    q_map: &'a BTreeMap<(S, A), Reward>,
+    temperature: f64,
+}
+
+impl<'a, S: GenericState, A: GenericAction> SoftmaxPolicy<'a, S, A> {
+    pub fn new(q_map: &'a BTreeMap<(S, A), Reward>, temperature: f64) -> Self {
+        assert!(temperature > 0.0, "temperature has to be positive");
+        Self { q_map, temperature }
+    }
+}
+
+impl<S: GenericState, A: GenericAction> Policy<S, A> for SoftmaxPolicy<'_, S, A> {
+    fn select_action<R: Rng>(
+        &mut self,
+        state: S,
+        possible_actions: &[A],
+        rng: &mut R,
+    ) -> Option<A> {
+        sample(&self.action_probabilities(state, possible_actions), rng)
+    }
+
+    fn action_probabilities(&self, state: S, possible_actions: &[A]) -> Vec<(A, f64)> {
+        let q_values: Vec<f64> = possible_actions
+            .iter()
+            .map(|action| q_value(self.q_map, state, *action))
+            .collect();
+        let Some(max_q) = q_values.iter().copied().reduce(f64::max) else {
+            return vec![];
+        };
+
+        // shifting by the maximum keeps exp from overflowing
+        let weights: Vec<f64> = q_values
+            .iter()
+            .map(|q| ((q - max_q) / self.temperature).exp())
+            .collect();
+        let total: f64 = weights.iter().sum();
+        possible_actions
+            .iter()
+            .zip(weights)
+            .map(|(action, weight)| (*action, weight / total))
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RandomPolicy;
+
+impl<S: GenericState, A: GenericAction> Policy<S, A> for RandomPolicy {
+    fn select_action<R: Rng>(
+        &mut self,
+        _state: S,
+        possible_actions: &[A],
+        rng: &mut R,
+    ) -> Option<A> {
+        random_policy_ma(possible_actions, rng)
+    }
+
+    fn action_probabilities(&self, _state: S, possible_actions: &[A]) -> Vec<(A, f64)> {
+        uniform(possible_actions)
+    }
+}
+
+// deterministic hand-written controller, the closure gets the state and its possible actions
+#[derive(Clone, Copy, Debug)]
+pub struct FnPolicy<F> {
+    select: F,
+}
+
+impl<F> FnPolicy<F> {
+    pub fn new(select: F) -> Self {
+        Self { select }
+    }
+}
+
+impl<S: GenericState, A: GenericAction, F: Fn(S, &[A]) -> Option<A>> Policy<S, A> for FnPolicy<F> {
+    fn select_action<R: Rng>(
+        &mut self,
+        state: S,
+        possible_actions: &[A],
+        _rng: &mut R,
+    ) -> Option<A> {
+        (self.select)(state, possible_actions)
+    }
+
+    fn action_probabilities(&self, state: S, possible_actions: &[A]) -> Vec<(A, f64)> {
+        match (self.select)(state, possible_actions) {
+            Some(action) => deterministic(action, possible_actions),
+            None => vec![],
+        }
+    }
+}
+
+// draws an action from a distribution as returned by action_probabilities
+pub fn sample<A: GenericAction, R: Rng>(probabilities: &[(A, f64)], rng: &mut R) -> Option<A> {
+    let mut remaining = rng.gen_range(0.0..1.0);
+    for (action, prob) in probabilities {
+        if remaining < *prob {
+            return Some(*action);
+        }
+        remaining -= prob;
+    }
+    // rounding errors, take the last action with positive probability
+    probabilities
+        .iter()
+        .rev()
+        .find(|(_, prob)| *prob > 0.0)
+        .map(|(action, _)| *action)
+}
+
+fn q_value<S: GenericState, A: GenericAction>(
+    q_map: &BTreeMap<(S, A), Reward>,
+    state: S,
+    action: A,
+) -> Reward {
+    *q_map.get(&(state, action)).unwrap_or(&0.0)
+}
+
+fn best_actions<S: GenericState, A: GenericAction>(
+    q_map: &BTreeMap<(S, A), Reward>,
+    state: S,
+    possible_actions: &[A],
+) -> Vec<A> {
+    let Some(max_q) = possible_actions
+        .iter()
+        .map(|action| q_value(q_map, state, *action))
+        .reduce(f64::max)
+    else {
+        return vec![];
+    };
+    possible_actions
+        .iter()
+        .filter(|action| q_value(q_map, state, **action) == max_q)
+        .copied()
+        .collect()
+}
+
+fn deterministic<A: GenericAction>(selected: A, possible_actions: &[A]) -> Vec<(A, f64)> {
+    possible_actions
+        .iter()
+        .map(|action| (*action, if *action == selected { 1.0 } else { 0.0 }))
+        .collect()
+}
+
+fn uniform<A: GenericAction>(possible_actions: &[A]) -> Vec<(A, f64)> {
+    let prob = 1.0 / possible_actions.len() as f64;
+    possible_actions
+        .iter()
+        .map(|action| (*action, prob))
+        .collect()
 }
 
 pub fn epsilon_greedy_policy<M: GenericMdp<S, A>, S: GenericState, A: GenericAction, R: Rng>(
@@ -0,0 +1,298 @@
+use std::{
+    cell::{Cell, RefCell},
+    fs::{self, File},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::Context;
+use rand::Rng;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    mdp::{GenericAction, GenericMdp, GenericState, Reward},
+    offline::Dataset,
+};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StepRecord<S, A> {
+    pub episode: usize,
+    pub t: usize,
+    pub state: S,
+    pub action: A,
+    pub reward: Reward,
+    pub next_state: S,
+    pub terminal: bool,
+    // last step of an episode that ended without reaching a terminal state
+    pub truncated: bool,
+}
+
+// wraps any mdp and records every step performed on it, so learners, evaluators and the Runner
+// can be recorded without changes. A call to get_initial_state starts the next episode.
+pub struct Recorder<'a, M, S: GenericState, A: GenericAction> {
+    mdp: &'a M,
+    records: RefCell<Vec<StepRecord<S, A>>>,
+    episode: Cell<usize>,
+    t: Cell<usize>,
+}
+
+impl<'a, M: GenericMdp<S, A>, S: GenericState, A: GenericAction> Recorder<'a, M, S, A> {
+    pub fn new(mdp: &'a M) -> Self {
+        Self {
+            mdp,
+            records: RefCell::new(vec![]),
+            episode: Cell::new(0),
+            t: Cell::new(0),
+        }
+    }
+
+    pub fn into_log(self) -> EpisodeLog<S, A> {
+        self.end_episode();
+        EpisodeLog {
+            records: self.records.into_inner(),
+        }
+    }
+
+    // marks the last step as truncated if the episode stopped early. Episodes without steps are
+    // skipped, some learners draw more than one initial state per episode.
+    fn end_episode(&self) {
+        if self.t.get() == 0 {
+            return;
+        }
+        if let Some(last) = self.records.borrow_mut().last_mut() {
+            last.truncated = !last.terminal;
+        }
+        self.episode.set(self.episode.get() + 1);
+        self.t.set(0);
+    }
+}
+
+impl<'a, M: GenericMdp<S, A>, S: GenericState, A: GenericAction> GenericMdp<S, A>
+    for Recorder<'a, M, S, A>
+{
+    fn perform_action<R: Rng>(&self, state_action: (S, A), rng: &mut R) -> (S, Reward) {
+        let (next_state, reward) = self.mdp.perform_action(state_action, rng);
+        self.records.borrow_mut().push(StepRecord {
+            episode: self.episode.get(),
+            t: self.t.get(),
+            state: state_action.0,
+            action: state_action.1,
+            reward,
+            next_state,
+            terminal: self.mdp.is_terminal(next_state),
+            truncated: false,
+        });
+        self.t.set(self.t.get() + 1);
+        (next_state, reward)
+    }
+
+    fn get_possible_actions(&self, current_state: S) -> Vec<A> {
+        self.mdp.get_possible_actions(current_state)
+    }
+
+    fn get_all_state_actions(&self) -> &[(S, A)] {
+        self.mdp.get_all_state_actions()
+    }
+
+    fn is_terminal(&self, state: S) -> bool {
+        self.mdp.is_terminal(state)
+    }
+
+    fn get_initial_state<R: Rng>(&self, rng: &mut R) -> S {
+        self.end_episode();
+        self.mdp.get_initial_state(rng)
+    }
+
+    fn get_discount_factor(&self) -> f64 {
+        self.mdp.get_discount_factor()
+    }
+}
+
+// appends records to a JSONL file one line at a time, flushed after every record so a log
+// survives the program being killed
+pub struct JsonlWriter {
+    writer: BufWriter<File>,
+}
+
+impl JsonlWriter {
+    pub fn create(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file =
+            File::create(path).with_context(|| format!("could not create {}", path.display()))?;
+        Ok(Self {
+            writer: BufWriter::new(file),
+        })
+    }
+
+    pub fn write<S: Serialize, A: Serialize>(
+        &mut self,
+        record: &StepRecord<S, A>,
+    ) -> anyhow::Result<()> {
+        serde_json::to_writer(&mut self.writer, record)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+const CSV_HEADER: [&str; 8] = [
+    "episode",
+    "t",
+    "state",
+    "action",
+    "reward",
+    "next_state",
+    "terminal",
+    "truncated",
+];
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EpisodeLog<S, A> {
+    pub records: Vec<StepRecord<S, A>>,
+}
+
+impl<S: GenericState, A: GenericAction> EpisodeLog<S, A> {
+    pub fn new(records: Vec<StepRecord<S, A>>) -> Self {
+        Self { records }
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    // the steps of every episode in the order they were recorded
+    pub fn episodes(&self) -> Vec<&[StepRecord<S, A>]> {
+        self.records
+            .chunk_by(|a, b| a.episode == b.episode)
+            .collect()
+    }
+
+    // truncated steps are not terminal, offline learners bootstrap from their next state
+    pub fn to_dataset(&self) -> Dataset<S, A> {
+        let mut dataset = Dataset::new();
+        self.records.iter().for_each(|record| {
+            dataset.push((
+                record.state,
+                record.action,
+                record.reward,
+                record.next_state,
+                record.terminal,
+            ))
+        });
+        dataset
+    }
+}
+
+impl<S: GenericState + Serialize, A: GenericAction + Serialize> EpisodeLog<S, A> {
+    pub fn save_jsonl(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let mut writer = JsonlWriter::create(path)?;
+        self.records
+            .iter()
+            .try_for_each(|record| writer.write(record))
+    }
+
+    // states and actions are stored as json in their cells, so any serializable type fits into
+    // the flat csv layout
+    pub fn save_csv(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let mut writer = csv::Writer::from_path(path)?;
+        writer.write_record(CSV_HEADER)?;
+        for record in self.records.iter() {
+            writer.write_record([
+                record.episode.to_string(),
+                record.t.to_string(),
+                serde_json::to_string(&record.state)?,
+                serde_json::to_string(&record.action)?,
+                record.reward.to_string(),
+                serde_json::to_string(&record.next_state)?,
+                record.terminal.to_string(),
+                record.truncated.to_string(),
+            ])?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<S: GenericState + DeserializeOwned, A: GenericAction + DeserializeOwned> EpisodeLog<S, A> {
+    pub fn load_jsonl(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let reader = BufReader::new(
+            File::open(path).with_context(|| format!("could not open {}", path.display()))?,
+        );
+        let mut records = vec![];
+        for (line, text) in reader.lines().enumerate() {
+            let text = text?;
+            if text.trim().is_empty() {
+                continue;
+            }
+            let record = serde_json::from_str(&text)
+                .with_context(|| format!("invalid record in line {}", line + 1))?;
+            records.push(record);
+        }
+        Ok(Self { records })
+    }
+
+    pub fn load_csv(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let mut reader = csv::Reader::from_path(path)?;
+        let mut records = vec![];
+        for (line, row) in reader.records().enumerate() {
+            let row = row?;
+            let field = |index: usize| {
+                row.get(index)
+                    .ok_or_else(|| anyhow::anyhow!("missing column {}", CSV_HEADER[index]))
+            };
+            let record = (|| -> anyhow::Result<StepRecord<S, A>> {
+                Ok(StepRecord {
+                    episode: field(0)?.parse()?,
+                    t: field(1)?.parse()?,
+                    state: serde_json::from_str(field(2)?)?,
+                    action: serde_json::from_str(field(3)?)?,
+                    reward: field(4)?.parse()?,
+                    next_state: serde_json::from_str(field(5)?)?,
+                    terminal: field(6)?.parse()?,
+                    truncated: field(7)?.parse()?,
+                })
+            })()
+            .with_context(|| format!("invalid record {}", line + 1))?;
+            records.push(record);
+        }
+        Ok(Self { records })
+    }
+}
+
+// steps through a log one record at a time, e.g. to drive a visualisation
+pub struct Replay<S, A> {
+    log: EpisodeLog<S, A>,
+    position: usize,
+}
+
+impl<S: GenericState, A: GenericAction> Replay<S, A> {
+    pub fn new(log: EpisodeLog<S, A>) -> Self {
+        Self { log, position: 0 }
+    }
+
+    pub fn rewind(&mut self) {
+        self.position = 0;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.log.records.len()
+    }
+}
+
+impl<S: GenericState, A: GenericAction> Iterator for Replay<S, A> {
+    type Item = StepRecord<S, A>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = self.log.records.get(self.position).cloned()?;
+        self.position += 1;
+        Some(record)
+    }
+}
@@ -1,26 +1,76 @@
-use crate::algorithms::GenericStateActionAlgorithm;
+use crate::algorithms::TabularAlgorithm;
 use std::collections::{BTreeMap, HashSet};
 
 use assert_float_eq::assert_f64_near;
 use rand::SeedableRng;
 
 use crate::{
-    algorithms::{q_learning::QLearning, sarsa::Sarsa},
-    mdp::{IndexAction, IndexMdp, IndexState, Transition},
+    agent::{
+        observer::{GreedyEvaluation, LearningCurve, TdErrorTracker},
+        runner::Runner,
+        stopping::{PolicyStability, QChange, StopReason, TimeBudget},
+        Agent, Observation, TabularAgent,
+    },
+    algorithms::{
+        dyna_q::DynaQ,
+        expected_sarsa::ExpectedSarsa,
+        monte_carlo::{Episode, MonteCarlo},
+        policy_iteration::policy_iteration,
+        psrl::{NormalGammaPrior, Psrl},
+        q_learning::QLearning,
+        q_learning_lambda::QLearningLambda,
+        r_learning::RLearning,
+        relative_value_iteration::relative_value_iteration,
+        replay::{Experience, ReplayBuffer},
+        sarsa::Sarsa,
+        sarsa_lambda::SarsaLambda,
+        value_iteration::{q_map_from_values, value_iteration},
+        Trace,
+    },
+    analysis::{optimal_actions, optimal_q_map, optimal_return, suboptimality, RegretTracker},
+    approximation::{
+        features::OneHot, linear_q, semi_gradient_q_learning::SemiGradientQLearning,
+        semi_gradient_sarsa::SemiGradientSarsa, tile_coding::TileCoding, FeatureExtractor,
+        GenericLinearAlgorithm,
+    },
+    benchmarks::executor::{run_jobs, run_seeds},
+    envs::blackjack::{BlackjackAction, BlackjackMdp, BlackjackState},
+    envs::exploration::{build_combination_lock, build_deep_sea, build_n_chain, build_river_swim},
+    envs::grid::{Connectivity, GridAction, GridConfig, GridState, Wind},
+    envs::intersection::{build_action, build_state, to_intersection_q_map},
+    envs::my_intersection::{IntersectionState, LightAction, LightState, MyIntersectionMdp},
+    envs::traffic::{Light, TrafficMdp, TrafficReward},
+    eval::{evaluate, evaluate_policy},
+    generator::{GarnetConfig, Rewards, Structure},
+    mdp::{GenericMdp, IndexAction, IndexMdp, IndexState, Transition},
+    offline::{
+        batch_q_learning::batch_q_learning,
+        fitted_q_iteration::fitted_q_iteration,
+        model::{estimate_mdp, EstimatedModel},
+        ope::{collect_episodes, estimate_all, importance_sampling, weighted_importance_sampling},
+        Dataset,
+    },
+    persistence::{load_learner, load_q_map, save_learner, save_q_map, Metadata},
+    policies::{
+        EpsilonGreedyPolicy, FnPolicy, GreedyPolicy, Policy, RandomPolicy, SoftmaxPolicy,
+        TabularPolicy,
+    },
+    recorder::{EpisodeLog, Recorder, Replay},
     utils::print_q_map,
 };
 
 #[test]
 fn test_q_learning() {
-    // this will select action 1 on first step and go to state 0
+    // this will select action 1 on first step and go to state 0, then action 0 into the terminal
+    // state, whose target is just the reward
     let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(212);
     let mdp = create_test_mdp();
-    let algo = QLearning::new(0.1, 0.1, 5);
+    let mut algo = QLearning::new(0.1, 0.1, 5);
     let q_map = algo.run(&mdp, 1, &mut rng);
 
     print_q_map(&q_map);
 
-    assert_f64_near!(*q_map.get(&(IndexState(0), IndexAction(0))).unwrap(), 0.0);
+    assert_f64_near!(*q_map.get(&(IndexState(0), IndexAction(0))).unwrap(), 1.0);
     assert_f64_near!(*q_map.get(&(IndexState(0), IndexAction(1))).unwrap(), -0.1);
     assert_f64_near!(*q_map.get(&(IndexState(1), IndexAction(0))).unwrap(), 0.0);
     assert_f64_near!(*q_map.get(&(IndexState(1), IndexAction(1))).unwrap(), 0.0);
@@ -31,21 +81,57 @@ fn test_sarsa() {
     let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(8);
     let mdp = create_test_mdp();
 
-    let algo = Sarsa::new(0.1, 0.1, 5);
+    let mut algo = Sarsa::new(0.1, 0.1, 5);
     let q_map = algo.run(&mdp, 1, &mut rng);
 
-    assert_f64_near!(*q_map.get(&(IndexState(0), IndexAction(0))).unwrap(), 0.0);
+    // action 1 first, then action 0 into the terminal state, whose target is just the reward
+    assert_f64_near!(*q_map.get(&(IndexState(0), IndexAction(0))).unwrap(), 1.0);
     assert_f64_near!(*q_map.get(&(IndexState(0), IndexAction(1))).unwrap(), -0.1);
     assert_f64_near!(*q_map.get(&(IndexState(1), IndexAction(0))).unwrap(), 0.0);
     assert_f64_near!(*q_map.get(&(IndexState(1), IndexAction(1))).unwrap(), 0.0);
 }
 
+#[test]
+fn test_terminal_bootstrap() {
+    // 0 -> 1 -> 2, the terminal state 2 has no transitions and therefore no actions
+    let mut mdp = IndexMdp::new(0.9, IndexState(0));
+    mdp.add_transition_vector(
+        (IndexState(0), IndexAction(0)),
+        vec![(1.0, IndexState(1), 0.0)],
+    )
+    .unwrap();
+    mdp.add_transition_vector(
+        (IndexState(1), IndexAction(0)),
+        vec![(1.0, IndexState(2), 1.0)],
+    )
+    .unwrap();
+    mdp.add_terminal_state(IndexState(2));
+
+    fn assert_values(q_map: &BTreeMap<(IndexState, IndexAction), f64>) {
+        assert_float_absolute_eq!(q_map[&(IndexState(1), IndexAction(0))], 1.0, 1e-6);
+        assert_float_absolute_eq!(q_map[&(IndexState(0), IndexAction(0))], 0.9, 1e-6);
+    }
+
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let episodes = 200;
+    assert_values(&QLearning::new(0.5, 0.1, 10).run(&mdp, episodes, &mut rng));
+    assert_values(&Sarsa::new(0.5, 0.1, 10).run(&mdp, episodes, &mut rng));
+    assert_values(&ExpectedSarsa::new(0.5, 0.1, 10).run(&mdp, episodes, &mut rng));
+    assert_values(
+        &SarsaLambda::new(0.5, 0.1, 0.5, 10, Trace::Replacing).run(&mdp, episodes, &mut rng),
+    );
+    assert_values(
+        &QLearningLambda::new(0.5, 0.1, 0.5, 10, Trace::Replacing).run(&mdp, episodes, &mut rng),
+    );
+    assert_values(&DynaQ::new(0.5, 0.1, 5, 10, true, true, &mdp).run(&mdp, episodes, &mut rng));
+}
+
 const EPISODES: usize = 1000;
 
 #[test]
 fn test_sarsa_equivalence() {
     let mdp = create_test_mdp();
-    let algo = Sarsa::new(0.1, 0.1, 1000);
+    let mut algo = Sarsa::new(0.1, 0.1, 1000);
 
     let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
     let q_map_1 = algo.run(&mdp, EPISODES, &mut rng);
@@ -60,7 +146,7 @@ fn test_sarsa_equivalence() {
 #[test]
 fn test_q_learning_equivalence() {
     let mdp = create_test_mdp();
-    let algo = QLearning::new(0.1, 0.9, 1000);
+    let mut algo = QLearning::new(0.1, 0.9, 1000);
 
     let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
     let q_map_1 = algo.run(&mdp, EPISODES, &mut rng);
@@ -72,6 +158,1399 @@ fn test_q_learning_equivalence() {
     assert_eq!(q_map_1, q_map_2);
 }
 
+#[test]
+fn test_monte_carlo_equivalence() {
+    // the visit counts carry over, so the second chunk keeps averaging over all returns
+    let mdp = create_test_mdp();
+
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let q_map_1 = MonteCarlo::new(0.1, 1000).run(&mdp, EPISODES, &mut rng);
+
+    let mut algo = MonteCarlo::new(0.1, 1000);
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let mut q_map_2 = algo.run(&mdp, EPISODES / 2, &mut rng);
+    algo.run_with_q_map(&mdp, EPISODES / 2, &mut rng, &mut q_map_2);
+
+    assert_eq!(q_map_1, q_map_2);
+}
+
+#[test]
+fn test_monte_carlo_first_visit() {
+    let mut algo = MonteCarlo::new(0.0, 10);
+    let mut episode = Episode::default();
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let mut q_map = BTreeMap::from([
+        ((IndexState(0), IndexAction(0)), 0.0),
+        ((IndexState(1), IndexAction(0)), 0.0),
+    ]);
+    let mut play = |steps: &[(usize, f64)], q_map: &mut BTreeMap<_, _>| {
+        for (state, reward) in steps {
+            algo.observe(
+                &mut episode,
+                q_map,
+                &Observation {
+                    state: IndexState(*state),
+                    action: IndexAction(0),
+                    reward: *reward,
+                    next_state: IndexState(2),
+                    next_possible_actions: vec![],
+                    terminal: false,
+                    discount_factor: 0.5,
+                },
+                &mut rng,
+            );
+        }
+        algo.end_episode(&mut episode, q_map, &mut rng);
+    };
+
+    // discounted returns 1.5, 1 and 2, only the first visit of (0, 0) counts
+    play(&[(0, 1.0), (1, 0.0), (0, 2.0)], &mut q_map);
+    assert_f64_near!(q_map[&(IndexState(0), IndexAction(0))], 1.5);
+    assert_f64_near!(q_map[&(IndexState(1), IndexAction(0))], 1.0);
+
+    // the mean runs over all episodes instead of keeping the last return
+    play(&[(0, 0.5)], &mut q_map);
+    assert_f64_near!(q_map[&(IndexState(0), IndexAction(0))], 1.0);
+    assert_f64_near!(q_map[&(IndexState(1), IndexAction(0))], 1.0);
+}
+
+#[test]
+fn test_value_iteration_terminal_states() {
+    // the terminal state has a rewarding self loop, e.g. from a map that keeps moves in place
+    let mut mdp = IndexMdp::new(0.9, IndexState(0));
+    mdp.add_transition_vector(
+        (IndexState(0), IndexAction(0)),
+        vec![(1.0, IndexState(1), 1.0)],
+    )
+    .unwrap();
+    mdp.add_transition_vector(
+        (IndexState(1), IndexAction(0)),
+        vec![(1.0, IndexState(1), 5.0)],
+    )
+    .unwrap();
+    mdp.add_terminal_state(IndexState(1));
+
+    // episodes end on entering the terminal state, so its transitions never pay out. Without the
+    // terminal check the values would be 46 and 50.
+    let values = value_iteration(&mdp, 1e-12);
+    assert_eq!(values[&IndexState(1)], 0.0);
+    assert!((values[&IndexState(0)] - 1.0).abs() < 1e-12);
+    let (_, policy_values) = policy_iteration(&mdp, 1e-12, 10_000);
+    assert_eq!(policy_values, values);
+}
+
+#[test]
+fn test_policy_iteration_undiscounted() {
+    // the first policy moves up, in the top row that is a -1 self loop that never terminates
+    let mdp = crate::envs::cliff_walking::build_mdp().unwrap();
+    let (policy, policy_values) = policy_iteration(&mdp, 1e-9, 1000);
+    let values = value_iteration(&mdp, 1e-9);
+    for (state, value) in values.iter() {
+        assert_f64_near!(policy_values[state], *value);
+    }
+    assert_f64_near!(policy_values[&GridState(3, 0)], -12.0);
+    assert_eq!(policy[&GridState(3, 0)], GridAction::Up);
+}
+
+#[test]
+fn test_value_iteration_sweep() {
+    // every sweep backs up each state with the best of all its actions, wherever that action is
+    // among the keys, and leaves terminal states at 0
+    let mut mdp = IndexMdp::new(0.9, IndexState(1));
+    for (action, reward) in [(0, 1.0), (1, 5.0), (2, 2.0)] {
+        mdp.add_transition_vector(
+            (IndexState(0), IndexAction(action)),
+            vec![(1.0, IndexState(2), reward)],
+        )
+        .unwrap();
+    }
+    mdp.add_transition_vector(
+        (IndexState(1), IndexAction(0)),
+        vec![(1.0, IndexState(0), 0.0)],
+    )
+    .unwrap();
+    mdp.add_transition_vector(
+        (IndexState(2), IndexAction(0)),
+        vec![(1.0, IndexState(2), 1.0)],
+    )
+    .unwrap();
+    mdp.add_terminal_state(IndexState(2));
+    assert_eq!(
+        value_iteration(&mdp, 1e-12),
+        BTreeMap::from([
+            (IndexState(0), 5.0),
+            (IndexState(1), 4.5),
+            (IndexState(2), 0.0)
+        ])
+    );
+
+    // so the results satisfy the bellman optimality equations on mdps with several actions per
+    // state
+    let garnet = GarnetConfig::new(30, 4, 3)
+        .with_rewards(Rewards::Dense {
+            min: -1.0,
+            max: 1.0,
+        })
+        .with_discount_factor(0.9)
+        .with_seed(4)
+        .generate()
+        .unwrap();
+    let mdp = garnet.mdp;
+    let values = value_iteration(&mdp, 1e-12);
+    let q_map = q_map_from_values(&mdp, &values);
+    for (state, value) in values.iter() {
+        let best = q_map
+            .iter()
+            .filter(|((s, _), _)| s == state)
+            .map(|(_, q)| *q)
+            .fold(f64::NEG_INFINITY, f64::max);
+        assert_float_absolute_eq!(*value, best, 1e-9);
+    }
+
+    // the same for the average reward backups on an ergodic mdp, gain plus bias is the best one step value
+    let garnet = GarnetConfig::new(30, 4, 3)
+        .with_structure(Structure::Ergodic)
+        .with_seed(4)
+        .generate()
+        .unwrap();
+    let mdp = garnet.mdp;
+    let (gain, bias) = relative_value_iteration(&mdp, 1e-12, 100_000).unwrap();
+    for (state, value) in bias.iter() {
+        let best = mdp
+            .transitions
+            .iter()
+            .filter(|((s, _), _)| s == state)
+            .map(|(_, transitions)| {
+                transitions
+                    .iter()
+                    .map(|(prob, next_state, reward)| prob * (reward + bias[next_state]))
+                    .sum::<f64>()
+            })
+            .fold(f64::NEG_INFINITY, f64::max);
+        assert_float_absolute_eq!(gain + value, best, 1e-9);
+    }
+}
+
+#[test]
+fn test_relative_value_iteration() {
+    // staying in state 0 earns 1 per step, moving on to state 1 and staying there earns 2
+    let mut mdp = IndexMdp::new(1.0, IndexState(0));
+    mdp.add_transition_vector(
+        (IndexState(0), IndexAction(0)),
+        vec![(1.0, IndexState(0), 1.0)],
+    )
+    .unwrap();
+    mdp.add_transition_vector(
+        (IndexState(0), IndexAction(1)),
+        vec![(1.0, IndexState(1), 0.0)],
+    )
+    .unwrap();
+    mdp.add_transition_vector(
+        (IndexState(1), IndexAction(0)),
+        vec![(1.0, IndexState(1), 2.0)],
+    )
+    .unwrap();
+
+    let (gain, bias) = relative_value_iteration(&mdp, 1e-9, 10_000).unwrap();
+
+    assert_float_absolute_eq!(gain, 2.0, 1e-6);
+    assert_float_absolute_eq!(*bias.get(&IndexState(0)).unwrap(), 0.0, 1e-6);
+    assert_float_absolute_eq!(*bias.get(&IndexState(1)).unwrap(), 2.0, 1e-6);
+
+    // a terminal state next to the rewarding cycle, the bias of state 0 grows without bound
+    let mut multichain = IndexMdp::new(1.0, IndexState(0));
+    multichain
+        .add_transition_vector(
+            (IndexState(0), IndexAction(0)),
+            vec![(1.0, IndexState(1), 0.0)],
+        )
+        .unwrap();
+    multichain
+        .add_transition_vector(
+            (IndexState(1), IndexAction(0)),
+            vec![(1.0, IndexState(1), 1.0)],
+        )
+        .unwrap();
+    multichain
+        .add_transition_vector(
+            (IndexState(1), IndexAction(1)),
+            vec![(1.0, IndexState(2), 0.0)],
+        )
+        .unwrap();
+    multichain.add_terminal_state(IndexState(2));
+
+    assert!(relative_value_iteration(&multichain, 1e-9, 10_000).is_err());
+}
+
+#[test]
+fn test_r_learning_continuing() {
+    // the same mdp as above, once in state 1 the run earns 2 per step forever
+    let mut mdp = IndexMdp::new(1.0, IndexState(0));
+    mdp.add_transition_vector(
+        (IndexState(0), IndexAction(0)),
+        vec![(1.0, IndexState(0), 1.0)],
+    )
+    .unwrap();
+    mdp.add_transition_vector(
+        (IndexState(0), IndexAction(1)),
+        vec![(1.0, IndexState(1), 0.0)],
+    )
+    .unwrap();
+    mdp.add_transition_vector(
+        (IndexState(1), IndexAction(0)),
+        vec![(1.0, IndexState(1), 2.0)],
+    )
+    .unwrap();
+
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let mut algo = RLearning::new(0.1, 0.01, 0.1, 5);
+    algo.run_continuing(&mdp, 20000, &mut rng);
+    assert_float_absolute_eq!(algo.get_rho(), 2.0, 1e-2);
+
+    // the run ignores max_steps and stays in state 1 instead of restarting in state 0
+    let mut agent = TabularAgent::from_mdp(algo, &mdp);
+    let stats = Runner::new(5).run_continuing(&mdp, &mut agent, 1000, &mut rng, &mut ());
+    assert_eq!(stats.steps, 1000);
+    assert!(!stats.terminated);
+    assert!(stats.total_reward > 1900.0);
+}
+
+#[test]
+fn test_tile_coding() {
+    let tile_coding = TileCoding::new(
+        8,
+        4,
+        vec![(0.0, 10.0)],
+        vec![IndexAction(0), IndexAction(1)],
+        |state: IndexState| vec![state.0 as f64],
+    );
+
+    let features = tile_coding.features(IndexState(3), IndexAction(0));
+    let close = tile_coding.features(IndexState(4), IndexAction(0));
+    let other_action = tile_coding.features(IndexState(3), IndexAction(1));
+
+    // one active tile per tiling
+    assert_eq!(features.len(), 8);
+    assert!(features
+        .iter()
+        .all(|(index, _)| *index < tile_coding.num_features()));
+
+    // neighbouring states share some tiles but not all of them
+    let shared = features.iter().filter(|f| close.contains(f)).count();
+    assert!(shared > 0 && shared < 8);
+
+    // actions use separate weights
+    assert!(features.iter().all(|f| !other_action.contains(f)));
+}
+
+// stop in state 0 for 1 or move on to state 1 and stop there for 10, both stops end the episode
+fn create_two_stop_mdp() -> IndexMdp {
+    let mut mdp = IndexMdp::new(0.9, IndexState(0));
+    for (state, stop_reward, other) in [(0, 1.0, 1), (1, 10.0, 0)] {
+        mdp.add_transition_vector(
+            (IndexState(state), IndexAction(0)),
+            vec![(1.0, IndexState(2), stop_reward)],
+        )
+        .unwrap();
+        mdp.add_transition_vector(
+            (IndexState(state), IndexAction(1)),
+            vec![(1.0, IndexState(other), 0.0)],
+        )
+        .unwrap();
+    }
+    mdp.add_terminal_state(IndexState(2));
+    mdp
+}
+
+#[test]
+fn test_semi_gradient_q_learning() {
+    // with one hot features the linear learner is tabular Q-learning, both learn the optimal
+    // values from a random behaviour
+    let mdp = create_two_stop_mdp();
+    let extractor = OneHot::from_mdp(&mdp);
+    let optimal = optimal_q_map(&mdp, 1e-12);
+
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let weights = SemiGradientQLearning::new(0.5, 1.0, 20).run(&mdp, &extractor, 500, &mut rng);
+    let q_map = QLearning::new(0.5, 1.0, 20).run(&mdp, 500, &mut rng);
+
+    for state_action in mdp.get_all_state_actions() {
+        let weight = linear_q(
+            &weights,
+            &extractor.features(state_action.0, state_action.1),
+        );
+        assert_float_absolute_eq!(weight, q_map[state_action], 1e-6);
+        assert_float_absolute_eq!(weight, optimal[state_action], 1e-6);
+    }
+}
+
+#[test]
+fn test_semi_gradient_sarsa() {
+    // with one hot features the linear learner is tabular SARSA. Greedy on optimistic values
+    // both settle on moving to state 1 and stopping there, which have their optimal values.
+    let mdp = create_two_stop_mdp();
+    let extractor = OneHot::from_mdp(&mdp);
+
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let mut weights = vec![20.0; extractor.num_features()];
+    SemiGradientSarsa::new(0.5, 0.0, 20).run_with_weights(
+        &mdp,
+        &extractor,
+        500,
+        &mut rng,
+        &mut weights,
+    );
+    let mut q_map = mdp
+        .get_all_state_actions()
+        .iter()
+        .map(|state_action| (*state_action, 20.0))
+        .collect();
+    Sarsa::new(0.5, 0.0, 20).run_with_q_map(&mdp, 500, &mut rng, &mut q_map);
+
+    for (state_action, optimal) in [
+        ((IndexState(0), IndexAction(1)), 9.0),
+        ((IndexState(1), IndexAction(0)), 10.0),
+    ] {
+        let weight = linear_q(
+            &weights,
+            &extractor.features(state_action.0, state_action.1),
+        );
+        assert_float_absolute_eq!(weight, q_map[&state_action], 1e-6);
+        assert_float_absolute_eq!(weight, optimal, 1e-6);
+    }
+}
+
+#[test]
+fn test_replay_buffer() {
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let mut buffer = ReplayBuffer::prioritized(3, 0.5, 1.0);
+
+    for state in 0..5 {
+        buffer.push(Experience {
+            state: IndexState(state),
+            action: IndexAction(0),
+            reward: 0.0,
+            next_state: IndexState(state + 1),
+            next_possible_actions: vec![IndexAction(0)],
+            terminal: false,
+        });
+    }
+
+    // the two oldest transitions were dropped
+    assert_eq!(buffer.len(), 3);
+    assert_eq!(buffer.get(0).unwrap().state, IndexState(2));
+
+    // five pushes at ratio 0.5 are due as two replays, the remaining half is carried over. Every
+    // replayed transition gets the priority returned by the update, the others keep the initial one
+    let mut replayed = vec![];
+    let count = buffer.replay_with(&mut rng, |experience, _| {
+        replayed.push(experience.state);
+        0.0
+    });
+    assert_eq!(count, 2);
+    for index in 0..buffer.len() {
+        let expected = if replayed.contains(&buffer.get(index).unwrap().state) {
+            0.0
+        } else {
+            1.0
+        };
+        assert_eq!(buffer.get_priority(index), Some(expected));
+    }
+
+    // priorities are magnitudes and new transitions get the highest priority seen so far, even
+    // after the transition that had it was dropped
+    buffer.update_priority(0, -3.0);
+    assert_eq!(buffer.get_priority(0), Some(3.0));
+    buffer.push(Experience {
+        state: IndexState(5),
+        action: IndexAction(0),
+        reward: 0.0,
+        next_state: IndexState(6),
+        next_possible_actions: vec![IndexAction(0)],
+        terminal: false,
+    });
+    assert_eq!(buffer.get(0).unwrap().state, IndexState(3));
+    assert_eq!(buffer.get_priority(2), Some(3.0));
+
+    // the carried over half and the new push are due as one replay
+    assert_eq!(buffer.replay_with(&mut rng, |_, _| 0.0), 1);
+
+    // a replayed step into a terminal state bootstraps with 0 even if the state has actions
+    let mut buffer = ReplayBuffer::uniform(1, 1.0);
+    buffer.push(Experience {
+        state: IndexState(0),
+        action: IndexAction(0),
+        reward: 1.0,
+        next_state: IndexState(1),
+        next_possible_actions: vec![IndexAction(0)],
+        terminal: true,
+    });
+    let mut q_map = BTreeMap::from([
+        ((IndexState(0), IndexAction(0)), 0.0),
+        ((IndexState(1), IndexAction(0)), 5.0),
+    ]);
+    buffer.replay(&mut QLearning::new(1.0, 0.1, 5), &mut q_map, 0.9, &mut rng);
+    assert_f64_near!(q_map[&(IndexState(0), IndexAction(0))], 1.0);
+}
+
+#[test]
+fn test_fitted_q_iteration() {
+    let mut dataset = Dataset::new();
+    dataset.push((IndexState(0), IndexAction(0), 1.0, IndexState(1), false));
+    dataset.push((IndexState(0), IndexAction(0), 3.0, IndexState(1), false));
+    dataset.push((IndexState(1), IndexAction(0), 10.0, IndexState(2), true));
+
+    let (q_map, _) = fitted_q_iteration(&dataset, 0.5, 1e-9, 100);
+
+    assert_f64_near!(*q_map.get(&(IndexState(1), IndexAction(0))).unwrap(), 10.0);
+    assert_f64_near!(*q_map.get(&(IndexState(0), IndexAction(0))).unwrap(), 7.0);
+
+    // batch q-learning reaches the same fixed point, a step size below 1 only slows it down
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let (q_map, sweeps) = batch_q_learning(&dataset, 0.5, 0.5, 1e-9, 1000, &mut rng);
+    assert!(sweeps < 1000);
+    assert!((q_map[&(IndexState(1), IndexAction(0))] - 10.0).abs() < 1e-8);
+    assert!((q_map[&(IndexState(0), IndexAction(0))] - 7.0).abs() < 1e-8);
+
+    let coverage = dataset.coverage(&[
+        (IndexState(0), IndexAction(0)),
+        (IndexState(0), IndexAction(1)),
+    ]);
+    assert_eq!(coverage.missing, vec![(IndexState(0), IndexAction(1))]);
+    assert_eq!(
+        *coverage
+            .counts
+            .get(&(IndexState(0), IndexAction(0)))
+            .unwrap(),
+        2
+    );
+}
+
+#[test]
+fn test_estimate_mdp() {
+    let trajectories = vec![
+        vec![
+            (IndexState(0), IndexAction(0), 1.0, IndexState(0), false),
+            (IndexState(0), IndexAction(0), 3.0, IndexState(1), true),
+        ],
+        vec![
+            (IndexState(0), IndexAction(0), 1.0, IndexState(0), false),
+            (IndexState(0), IndexAction(0), 1.0, IndexState(0), false),
+            (IndexState(0), IndexAction(1), 0.0, IndexState(1), true),
+        ],
+    ];
+
+    let mdp = estimate_mdp(&trajectories, 0.9).unwrap();
+    assert_eq!(mdp.initial_state, IndexState(0));
+    assert!(mdp.terminal_states.contains(&IndexState(1)));
+    assert_eq!(
+        *mdp.transitions
+            .get(&(IndexState(0), IndexAction(0)))
+            .unwrap(),
+        vec![(0.75, IndexState(0), 1.0), (0.25, IndexState(1), 3.0)]
+    );
+
+    let model = EstimatedModel::from_trajectories(&trajectories);
+    assert_eq!(model.count((IndexState(0), IndexAction(0))), 4);
+    assert_f64_near!(
+        model.mean_reward((IndexState(0), IndexAction(0))).unwrap(),
+        1.5
+    );
+
+    let (low, high) = model
+        .probability_interval((IndexState(0), IndexAction(0)), IndexState(1), 1.96)
+        .unwrap();
+    assert!(low > 0.0 && low < 0.25 && high > 0.25 && high < 1.0);
+
+    // DynaQ exports its own model, a stochastic model has the same estimates
+    let mut mdp = IndexMdp::new(0.9, IndexState(0));
+    for state in [IndexState(0), IndexState(1)] {
+        mdp.add_transition_vector((state, IndexAction(0)), vec![(1.0, state, 0.0)])
+            .unwrap();
+    }
+    for deterministic in [false, true] {
+        let algo = DynaQ::new(0.1, 0.1, 5, 100, deterministic, true, &mdp);
+        let mut agent = TabularAgent::from_mdp(algo, &mdp);
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+        for (state, action, reward, next_state, terminal) in trajectories[0]
+            .iter()
+            .chain(trajectories[1].iter())
+            .filter(|(_, action, _, _, _)| *action == IndexAction(0))
+        {
+            agent.observe(
+                &Observation {
+                    state: *state,
+                    action: *action,
+                    reward: *reward,
+                    next_state: *next_state,
+                    next_possible_actions: if *terminal {
+                        vec![]
+                    } else {
+                        vec![IndexAction(0)]
+                    },
+                    terminal: *terminal,
+                    discount_factor: 0.9,
+                },
+                &mut rng,
+            );
+        }
+
+        let exported = agent.algorithm.export_model(0.9, IndexState(0));
+        let expected = if deterministic {
+            vec![(1.0, IndexState(0), 1.0)]
+        } else {
+            vec![(0.75, IndexState(0), 1.0), (0.25, IndexState(1), 3.0)]
+        };
+        assert_eq!(
+            exported.transitions[&(IndexState(0), IndexAction(0))],
+            expected
+        );
+        assert!(exported.terminal_states.contains(&IndexState(1)));
+    }
+}
+
+#[test]
+fn test_psrl() {
+    // action 0 always leads to the rewarding terminal state
+    let mut mdp = IndexMdp::new(0.9, IndexState(0));
+    mdp.add_transition_vector(
+        (IndexState(0), IndexAction(0)),
+        vec![(1.0, IndexState(1), 1.0)],
+    )
+    .unwrap();
+    mdp.add_transition_vector(
+        (IndexState(0), IndexAction(1)),
+        vec![(1.0, IndexState(2), 0.0)],
+    )
+    .unwrap();
+    mdp.add_terminal_state(IndexState(1));
+    mdp.add_terminal_state(IndexState(2));
+
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let mut algo = Psrl::new(10, 1e-9, 1.0, NormalGammaPrior::default(), &mdp);
+    let q_map = algo.run(&mdp, 50, &mut rng);
+
+    assert!(
+        q_map.get(&(IndexState(0), IndexAction(0))).unwrap()
+            > q_map.get(&(IndexState(0), IndexAction(1))).unwrap()
+    );
+}
+
+#[test]
+fn test_runner() {
+    let mdp = create_test_mdp();
+    let runner = Runner::new(5);
+
+    // the agent reproduces the q_map of QLearning::run, which drives the same agent
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(212);
+    let mut agent = TabularAgent::from_mdp(QLearning::new(0.1, 0.1, 5), &mdp);
+    let stats = runner.run(&mdp, &mut agent, 1, &mut rng);
+
+    assert_eq!(stats.len(), 1);
+    assert!(stats[0].steps <= runner.get_max_steps());
+    assert_f64_near!(
+        *agent.q_map().get(&(IndexState(0), IndexAction(1))).unwrap(),
+        -0.1
+    );
+
+    // model based agents run on the same loop
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let mut agent = TabularAgent::from_mdp(DynaQ::new(0.1, 0.1, 5, 5, false, true, &mdp), &mdp);
+    let stats = runner.run(&mdp, &mut agent, 10, &mut rng);
+
+    assert_eq!(
+        stats.iter().map(|s| s.episode).collect::<Vec<_>>(),
+        (0..10).collect::<Vec<_>>()
+    );
+    assert!(agent.q_map().values().any(|q| *q != 0.0));
+}
+
+#[test]
+fn test_observers() {
+    let mdp = create_test_mdp();
+    let runner = Runner::new(5);
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(212);
+    let mut q_map = mdp
+        .get_all_state_actions()
+        .iter()
+        .map(|state_action| (*state_action, 0.0))
+        .collect();
+
+    let mut curve = LearningCurve::new();
+    let mut td_errors = TdErrorTracker::new();
+    let mut evaluation = GreedyEvaluation::new(&mdp, 4, 2, 5, 0);
+    runner.run_with_q_map(
+        QLearning::new(0.1, 0.1, 5),
+        &mdp,
+        10,
+        &mut rng,
+        &mut q_map,
+        &mut (&mut curve, (&mut td_errors, &mut evaluation)),
+    );
+
+    assert_eq!(curve.episodes.len(), 10);
+    assert_eq!(curve.moving_average(3).len(), 10);
+    assert_eq!(td_errors.mean_abs.len(), 10);
+    // the first episode ends with a reward of 10 into the terminal state while every q is still 0
+    assert_f64_near!(td_errors.max_abs[0], 10.0);
+    // every 4 episodes and once more after training
+    assert_eq!(
+        evaluation
+            .results
+            .iter()
+            .map(|(trained, _)| *trained)
+            .collect::<Vec<_>>(),
+        vec![4, 8, 10]
+    );
+    assert!(q_map.values().any(|q| *q != 0.0));
+}
+
+#[test]
+fn test_stopping() {
+    let mdp = create_test_mdp();
+    let runner = Runner::new(5);
+
+    // a zero budget is used up after the first episode
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let mut agent = TabularAgent::from_mdp(QLearning::new(0.1, 0.1, 5), &mdp);
+    let mut budget = TimeBudget::new(std::time::Duration::ZERO);
+    let (stats, reason) = runner.run_until(&mdp, &mut agent, 100, &mut rng, &mut budget, &mut ());
+    assert_eq!((stats.len(), reason), (1, StopReason::TimeBudget));
+
+    // the first criterion to trigger decides, without any the run uses all episodes
+    let mut criteria = (QChange::new(1e-3, 5), PolicyStability::new(3, 1));
+    let (stats, reason) =
+        runner.run_until(&mdp, &mut agent, 10_000, &mut rng, &mut criteria, &mut ());
+    assert_ne!(reason, StopReason::MaxEpisodes);
+    assert!(stats.len() < 10_000);
+
+    let (stats, reason) = runner.run_until(&mdp, &mut agent, 3, &mut rng, &mut (), &mut ());
+    assert_eq!((stats.len(), reason), (3, StopReason::MaxEpisodes));
+}
+
+#[test]
+fn test_executor() {
+    let mdp = create_test_mdp();
+    let train = |index: usize, rng: &mut rand_chacha::ChaCha20Rng| {
+        let q_map = QLearning::new(0.1, 0.1, 5).run(&mdp, 20, rng);
+        (index, q_map.values().sum::<f64>())
+    };
+
+    // the results do not depend on the number of threads
+    let results: Vec<_> = (1..=4)
+        .map(|threads| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap()
+                .install(|| run_seeds(3, 8, train))
+        })
+        .collect();
+    assert!(results.iter().all(|result| *result == results[0]));
+    assert_eq!(
+        results[0]
+            .iter()
+            .map(|(index, _)| *index)
+            .collect::<Vec<_>>(),
+        (0..8).collect::<Vec<_>>()
+    );
+
+    // every job gets the same seeds
+    let grouped = run_jobs(&[0, 1], 3, 8, |_, index, rng| train(index, rng));
+    assert_eq!(grouped, vec![results[0].clone(), results[0].clone()]);
+}
+
+#[test]
+fn test_eval_report() {
+    let mdp = create_test_mdp();
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+
+    // action 1 keeps the agent in state 0 until max_steps
+    let mut stay = FnPolicy::new(|_, _: &[IndexAction]| Some(IndexAction(1)));
+    let report = evaluate(&mdp, &mut stay, 4, 3, &mut rng);
+    let gamma = mdp.get_discount_factor();
+    assert_eq!(report.returns, vec![-3.0; 4]);
+    assert_f64_near!(report.discounted_mean, -(1.0 + gamma + gamma * gamma));
+    assert_eq!(report.lengths, vec![3; 4]);
+    assert_f64_near!(report.std_dev, 0.0);
+    assert_f64_near!(report.truncated_fraction, 1.0);
+
+    // going for the terminal state ends most episodes early
+    let mut go = FnPolicy::new(|_, _: &[IndexAction]| Some(IndexAction(0)));
+    let report = evaluate(&mdp, &mut go, 50, 100, &mut rng);
+    assert!(report.truncated_fraction < 1.0);
+    assert!(report.percentile(0.0) <= report.percentile(50.0));
+    assert!(report.percentile(50.0) <= report.percentile(100.0));
+    let (lower, upper) = report.bootstrap_ci(0.95, 200, &mut rng);
+    assert!(lower <= report.mean && report.mean <= upper);
+    assert_f64_near!(report.std_error, report.std_dev / 50f64.sqrt());
+
+    // the plain evaluators report the mean of the same rollouts
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(1);
+    let report = evaluate(&mdp, &mut go, 10, 100, &mut rng);
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(1);
+    assert_f64_near!(
+        evaluate_policy(&mdp, &mut go, 10, 100, &mut rng),
+        report.mean
+    );
+}
+
+#[test]
+fn test_off_policy_evaluation() {
+    let mdp = create_test_mdp();
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let gamma = mdp.get_discount_factor();
+    let episodes = collect_episodes(&mdp, &mut RandomPolicy, 2000, 5, &mut rng);
+    assert!(episodes
+        .iter()
+        .flatten()
+        .all(|step| step.behaviour_probability == 0.5));
+
+    // evaluating the logging policy itself weighs every episode with 1
+    let logged_mean = episodes
+        .iter()
+        .map(|episode| {
+            episode
+                .iter()
+                .rev()
+                .fold(0.0, |ret, step| step.reward + gamma * ret)
+        })
+        .sum::<f64>()
+        / episodes.len() as f64;
+    assert_f64_near!(
+        importance_sampling(&episodes, &RandomPolicy, gamma),
+        logged_mean
+    );
+    assert_f64_near!(
+        weighted_importance_sampling(&episodes, &RandomPolicy, gamma),
+        logged_mean
+    );
+
+    // all estimators land near the on-policy value of a different policy
+    let mut target = FnPolicy::new(|_, _: &[IndexAction]| Some(IndexAction(0)));
+    let on_policy = evaluate(&mdp, &mut target, 2000, 5, &mut rng).discounted_mean;
+    let estimates = estimate_all(&episodes, &target, gamma, 1e-9, 100);
+    for estimate in [
+        estimates.importance_sampling,
+        estimates.weighted_importance_sampling,
+        estimates.per_decision_importance_sampling,
+        estimates.direct_method,
+        estimates.doubly_robust,
+        estimates.weighted_doubly_robust,
+    ] {
+        assert!((estimate - on_policy).abs() < 0.1 * on_policy.abs());
+    }
+}
+
+#[test]
+fn test_analysis() {
+    // the value the optimal episodes benchmark used to hardcode
+    let grid_world = crate::envs::grid_world::build_mdp().unwrap();
+    assert_f64_near!(optimal_return(&grid_world, 1e-9), -13.0);
+
+    let mdp = create_test_mdp();
+    let optimal = optimal_q_map(&mdp, 1e-9);
+    let report = suboptimality(&mdp, &optimal, 1e-9);
+    assert!(report.max_q_error < 1e-6);
+    assert_f64_near!(report.optimal_action_fraction, 1.0);
+    assert!(report.max_value_loss < 1e-6);
+
+    // looping in state 0 is never optimal
+    let mut q_map = optimal.clone();
+    q_map.insert((IndexState(0), IndexAction(1)), 1e6);
+    let report = suboptimality(&mdp, &q_map, 1e-9);
+    assert!(report.max_q_error > 1e5);
+    assert_f64_near!(report.optimal_action_fraction, 0.5);
+    assert!(report.value_loss[&IndexState(0)] > 0.0);
+
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let mut agent = TabularAgent::from_mdp(QLearning::new(0.1, 0.1, 5), &mdp);
+    let mut regret = RegretTracker::from_mdp(&mdp, 1e-9);
+    Runner::new(5).run_observed(&mdp, &mut agent, 10, &mut rng, &mut regret);
+    assert_eq!(regret.cumulative_regret.len(), 10);
+    assert_f64_near!(regret.total(), regret.regret.iter().sum::<f64>());
+}
+
+#[test]
+fn test_recorder() {
+    let mdp = create_test_mdp();
+    let dir = std::env::temp_dir();
+    let recorder = Recorder::new(&mdp);
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let mut agent = TabularAgent::from_mdp(QLearning::new(0.1, 0.1, 5), &mdp);
+    let stats = Runner::new(5).run(&recorder, &mut agent, 10, &mut rng);
+    let log = recorder.into_log();
+
+    // one row per step, the last row of an episode is terminal or truncated
+    let episodes = log.episodes();
+    assert_eq!(episodes.len(), 10);
+    for (episode, stats) in episodes.iter().zip(stats.iter()) {
+        assert_eq!(episode.len(), stats.steps);
+        let last = episode.last().unwrap();
+        assert_eq!(last.terminal, stats.terminated);
+        assert_eq!(last.truncated, !stats.terminated);
+        assert!(episode[..episode.len() - 1]
+            .iter()
+            .all(|record| !record.terminal && !record.truncated));
+    }
+    assert_eq!(log.to_dataset().len(), log.len());
+
+    let path = dir.join("mdp_test_log.jsonl");
+    log.save_jsonl(&path).unwrap();
+    assert_eq!(EpisodeLog::load_jsonl(&path).unwrap(), log);
+    let path = dir.join("mdp_test_log.csv");
+    log.save_csv(&path).unwrap();
+    assert_eq!(EpisodeLog::load_csv(&path).unwrap(), log);
+
+    // replays return the recorded rows in order
+    let replayed: Vec<_> = Replay::new(log.clone()).collect();
+    assert_eq!(replayed, log.records);
+}
+
+#[test]
+fn test_blackjack() {
+    let env = BlackjackMdp::new();
+    assert_eq!(env.get_all_state_actions().len(), 410);
+    let total: f64 = env.initial_distribution().iter().map(|(p, _)| p).sum();
+    assert!((total - 1.0).abs() < 1e-9);
+    // an ace and a ten valued card in either order
+    let naturals: f64 = env
+        .initial_distribution()
+        .iter()
+        .filter(|(_, state)| matches!(state, BlackjackState::Natural(_)))
+        .map(|(p, _)| p)
+        .sum();
+    assert!((naturals - 2.0 * (1.0 / 13.0) * (4.0 / 13.0)).abs() < 1e-9);
+
+    let model = env.build_model().unwrap();
+    model.transitions.values().for_each(|transitions| {
+        let total: f64 = transitions.iter().map(|(p, _, _)| p).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+    });
+
+    // parts of the optimal policy of figure 5.2 in Sutton & Barto
+    let policy = TabularPolicy::from_q_map(&optimal_q_map(&model, 1e-9));
+    let action = |player, dealer, usable_ace| {
+        policy.actions[&BlackjackState::Running(player, dealer, usable_ace)]
+    };
+    assert_eq!(action(12, 2, false), BlackjackAction::Hit);
+    assert_eq!(action(12, 4, false), BlackjackAction::Stick);
+    assert_eq!(action(16, 6, false), BlackjackAction::Stick);
+    assert_eq!(action(16, 7, false), BlackjackAction::Hit);
+    assert_eq!(action(18, 8, true), BlackjackAction::Stick);
+    assert_eq!(action(18, 9, true), BlackjackAction::Hit);
+    (1..=10).for_each(|dealer| {
+        assert_eq!(action(17, dealer, false), BlackjackAction::Stick);
+        assert_eq!(action(17, dealer, true), BlackjackAction::Hit);
+    });
+
+    // a natural only draws against a dealer natural, a ten showing hides an ace with 1/13
+    assert_eq!(
+        model.transitions[&(BlackjackState::Natural(10), BlackjackAction::Stick)],
+        vec![
+            (12.0 / 13.0, BlackjackState::Win, 1.0),
+            (1.0 / 13.0, BlackjackState::Draw, 0.0)
+        ]
+    );
+    // a three card 21 draws with any other dealer 21 but loses to a dealer natural
+    let loss = |state| {
+        model.transitions[&(state, BlackjackAction::Stick)]
+            .iter()
+            .filter(|(_, next_state, _)| *next_state == BlackjackState::Loss)
+            .map(|(p, _, _)| p)
+            .sum::<f64>()
+    };
+    assert!((loss(BlackjackState::Running(21, 1, false)) - 4.0 / 13.0).abs() < 1e-9);
+    assert_eq!(loss(BlackjackState::Running(21, 6, true)), 0.0);
+
+    // sampled games agree with the model
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let state = BlackjackState::Running(18, 10, false);
+    let expected: f64 = model.transitions[&(state, BlackjackAction::Stick)]
+        .iter()
+        .map(|(p, _, reward)| p * reward)
+        .sum();
+    let sampled = (0..20_000)
+        .map(|_| {
+            env.perform_action((state, BlackjackAction::Stick), &mut rng)
+                .1
+        })
+        .sum::<f64>()
+        / 20_000.0;
+    assert!((expected - sampled).abs() < 0.02);
+    let initial = (0..2_000)
+        .map(|_| env.get_initial_state(&mut rng))
+        .collect::<HashSet<_>>();
+    assert!(initial.iter().all(|state| env
+        .get_possible_actions(*state)
+        .iter()
+        .all(|action| model.transitions.contains_key(&(*state, *action)))));
+    assert!(initial
+        .iter()
+        .any(|state| matches!(state, BlackjackState::Natural(_))));
+
+    // first-visit Monte Carlo averages the returns that follow a pair
+    let q_map = MonteCarlo::new(1.0, 100).run(&env, 200_000, &mut rng);
+    let optimal = optimal_q_map(&model, 1e-9);
+    let q = q_map[&(state, BlackjackAction::Stick)];
+    assert!((q - optimal[&(state, BlackjackAction::Stick)]).abs() < 0.05);
+}
+
+#[test]
+fn test_grid() {
+    let map = "
+        S.#.
+        .$#G
+        ....
+    ";
+    assert!(GridConfig::parse(map).unwrap().build().is_err());
+    assert!(GridConfig::parse("S..\n..").is_err());
+    let config = GridConfig::parse(map)
+        .unwrap()
+        .with_reward_cell('$', 5.0)
+        .with_goal_reward(10.0);
+    let mdp = config.build().unwrap();
+    // walls are no states
+    assert_eq!(mdp.states_actions.len(), 10 * 4);
+    assert!(mdp.terminal_states.contains(&GridState(1, 3)));
+    let transition = |mdp: &crate::mdp::MapMdp<GridState, GridAction>, state, action| {
+        mdp.transitions[&(state, action)].clone()
+    };
+    assert_eq!(
+        transition(&mdp, GridState(0, 1), GridAction::Right),
+        vec![(1.0, GridState(0, 1), -1.0)]
+    );
+    assert_eq!(
+        transition(&mdp, GridState(0, 1), GridAction::Down),
+        vec![(1.0, GridState(1, 1), 5.0)]
+    );
+
+    // king's moves and wind
+    let mdp = config
+        .clone()
+        .with_connectivity(Connectivity::Eight)
+        .with_wind(
+            GridState(2, 2),
+            Wind {
+                direction: GridAction::Up,
+                strengths: vec![(0.5, 1), (0.5, 2)],
+            },
+        )
+        .build()
+        .unwrap();
+    assert_eq!(mdp.states_actions.len(), 10 * 8);
+    assert_eq!(
+        transition(&mdp, GridState(2, 2), GridAction::Right),
+        vec![(0.5, GridState(0, 3), -1.0), (0.5, GridState(1, 3), 10.0)]
+    );
+    assert_eq!(
+        transition(&mdp, GridState(2, 2), GridAction::UpLeft),
+        vec![(1.0, GridState(0, 1), -1.0)]
+    );
+
+    // the cliff walking variants are maps now
+    let cliff_walking = crate::envs::cliff_walking::build_mdp().unwrap();
+    assert_f64_near!(optimal_return(&cliff_walking, 1e-9), -12.0);
+    let slippery = crate::envs::slippery_cliff_walking::build_mdp(0.1).unwrap();
+    let transitions = transition(&slippery, GridState(2, 5), GridAction::Right);
+    assert_eq!(transitions.len(), 2);
+    assert!(transitions.contains(&(0.1, GridState(3, 5), -100.0)));
+
+    // the grid world pays the goal reward for entering the goal from any side, the old builder
+    // charged a step for moving up into it. The optimum is the same, 13 steps and the goal.
+    let grid_world = crate::envs::grid_world::build_mdp().unwrap();
+    assert_eq!(
+        transition(&grid_world, GridState(1, 11), GridAction::Up),
+        vec![(1.0, GridState(0, 11), 0.0)]
+    );
+    assert_eq!(
+        transition(&grid_world, GridState(0, 10), GridAction::Right),
+        vec![(1.0, GridState(0, 11), 0.0)]
+    );
+    assert_f64_near!(optimal_return(&grid_world, 1e-9), -13.0);
+}
+
+#[test]
+fn test_windy_grid_world() {
+    use crate::envs::windy_grid_world::build_mdp;
+
+    let windy = build_mdp(Connectivity::Four, false).unwrap();
+    assert_f64_near!(optimal_return(&windy, 1e-9), -15.0);
+    let king = build_mdp(Connectivity::Eight, false).unwrap();
+    assert_f64_near!(optimal_return(&king, 1e-9), -7.0);
+
+    // the stochastic wind spreads moves out of windy columns over three rows
+    let stochastic = build_mdp(Connectivity::Four, true).unwrap();
+    let transitions = &stochastic.transitions[&(GridState(3, 6), GridAction::Left)];
+    let rows: Vec<usize> = transitions.iter().map(|(_, state, _)| state.0).collect();
+    assert_eq!(rows, vec![0, 1, 2]);
+    assert!((optimal_return(&stochastic, 1e-9) + 20.628).abs() < 1e-3);
+    let stochastic_king = build_mdp(Connectivity::Eight, true).unwrap();
+    assert!((optimal_return(&stochastic_king, 1e-9) + 11.820).abs() < 1e-3);
+}
+
+#[test]
+fn test_car_rental() {
+    use crate::envs::car_rental::{CarRentalAction, CarRentalConfig, CarRentalState};
+
+    let config = CarRentalConfig::new().with_max_cars(8).with_max_move(3);
+    let mdp = config.build().unwrap();
+    // 81 states, each location can give min(3, cars) cars
+    assert_eq!(mdp.transitions.len(), 81 + 2 * 9 * (1 + 2 + 3 * 6));
+    for transitions in mdp.transitions.values() {
+        let total: f64 = transitions.iter().map(|(prob, _, _)| prob).sum();
+        assert!((total - 1.0).abs() < 1e-12);
+    }
+    // moves are only possible with the cars at hand
+    assert_eq!(
+        config.actions(0, 1),
+        vec![CarRentalAction(-1), CarRentalAction(0)]
+    );
+
+    // both solvers agree and nothing is moved without cars to move
+    let (policy, policy_values) = policy_iteration(&mdp, 1e-9, 10_000);
+    let values = value_iteration(&mdp, 1e-9);
+    for (state, value) in values.iter() {
+        assert!((value - policy_values[state]).abs() < 1e-6);
+    }
+    assert_eq!(policy[&CarRentalState(0, 0)], CarRentalAction(0));
+    assert!(policy[&CarRentalState(8, 0)].0 > 0);
+
+    // the shuttle makes the first move free
+    let shuttle = config.clone().with_free_shuttle(true).build().unwrap();
+    let key = (CarRentalState(4, 4), CarRentalAction(1));
+    let reward = |mdp: &crate::mdp::MapMdp<CarRentalState, CarRentalAction>| -> f64 {
+        mdp.transitions[&key]
+            .iter()
+            .map(|(prob, _, reward)| prob * reward)
+            .sum()
+    };
+    assert!((reward(&shuttle) - reward(&mdp) - config.move_cost).abs() < 1e-9);
+    let fee = config.with_parking_fee(4, 4.0).build().unwrap();
+    assert!((reward(&mdp) - reward(&fee) - 4.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_gamblers_problem() {
+    use crate::envs::gamblers_problem::{build_mdp, GamblerAction, GamblerState};
+
+    let mdp = build_mdp(100, 0.4).unwrap();
+    assert_eq!(mdp.transitions.len(), 2500);
+    let values = value_iteration(&mdp, 1e-12);
+    assert!((values[&GamblerState(50)] - 0.4).abs() < 1e-9);
+    assert!((values[&GamblerState(75)] - (0.4 + 0.6 * 0.4)).abs() < 1e-9);
+
+    // the full set of optimal stakes, not one of them at random. The coin is undiscounted, so value
+    // iteration runs far tighter than the tolerance for ties.
+    let optimal = optimal_actions(&mdp, 1e-14, 1e-8);
+    assert_eq!(optimal.len(), 99);
+    let stakes = |capital: usize| -> Vec<usize> {
+        optimal[&GamblerState(capital)]
+            .iter()
+            .map(|action| action.0)
+            .collect()
+    };
+    assert_eq!(stakes(50), vec![50]);
+    assert_eq!(stakes(51), vec![1, 49]);
+    assert_eq!(stakes(26), vec![1, 24, 26]);
+    assert_eq!(stakes(12), vec![12]);
+
+    // the greedy policy picks one of them
+    let q_map = optimal_q_map(&mdp, 1e-9);
+    let greedy = TabularPolicy::from_q_map(&q_map);
+    for (state, actions) in optimal.iter() {
+        assert!(actions.contains(&greedy.actions[state]));
+    }
+
+    // with a favourable coin betting a single dollar is always optimal
+    let favourable = optimal_actions(&build_mdp(100, 0.6).unwrap(), 1e-14, 1e-8);
+    assert!(favourable
+        .values()
+        .all(|actions| actions.contains(&GamblerAction(1))));
+}
+
+#[test]
+fn test_intersection_model() {
+    let max_cars = 4;
+    let sampled = MyIntersectionMdp::new(0.6, 0.2, max_cars);
+    let model = crate::envs::intersection::build_mdp(&sampled).unwrap();
+    assert_eq!(model.discount_factor, sampled.get_discount_factor());
+    assert_eq!(
+        model.states_actions.len(),
+        sampled.get_all_state_actions().len()
+    );
+
+    // sampled next states follow the explicit probabilities
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let state = IntersectionState {
+        light_state: LightState::NorthSouthOpen,
+        ns_cars: 2,
+        ew_cars: 4,
+    };
+    for action in [LightAction::Stay, LightAction::Change] {
+        let mut counts: BTreeMap<IndexState, f64> = BTreeMap::new();
+        for _ in 0..10_000 {
+            let (next_state, _) = sampled.perform_action((state, action), &mut rng);
+            *counts.entry(build_state(next_state, max_cars)).or_default() += 1.0 / 10_000.0;
+        }
+        let transitions = &model.transitions[&(build_state(state, max_cars), build_action(action))];
+        assert_eq!(transitions.len(), counts.len());
+        transitions.iter().for_each(|(prob, next_state, _)| {
+            assert!((prob - counts[next_state]).abs() < 0.02);
+        });
+    }
+
+    // the exact optimum achieves its value on the sampled environment
+    let optimal = optimal_q_map(&model, 1e-9);
+    let optimal_value = value_iteration(&model, 1e-9)[&model.initial_state];
+    let q_map = to_intersection_q_map(&optimal, max_cars);
+    let report = evaluate(
+        &sampled,
+        &mut GreedyPolicy::new(&q_map),
+        2000,
+        100,
+        &mut rng,
+    );
+    assert!((report.discounted_mean - optimal_value).abs() < 3.0 * report.std_error + 0.01);
+}
+
+#[test]
+fn test_traffic() {
+    let mdp = TrafficMdp::new([0.5, 0.5, 0.3, 0.3]).with_max_cars(2);
+    // 81 queue configurations, the green phases have two actions, the clearance phases one
+    assert_eq!(mdp.get_all_state_actions().len(), 81 * 8);
+    assert!(mdp
+        .get_all_state_actions()
+        .iter()
+        .all(|(state, action)| mdp.get_possible_actions(*state).contains(action)));
+
+    // a full cycle passes yellow and all-red before the other light turns green
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let mut state = mdp.get_initial_state(&mut rng);
+    let mut phases = vec![];
+    for action in [Light::Yellow, Light::AllRed, Light::Red, Light::Yellow] {
+        assert_eq!(
+            mdp.get_possible_actions(state).len(),
+            if phases.len() % 3 == 0 { 2 } else { 1 }
+        );
+        state = mdp.perform_action((state, action), &mut rng).0;
+        phases.push(state.lights);
+    }
+    assert_eq!(
+        phases,
+        vec![
+            [Light::Yellow, Light::Red],
+            [Light::AllRed, Light::Red],
+            [Light::Red, Light::Green],
+            [Light::Red, Light::Yellow],
+        ]
+    );
+
+    // queues only shrink on green and never exceed the cap
+    let mdp = TrafficMdp::new([1.0, 1.0, 0.0, 0.0])
+        .with_max_cars(2)
+        .with_reward(TrafficReward::Throughput);
+    let mut state = mdp.get_initial_state(&mut rng);
+    state.vehicles = [0, 0, 2, 2];
+    let (next_state, reward) = mdp.perform_action((state, Light::Green), &mut rng);
+    assert_eq!(next_state.vehicles, [0, 0, 2, 2]);
+    assert_f64_near!(reward, 0.0);
+    let (next_state, _) = mdp.perform_action((next_state, Light::Yellow), &mut rng);
+    assert_eq!(next_state.vehicles, [1, 1, 2, 2]);
+    let (next_state, _) = mdp.perform_action((next_state, Light::AllRed), &mut rng);
+    let (next_state, reward) = mdp.perform_action((next_state, Light::Red), &mut rng);
+    assert_eq!(next_state.vehicles, [2, 2, 1, 1]);
+    assert_f64_near!(reward, 2.0);
+}
+
+#[test]
+fn test_garnet() {
+    // states reachable from the given state with positive probability
+    let reachable = |mdp: &IndexMdp, from: IndexState| {
+        let mut seen = vec![from];
+        let mut i = 0;
+        while i < seen.len() {
+            let state = seen[i];
+            for ((s, _), transitions) in mdp.transitions.iter() {
+                if *s == state {
+                    transitions.iter().for_each(|(_, next_state, _)| {
+                        if !seen.contains(next_state) {
+                            seen.push(*next_state);
+                        }
+                    });
+                }
+            }
+            i += 1;
+        }
+        seen
+    };
+
+    let config = GarnetConfig::new(30, 3, 2)
+        .with_structure(Structure::Absorbing {
+            n_terminal_states: 2,
+        })
+        .with_rewards(Rewards::Sparse { reward: 1.0 })
+        .with_initial_states(4)
+        .with_seed(7);
+    let garnet = config.generate().unwrap();
+    let mdp = &garnet.mdp;
+    assert_eq!(mdp.terminal_states.len(), 2);
+    for state in (0..30).map(IndexState).filter(|s| !mdp.is_terminal(*s)) {
+        assert!(reachable(mdp, state).iter().any(|s| mdp.is_terminal(*s)));
+    }
+    for transitions in mdp.transitions.values() {
+        assert!(transitions.len() <= 2);
+        assert_f64_near!(transitions.iter().map(|(p, _, _)| p).sum::<f64>(), 1.0, 10);
+        transitions.iter().for_each(|(_, next_state, reward)| {
+            assert_eq!(
+                *reward,
+                if mdp.is_terminal(*next_state) {
+                    1.0
+                } else {
+                    0.0
+                }
+            )
+        });
+    }
+    assert_eq!(garnet.initial_distribution.len(), 4);
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let initial_state = garnet.get_initial_state(&mut rng);
+    assert!(garnet
+        .initial_distribution
+        .iter()
+        .any(|(_, state)| *state == initial_state));
+
+    // the stored config reproduces the mdp
+    let json = serde_json::to_string(&config).unwrap();
+    let restored: GarnetConfig = serde_json::from_str(&json).unwrap();
+    assert_eq!(
+        restored.generate().unwrap().mdp.transitions,
+        mdp.transitions
+    );
+
+    let communicating = config
+        .with_structure(Structure::Communicating)
+        .generate()
+        .unwrap()
+        .mdp;
+    for state in (0..30).map(IndexState) {
+        assert_eq!(reachable(&communicating, state).len(), 30);
+    }
+
+    let ergodic = config
+        .with_structure(Structure::Ergodic)
+        .generate()
+        .unwrap()
+        .mdp;
+    assert!(ergodic.transitions.values().all(|t| t.len() == 30));
+}
+
+#[test]
+fn test_exploration_mdps() {
+    let optimal_value = |mdp: &IndexMdp| value_iteration(mdp, 1e-9)[&mdp.initial_state];
+
+    // the values documented next to the builders
+    let river_swim = build_river_swim(6, 0.95).unwrap();
+    assert!((optimal_value(&river_swim) - 2.8016).abs() < 1e-4);
+    let chain = build_n_chain(5, 0.2, 0.99).unwrap();
+    assert!((optimal_value(&chain) - 354.7681).abs() < 1e-4);
+    let lock = build_combination_lock(10, 3, 0.99, 0).unwrap();
+    assert!((optimal_value(&lock) - 0.99f64.powi(9)).abs() < 1e-6);
+    let deep_sea = build_deep_sea(10, 1.0, 0).unwrap();
+    assert!((optimal_value(&deep_sea) - 0.991).abs() < 1e-6);
+
+    // every lock state has exactly one action that does not reset. Without discounting resetting
+    // is optimal as well, so the greedy return is only checked for the discounted lock.
+    assert_f64_near!(optimal_return(&lock, 1e-9), 1.0);
+    for i in 0..10 {
+        let advancing = lock
+            .get_possible_actions(IndexState(i))
+            .iter()
+            .filter(|action| lock.transitions[&(IndexState(i), **action)][0].1 != IndexState(0))
+            .count();
+        assert_eq!(advancing, 1);
+    }
+}
+
+#[test]
+fn test_policies() {
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let actions = [IndexAction(0), IndexAction(1), IndexAction(2)];
+    let q_map = BTreeMap::from([
+        ((IndexState(0), IndexAction(0)), 1.0),
+        ((IndexState(0), IndexAction(1)), 1.0),
+        ((IndexState(0), IndexAction(2)), 0.0),
+    ]);
+
+    // the greedy share is split between the tied actions
+    let mut policy = EpsilonGreedyPolicy::new(&q_map, 0.3);
+    assert_f64_near!(
+        policy.action_probability(IndexState(0), IndexAction(0), &actions),
+        0.45
+    );
+    assert_f64_near!(
+        policy.action_probability(IndexState(0), IndexAction(2), &actions),
+        0.1
+    );
+    assert_ne!(
+        policy.select_action(IndexState(0), &actions, &mut rng),
+        None
+    );
+
+    let softmax = SoftmaxPolicy::new(&q_map, 1.0);
+    let probabilities = softmax.action_probabilities(IndexState(0), &actions);
+    assert_float_absolute_eq!(
+        probabilities.iter().map(|(_, p)| p).sum::<f64>(),
+        1.0,
+        1e-12
+    );
+    assert!(probabilities[0].1 > probabilities[2].1);
+
+    let tabular = TabularPolicy::from_q_map(&q_map);
+    assert_eq!(tabular.actions.get(&IndexState(0)), Some(&IndexAction(0)));
+
+    // hand-written controllers run through the same evaluator as learned policies
+    let mdp = create_test_mdp();
+    let mut always_stay = FnPolicy::new(|_, _: &[IndexAction]| Some(IndexAction(1)));
+    let avg_reward = evaluate_policy(&mdp, &mut always_stay, 2, 10, &mut rng);
+    assert_f64_near!(avg_reward, -10.0);
+}
+
+#[test]
+fn test_persistence() {
+    let mdp = create_test_mdp();
+    let dir = std::env::temp_dir();
+    let metadata = Metadata::new("test", 20).with_seed(0);
+
+    let mut algo = DynaQ::new(0.1, 0.1, 5, 100, false, true, &mdp);
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let q_map = algo.run(&mdp, 20, &mut rng);
+
+    let path = dir.join("mdp_test_q_map.json");
+    save_q_map(&path, &metadata, &q_map).unwrap();
+    let (loaded_metadata, loaded_q_map) = load_q_map(&path).unwrap();
+    assert_eq!(loaded_metadata, metadata);
+    assert_eq!(loaded_q_map, q_map);
+
+    // a file of another kind is rejected
+    assert!(
+        load_learner::<DynaQ<IndexState, IndexAction>, IndexState, IndexAction>(&path).is_err()
+    );
+
+    // training resumed from disk matches uninterrupted training
+    let mut algo = DynaQ::new(0.1, 0.1, 5, 100, false, true, &mdp);
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
+    let mut q_map = algo.run(&mdp, 10, &mut rng);
+    let path = dir.join("mdp_test_learner.json");
+    save_learner(&path, &metadata, &algo, &q_map).unwrap();
+    let (_, mut loaded, mut loaded_q_map): (_, DynaQ<_, _>, _) = load_learner(&path).unwrap();
+
+    let mut resumed_rng = rng.clone();
+    algo.run_with_q_map(&mdp, 10, &mut rng, &mut q_map);
+    loaded.run_with_q_map(&mdp, 10, &mut resumed_rng, &mut loaded_q_map);
+    assert_eq!(loaded_q_map, q_map);
+}
+
 fn create_test_mdp() -> IndexMdp {
     let transition_probabilities: BTreeMap<(IndexState, IndexAction), Vec<Transition>> =
         BTreeMap::from([
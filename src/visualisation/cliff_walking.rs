@@ -73,6 +73,10 @@ impl eframe::App for MyApp {
                             Some(CliffWalkingAction::Down) => "⬇",
                             Some(CliffWalkingAction::Left) => "⬅",
                             Some(CliffWalkingAction::Right) => "➡",
+                            Some(CliffWalkingAction::UpLeft) => "⬉",
+                            Some(CliffWalkingAction::UpRight) => "⬈",
+                            Some(CliffWalkingAction::DownLeft) => "⬋",
+                            Some(CliffWalkingAction::DownRight) => "⬊",
                             None => panic!("you fucked up"),
                         };
                         // let label = format!("{}", state_index);
@@ -1,4 +1,5 @@
 use std::collections::BTreeMap;
+use std::path::PathBuf;
 
 use iced::alignment::Horizontal;
 use iced::alignment::Vertical;
@@ -21,27 +22,50 @@ use crate::algorithms::q_learning::QLearning;
 use crate::envs::my_intersection::LightAction;
 use crate::envs::my_intersection::LightState;
 use crate::mdp::GenericMdp;
+use crate::persistence::{load_q_map, save_q_map, Metadata};
+use crate::recorder::{EpisodeLog, JsonlWriter, Replay, StepRecord};
 
-use crate::multiagent::intersection::{MAIntersectionRunnerSingleAgentRL, MAState};
+use crate::multiagent::intersection::{Action, MAIntersectionRunnerSingleAgentRL, MAState};
 
-pub fn main() -> iced::Result {
+const Q_MAP_1_PATH: &str = "results/ma_intersection_q_map_1.json";
+const Q_MAP_2_PATH: &str = "results/ma_intersection_q_map_2.json";
+
+// simulates live unless a log to replay is given, live runs can be recorded to a JSONL log
+#[derive(Debug, Clone, Default)]
+pub struct Flags {
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+}
+
+pub fn main(flags: Flags) -> iced::Result {
     MAIntersection::run(Settings {
         antialiasing: true,
+        flags,
         ..Settings::default()
     })
 }
 
 struct MAIntersection {
-    mdp: MAIntersectionRunnerSingleAgentRL<QLearning>,
-    q_map_1: BTreeMap<(MAState, LightAction), f64>,
-    q_map_2: BTreeMap<(MAState, LightAction), f64>,
+    source: Source,
     state: MAState,
     steps: usize,
     total_reward: f64,
-    rng: ChaCha20Rng,
     cache: Cache,
 }
 
+enum Source {
+    Live(Box<Live>),
+    Replay(Replay<MAState, Action>),
+}
+
+struct Live {
+    mdp: MAIntersectionRunnerSingleAgentRL<QLearning>,
+    q_map_1: BTreeMap<(MAState, LightAction), f64>,
+    q_map_2: BTreeMap<(MAState, LightAction), f64>,
+    rng: ChaCha20Rng,
+    writer: Option<JsonlWriter>,
+}
+
 #[derive(Debug, Clone, Copy)]
 enum Message {
     Tick(time::OffsetDateTime),
@@ -51,34 +75,26 @@ impl Application for MAIntersection {
     type Executor = executor::Default;
     type Message = Message;
     type Theme = Theme;
-    type Flags = ();
-
-    fn new(_flags: ()) -> (Self, Command<Message>) {
-        let max_steps = 5000;
-        let q_algo_1 = QLearning::new(0.1, 0.1, max_steps);
-        let q_algo_2 = QLearning::new(0.1, 0.1, max_steps);
-
-        let mdp = MAIntersectionRunnerSingleAgentRL::new(
-            0.1, 0.6, 0.1, 0.6, 10, q_algo_1, q_algo_2, max_steps,
-        );
-
-        let (mut q_map_1, mut q_map_2) = mdp.gen_q_maps();
-        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
-
-        println!("Learning...");
-        mdp.run(1000, &mut q_map_1, &mut q_map_2, &mut rng);
-        let state = mdp.mdp.get_initial_state(&mut rng);
-        println!("Done!");
+    type Flags = Flags;
+
+    fn new(flags: Flags) -> (Self, Command<Message>) {
+        let (source, state) = match flags.replay {
+            Some(path) => {
+                let log: EpisodeLog<MAState, Action> =
+                    EpisodeLog::load_jsonl(&path).expect("could not load log");
+                println!("Replaying {} steps from {}", log.len(), path.display());
+                let state = log.records.first().expect("log is empty").state;
+                (Source::Replay(Replay::new(log)), state)
+            }
+            None => live_source(flags.record),
+        };
 
         (
             MAIntersection {
-                mdp,
-                q_map_1,
-                q_map_2,
+                source,
                 state,
                 steps: 0,
                 total_reward: 0.0,
-                rng,
                 cache: Default::default(),
             },
             Command::none(),
@@ -92,12 +108,43 @@ impl Application for MAIntersection {
     fn update(&mut self, message: Message) -> Command<Message> {
         match message {
             Message::Tick(_) => {
-                let (next_state, reward) =
-                    self.mdp
-                        .single_step(self.state, &self.q_map_1, &self.q_map_2, &mut self.rng);
-                self.state = next_state;
+                let record = match &mut self.source {
+                    Source::Live(live) => {
+                        let Live {
+                            mdp,
+                            q_map_1,
+                            q_map_2,
+                            rng,
+                            writer,
+                        } = live.as_mut();
+                        let (action, next_state, reward) =
+                            mdp.single_step(self.state, q_map_1, q_map_2, rng);
+                        let record = StepRecord {
+                            episode: 0,
+                            t: self.steps,
+                            state: self.state,
+                            action,
+                            reward,
+                            next_state,
+                            terminal: mdp.mdp.is_terminal(next_state),
+                            truncated: false,
+                        };
+                        if let Some(writer) = writer {
+                            if let Err(err) = writer.write(&record) {
+                                println!("Could not record step: {err:#}");
+                            }
+                        }
+                        record
+                    }
+                    // the last frame stays when the log is over
+                    Source::Replay(replay) => match replay.next() {
+                        Some(record) => record,
+                        None => return Command::none(),
+                    },
+                };
+                self.state = record.next_state;
                 self.steps += 1;
-                self.total_reward += reward;
+                self.total_reward += record.reward;
                 self.cache.clear();
             }
         }
@@ -131,6 +178,67 @@ impl Application for MAIntersection {
     }
 }
 
+fn live_source(record: Option<PathBuf>) -> (Source, MAState) {
+    let max_steps = 5000;
+    let q_algo_1 = QLearning::new(0.1, 0.1, max_steps);
+    let q_algo_2 = QLearning::new(0.1, 0.1, max_steps);
+
+    let mut mdp = MAIntersectionRunnerSingleAgentRL::new(
+        0.1, 0.6, 0.1, 0.6, 10, q_algo_1, q_algo_2, max_steps,
+    );
+
+    let seed = 0;
+    let episodes = 1000;
+    let metadata = Metadata::new("ma_intersection", episodes)
+        .with_parameter("new_car_prob_ns_1", 0.1)
+        .with_parameter("new_car_prob_ew_1", 0.6)
+        .with_parameter("new_car_prob_ns_2", 0.1)
+        .with_parameter("new_car_prob_ew_2", 0.6)
+        .with_parameter("max_cars", 10.0)
+        .with_seed(seed);
+    let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(seed);
+
+    // reuse the q_maps of an earlier launch with the same settings
+    let (q_map_1, q_map_2) = match (load_q_map(Q_MAP_1_PATH), load_q_map(Q_MAP_2_PATH)) {
+        (Ok((metadata_1, q_map_1)), Ok((metadata_2, q_map_2)))
+            if metadata_1 == metadata && metadata_2 == metadata =>
+        {
+            println!("Loaded q_maps from {Q_MAP_1_PATH} and {Q_MAP_2_PATH}");
+            (q_map_1, q_map_2)
+        }
+        _ => {
+            let (mut q_map_1, mut q_map_2) = mdp.gen_q_maps();
+            println!("Learning...");
+            mdp.run(episodes, &mut q_map_1, &mut q_map_2, &mut rng);
+            println!("Done!");
+
+            if let Err(err) = save_q_map(Q_MAP_1_PATH, &metadata, &q_map_1)
+                .and_then(|_| save_q_map(Q_MAP_2_PATH, &metadata, &q_map_2))
+            {
+                println!("Could not save q_maps: {err:#}");
+            }
+            (q_map_1, q_map_2)
+        }
+    };
+    let state = mdp.mdp.get_initial_state(&mut rng);
+
+    let writer = record.map(|path| {
+        println!("Recording to {}", path.display());
+        JsonlWriter::create(path).expect("could not create log")
+    });
+
+    (
+        Source::Live(Box::new(Live {
+            mdp,
+            q_map_1,
+            q_map_2,
+            rng,
+            writer,
+        })),
+        state,
+    )
+}
+
 impl<Message> canvas::Program<Message, Renderer> for MAIntersection {
     type State = ();
 
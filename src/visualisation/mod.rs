@@ -1,7 +1,7 @@
 use rand::SeedableRng;
 
 use crate::{
-    algorithms::{sarsa::Sarsa, GenericStateActionAlgorithm},
+    algorithms::{sarsa::Sarsa, TabularAlgorithm},
     envs,
 };
 
@@ -26,7 +26,7 @@ pub fn vis_test() {
 
     let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(0);
 
-    let algo = Sarsa::new(alpha, epsilon, learning_max_steps);
+    let mut algo = Sarsa::new(alpha, epsilon, learning_max_steps);
     let q_map = algo.run(&cliff_walking_mdp, learning_episodes, &mut rng);
 
     cliff_walking::show_strategy(&cliff_walking_mdp, &q_map).expect("some gui error");